@@ -1,4 +1,4 @@
-use super::hulls::HullKey;
+use super::hulls::{HullKey, HullSocket};
 use super::munitions::{MunitionFamily, WeaponRole};
 use super::{Buff, Buffs, Faction, MissileSize};
 use crate::utils::{ContiguousExt, Size};
@@ -50,6 +50,12 @@ impl Component {
     }
   }
 
+  /// Whether or not this component can be installed in the given socket of the given hull,
+  /// taking into account its faction, kind and size.
+  pub fn is_legal_in(self, hull: HullKey, hull_socket: &HullSocket) -> bool {
+    self.kind == hull_socket.kind && self.can_fit_in(hull_socket.size) && self.is_usable_on(hull)
+  }
+
   pub const fn cost(self, socket_size: Size) -> Option<usize> {
     let tiling_quantity = self.tiling_quantity(socket_size);
     if tiling_quantity == 0 { None } else { Some(self.point_cost * tiling_quantity) }
@@ -286,6 +292,16 @@ pub enum SigType {
   Comms
 }
 
+impl SigType {
+  pub const fn to_str(self) -> &'static str {
+    match self {
+      Self::ElectroOptical => "electro_optical",
+      Self::Radar => "radar",
+      Self::Comms => "comms"
+    }
+  }
+}
+
 impl FromStr for SigType {
   type Err = ParseSigTypeError;
 
@@ -299,6 +315,12 @@ impl FromStr for SigType {
   }
 }
 
+impl fmt::Display for SigType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(self.to_str())
+  }
+}
+
 #[derive(Debug, Error, Clone, Copy, PartialEq, Eq, Default)]
 #[error("failed to parse signature type")]
 pub struct ParseSigTypeError;
@@ -373,10 +395,7 @@ pub enum CompoundingCostClass {
 
 impl CompoundingCostClass {
   pub const fn first_instance_free(self) -> bool {
-    match self {
-      Self::Berthing | Self::Magazine => true,
-      _ => false
-    }
+    matches!(self, Self::Berthing | Self::Magazine)
   }
 
   pub const fn multiplier(self) -> usize {
@@ -3,7 +3,7 @@ pub mod config;
 use crate::format::key::Key;
 use crate::utils::{ContiguousExt, Size};
 use super::{Buff, Direction, Faction};
-use super::components::ComponentKind;
+use super::components::{ComponentKey, ComponentKind};
 use self::config::HullConfigTemplateFull;
 
 use bytemuck::Contiguous;
@@ -76,7 +76,7 @@ impl HullSocket {
 
   #[inline]
   const fn module(save_key: Key, size: Size, desirability: f32) -> Self {
-    HullSocket { save_key, kind: ComponentKind::Mount, size, direction: None, desirability }
+    HullSocket { save_key, kind: ComponentKind::Module, size, direction: None, desirability }
   }
 }
 
@@ -136,6 +136,15 @@ impl HullKey {
       Self::MoorlineLineShip => &MOORLINE_LINE_SHIP
     }
   }
+
+  /// Every component that is legal to install in the socket with the given key,
+  /// or `None` if this hull has no such socket.
+  pub fn legal_components(self, socket_key: Key) -> Option<impl Iterator<Item = ComponentKey>> {
+    let hull_socket = self.hull().get_socket(socket_key)?;
+    Some(ComponentKey::values().filter(move |component_key| {
+      component_key.component().is_legal_in(self, hull_socket)
+    }))
+  }
 }
 
 impl FromStr for HullKey {
@@ -659,7 +668,7 @@ pub mod list {
     base_integrity: 3000.0,
     armor_thickness: 20.0,
     base_crew_complement: 100,
-    base_sig_radius: 168.945282,
+    base_sig_radius: 168.94528,
     buffs: &[
       Buff::MissileProgrammingChannels(1),
       Buff::RepairTeamMoveSpeed(0.2)
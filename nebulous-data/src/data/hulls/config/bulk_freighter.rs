@@ -8,6 +8,7 @@ use rand::seq::SliceRandom;
 #[cfg(feature = "rand")]
 use rand::Rng;
 use uuid::{Uuid, uuid};
+use xml::Extra;
 
 use std::array::from_fn as array_from_fn;
 use std::iter::repeat_with;
@@ -28,7 +29,7 @@ impl Distribution<HullConfig> for HullConfigBulkFreighter {
       let key = *self.variants[i].select_array(&PRIMARY_STRUCTURES[i]);
       let dressing = repeat_with(|| rng.gen_range(0..=1))
         .take(dressing_counts[i]).collect();
-      SegmentConfiguration { key, dressing }
+      SegmentConfiguration { key, dressing, extra: Extra::new() }
     });
 
     let bridge_locations = can_mount_bridges(self.variants);
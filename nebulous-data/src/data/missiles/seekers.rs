@@ -361,7 +361,7 @@ impl<S: Copy> SeekerStrategy<S> {
     zsize!(self.secondaries.len().wrapping_add(1))
   }
 
-  pub fn iter(&self) -> SeekerStrategyIter<S> {
+  pub fn iter(&self) -> SeekerStrategyIter<'_, S> {
     std::iter::once((self.primary, SeekerMode::Targeting)).chain(self.secondaries.iter().copied())
   }
 }
@@ -908,11 +908,11 @@ impl CountermeasuresMask {
     self.and(category.mask().not())
   }
 
-  pub fn iter_filtered(self) -> impl Iterator<Item = Countermeasure> + DoubleEndedIterator + Clone {
+  pub fn iter_filtered(self) -> impl DoubleEndedIterator<Item = Countermeasure> + Clone {
     Countermeasure::values().filter(move |&cm| self[cm])
   }
 
-  pub fn values() -> impl Iterator<Item = Self> + DoubleEndedIterator + Clone {
+  pub fn values() -> impl DoubleEndedIterator<Item = Self> + Clone {
     const MAX: u32 = CountermeasuresMask::ALL.to_num();
     (0..=MAX).map(Self::from_num)
   }
@@ -956,7 +956,7 @@ impl<'a, T> IntoIterator for &'a CountermeasureMatrix<T> {
   type Item = &'a T;
 
   fn into_iter(self) -> Self::IntoIter {
-    self.as_array_ref().into_iter()
+    self.as_array_ref().iter()
   }
 }
 
@@ -965,7 +965,7 @@ impl<'a, T> IntoIterator for &'a mut CountermeasureMatrix<T> {
   type Item = &'a mut T;
 
   fn into_iter(self) -> Self::IntoIter {
-    self.as_array_ref_mut().into_iter()
+    self.as_array_ref_mut().iter_mut()
   }
 }
 
@@ -1,32 +1,41 @@
+#[cfg(feature = "convert")]
+pub mod convert;
+pub mod file;
 pub mod key;
+pub mod migrate;
+pub mod validate;
 
 use crate::data::{Faction, MissileSize};
 use crate::data::components::ComponentKey;
 use crate::data::hulls::config::Variant;
 use crate::data::hulls::HullKey;
 use crate::data::missiles::{AuxiliaryKey, AvionicsKey, Maneuvers, WarheadKey};
-use crate::data::missiles::bodies::MissileBodyKey;
+use crate::data::missiles::bodies::{MissileBodyKey, MissileBodyVariant};
 use crate::data::missiles::seekers::{SeekerMode, SeekerKey};
 use crate::data::missiles::engines::EngineSettings;
 use crate::data::munitions::MunitionKey;
 use self::key::Key;
+use self::migrate::MigrationError;
 
 use bytemuck::Contiguous;
 #[cfg(feature = "rand")]
 use rand::Rng;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use xml::{DeserializeElement, DeserializeNodes, SerializeElement, SerializeNodes, Element, Nodes, Attributes};
+use xml::{DeserializeElement, DeserializeNodes, SerializeElement, SerializeNodes, Element, Nodes, Node, Attributes, Extra, Locate};
+use xml::pull::ElementReader;
 
 #[doc(no_inline)]
 pub use uuid::Uuid;
 #[doc(no_inline)]
 pub use xml::{read_nodes, write_nodes};
+pub use self::file::{FileError, SaveFile, SaveKind};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::io::BufRead;
 use std::num::NonZeroUsize as zsize;
 use std::ops::{Add, AddAssign, Index};
 use std::str::FromStr;
@@ -49,7 +58,40 @@ pub enum FormatError {
   #[error("unknown hull component data type {0:?}")]
   UnknownComponentDataType(Box<str>),
   #[error("unknown missile settings type {0:?}")]
-  UnknownMissileSettingsType(Box<str>)
+  UnknownMissileSettingsType(Box<str>),
+  #[error(transparent)]
+  MigrationError(#[from] MigrationError),
+  #[error("{1} at {0}")]
+  Located(Box<xml::Location>, Box<FormatError>)
+}
+
+impl FormatError {
+  /// The location of the element this error occurred in, if it is known.
+  pub fn location(&self) -> Option<&xml::Location> {
+    match self {
+      FormatError::XmlError(error) => error.location(),
+      FormatError::Located(location, _) => Some(location),
+      _ => None
+    }
+  }
+}
+
+/// Returned by [`Fleet::merge`] when the fleets belong to different factions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("cannot merge a {} fleet into a {} fleet", .found.name(), .expected.name())]
+pub struct FactionMismatchError {
+  pub expected: Faction,
+  pub found: Faction
+}
+
+impl Locate for FormatError {
+  fn locate(self, location: &xml::Location) -> Self {
+    match self {
+      FormatError::XmlError(error) => FormatError::XmlError(error.locate(location)),
+      FormatError::Located(..) => self,
+      error => FormatError::Located(Box::new(location.clone()), Box::new(error))
+    }
+  }
 }
 
 impl From<xml::DeserializeErrorWrapper<FormatError>> for FormatError {
@@ -64,6 +106,13 @@ impl From<xml::DeserializeErrorWrapper<xml::Error>> for FormatError {
   }
 }
 
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("stored missile cost {stored} does not match calculated cost {calculated}")]
+pub struct MissileCostMismatch {
+  pub stored: usize,
+  pub calculated: usize
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 #[cfg_attr(feature = "serde", serde(transparent))]
@@ -75,10 +124,13 @@ impl<T> DeserializeNodes for Root<T> where T: DeserializeElement<Error = FormatE
   type Error = FormatError;
 
   fn deserialize_nodes(nodes: Nodes) -> Result<Self, Self::Error> {
-    nodes.try_into_one_element()
-      .map_err(FormatError::from)
-      .and_then(T::deserialize_element)
-      .map(|element| Root { element })
+    let mut element = nodes.try_into_one_element()?;
+    // These are written back out by `Root::serialize_nodes`, so they are not kept by the element itself.
+    let attributes = Vec::from(std::mem::take(&mut element.attributes).list).into_iter()
+      .filter(|(name, _)| !matches!(name.as_ref(), "xmlns:xsd" | "xmlns:xsi"))
+      .collect::<Attributes>();
+    element.attributes = attributes;
+    T::deserialize_element(element).map(|element| Root { element })
   }
 }
 
@@ -86,15 +138,26 @@ impl<T> SerializeNodes for Root<T> where T: SerializeElement<Error = Infallible>
   type Error = Infallible;
 
   fn serialize_nodes(self) -> Result<Nodes, Self::Error> {
-    let mut element = self.element.serialize_element()?;
-    let mut attributes = Vec::from(std::mem::take(&mut element.attributes).list);
-    attributes.push(("xmlns:xsd".into(), "http://www.w3.org/2001/XMLSchema".into()));
-    attributes.push(("xmlns:xsi".into(), "http://www.w3.org/2001/XMLSchema-instance".into()));
-    element.attributes = Attributes::from(attributes);
-    Ok(Nodes::new_one(element))
+    self.element.serialize_element().map(root_nodes)
   }
 }
 
+impl Root<Fleet> {
+  /// Serializes this fleet as a fleet file of an older (or the current) version of the fleet format,
+  /// failing if the fleet uses anything that version cannot represent.
+  pub fn serialize_nodes_version(self, version: usize) -> Result<Nodes, MigrationError> {
+    self.element.serialize_element_version(version).map(root_nodes)
+  }
+}
+
+fn root_nodes(mut element: Element) -> Nodes {
+  let mut attributes = Vec::from(std::mem::take(&mut element.attributes).list);
+  attributes.push(("xmlns:xsd".into(), "http://www.w3.org/2001/XMLSchema".into()));
+  attributes.push(("xmlns:xsi".into(), "http://www.w3.org/2001/XMLSchema-instance".into()));
+  element.attributes = Attributes::from(attributes);
+  Nodes::new_one(element)
+}
+
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Fleet {
@@ -103,10 +166,15 @@ pub struct Fleet {
   pub faction_key: Faction,
   pub description: Option<String>,
   pub ships: Vec<Ship>,
-  pub missile_types: Vec<MissileTemplate>
+  pub missile_types: Vec<MissileTemplate>,
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Extra::is_empty"))]
+  pub extra: Extra
 }
 
 impl Fleet {
+  /// Fleets with more ships than this may not load correctly.
+  pub const MAX_SHIPS: usize = 10;
+
   #[cfg(feature = "rand")]
   pub fn dupe<R: Rng + ?Sized>(&self, rng: &mut R) -> Self {
     Fleet {
@@ -115,7 +183,8 @@ impl Fleet {
       faction_key: self.faction_key,
       description: self.description.clone(),
       ships: self.dupe_ships(rng),
-      missile_types: self.missile_types.clone()
+      missile_types: self.missile_types.clone(),
+      extra: self.extra.clone()
     }
   }
 
@@ -138,6 +207,172 @@ impl Fleet {
     new_ships
   }
 
+  /// Creates a duplicate of this fleet's ships like [`Fleet::dupe_ships`], but with the duplicates placed beside the
+  /// originals rather than on top of them, so that several copies can be placed into this fleet without overlapping.
+  ///
+  /// Each duplicate is formed on the leader of its original formation (or on its original, if it was not in one),
+  /// offset sideways by `copy` times the width of that formation, see [`Fleet::formation_width`].
+  /// Formations that are nested more than one ship deep are only re-keyed.
+  #[cfg(feature = "rand")]
+  pub fn dupe_ships_beside<R: Rng + ?Sized>(&self, copy: usize, rng: &mut R) -> Vec<Ship> {
+    let is_leader = |key: Uuid| self.ships.iter().any(|ship| ship.key == key && ship.initial_formation.is_none());
+    let mut new_ships = self.dupe_ships(rng);
+    for (ship, new_ship) in self.ships.iter().zip(new_ships.iter_mut()) {
+      let (leader, relative_position) = match ship.initial_formation {
+        None => (ship.key, Vector3::default()),
+        Some(initial_formation) if is_leader(initial_formation.guide_key) => {
+          (initial_formation.guide_key, initial_formation.relative_position)
+        },
+        Some(..) => continue
+      };
+
+      let offset = self.formation_width(leader) * copy as f32;
+      new_ship.initial_formation = Some(InitialFormation {
+        guide_key: leader,
+        relative_position: Vector3 { x: relative_position.x + offset, ..relative_position }
+      });
+    };
+
+    new_ships
+  }
+
+  /// The diameter of the smallest sphere around a formation's leader that contains the formation radius
+  /// (see [`Hull::formation_radius`][crate::data::hulls::Hull::formation_radius]) of every ship in its formation.
+  pub fn formation_width(&self, leader: Uuid) -> f32 {
+    let radius = self.ships.iter()
+      .filter_map(|ship| {
+        let distance = match ship.initial_formation {
+          _ if ship.key == leader => 0.0,
+          Some(initial_formation) if initial_formation.guide_key == leader => initial_formation.relative_position.length(),
+          _ => return None
+        };
+
+        Some(distance + ship.hull_type.hull().formation_radius())
+      })
+      .fold(0.0, f32::max);
+    radius * 2.0
+  }
+
+  /// Moves every ship and missile template of `other` into this fleet.
+  ///
+  /// Ships and magazines whose keys collide with ones already in this fleet are given new keys.
+  /// Missile templates with the same design as one already in this fleet are merged into it,
+  /// and templates whose name is already taken by a different design are renamed.
+  /// Magazines holding missiles are updated to refer to the merged or renamed templates.
+  ///
+  /// Fails without changing this fleet if `other` belongs to a different faction.
+  #[cfg(feature = "rand")]
+  pub fn merge<R: Rng + ?Sized>(&mut self, mut other: Fleet, rng: &mut R) -> Result<(), FactionMismatchError> {
+    if other.faction_key != self.faction_key {
+      return Err(FactionMismatchError { expected: self.faction_key, found: other.faction_key });
+    };
+
+    let mut missile_renames = HashMap::new();
+    for mut missile_template in other.missile_types {
+      let existing = self.missile_types.iter()
+        .find(|existing| existing.is_same_design(&missile_template));
+      let name = match existing {
+        Some(existing) => existing.associated_template_name.clone(),
+        None => missile_template.associated_template_name.as_deref()
+          .map(|name| self.unique_missile_name(name))
+      };
+
+      if let (Some(old_name), Some(new_name)) = (&missile_template.associated_template_name, &name) {
+        if old_name != new_name {
+          missile_renames.insert(old_name.clone(), new_name.clone());
+        };
+      };
+
+      if existing.is_none() {
+        missile_template.associated_template_name = name;
+        self.missile_types.push(missile_template);
+      };
+    };
+
+    let mut ship_keys = self.ships.iter().map(|ship| ship.key).collect::<HashSet<Uuid>>();
+    let mut magazine_keys = self.ships.iter()
+      .flat_map(|ship| ship.iter_magazines())
+      .map(|magazine_save_data| magazine_save_data.magazine_key)
+      .collect::<HashSet<Key>>();
+    let mut new_ship_keys = HashMap::new();
+    for ship in other.ships.iter_mut() {
+      if !ship_keys.insert(ship.key) {
+        let key = std::iter::repeat_with(|| crate::utils::gen_uuid(rng))
+          .find(|&key| ship_keys.insert(key)).unwrap();
+        new_ship_keys.insert(ship.key, key);
+        ship.key = key;
+      };
+
+      for magazine_save_data in ship.iter_magazines_mut() {
+        if !magazine_keys.insert(magazine_save_data.magazine_key) {
+          magazine_save_data.magazine_key = std::iter::repeat_with(|| rng.gen::<Key>())
+            .find(|&key| magazine_keys.insert(key)).unwrap();
+        };
+      };
+
+      ship.rename_missiles(&missile_renames);
+    };
+
+    for ship in other.ships.iter_mut() {
+      if let Some(initial_formation) = ship.initial_formation.as_mut() {
+        if let Some(&guide_key) = new_ship_keys.get(&initial_formation.guide_key) {
+          initial_formation.guide_key = guide_key;
+        };
+      };
+    };
+
+    self.total_points += other.total_points;
+    self.ships.append(&mut other.ships);
+    Ok(())
+  }
+
+  /// Splits this fleet in two, the first fleet containing the ships that match the predicate and the second containing the rest.
+  ///
+  /// Each fleet keeps only the missile templates its own ships use,
+  /// and initial formations guided by a ship that ended up in the other fleet are dropped.
+  pub fn split<F>(self, mut predicate: F) -> (Fleet, Fleet)
+  where F: FnMut(&Ship) -> bool {
+    let (ships_a, ships_b) = self.ships.into_iter().partition::<Vec<Ship>, _>(|ship| predicate(ship));
+    let part = |mut ships: Vec<Ship>| {
+      let ship_keys = ships.iter().map(|ship| ship.key).collect::<HashSet<Uuid>>();
+      for ship in ships.iter_mut() {
+        if ship.initial_formation.is_some_and(|initial_formation| !ship_keys.contains(&initial_formation.guide_key)) {
+          ship.initial_formation = None;
+        };
+      };
+
+      let missile_names = ships.iter().flat_map(Ship::iter_missile_names).collect::<HashSet<&str>>();
+      let missile_types = self.missile_types.iter()
+        .filter(|missile_template| {
+          missile_template.associated_template_name.as_deref()
+            .is_some_and(|name| missile_names.contains(name))
+        })
+        .cloned().collect::<Vec<MissileTemplate>>();
+
+      Fleet {
+        name: self.name.clone(),
+        total_points: ships.iter().map(|ship| ship.cost).sum(),
+        faction_key: self.faction_key,
+        description: self.description.clone(),
+        missile_types,
+        ships,
+        extra: self.extra.clone()
+      }
+    };
+
+    (part(ships_a), part(ships_b))
+  }
+
+  /// Returns `name`, or `name` with the lowest number suffix that makes it unique among this fleet's missile templates.
+  #[cfg(feature = "rand")]
+  fn unique_missile_name(&self, name: &str) -> String {
+    let is_taken = |name: &str| self.missile_types.iter()
+      .any(|missile_template| missile_template.associated_template_name.as_deref() == Some(name));
+    std::iter::once(name.to_owned())
+      .chain((2..).map(|n| format!("{name} ({n})")))
+      .find(|name| !is_taken(name)).unwrap()
+  }
+
   pub fn calculate_costs(&self, missile_templates: &[MissileTemplate]) -> Costs {
     let mut costs = Costs::default();
     for ship in self.ships.iter() {
@@ -146,24 +381,85 @@ impl Fleet {
 
     costs
   }
+
+  /// Recalculates the cost of every ship against this fleet's missile types,
+  /// and sets `total_points` to their sum.
+  pub fn recalculate_costs(&mut self) {
+    let mut total_points = 0;
+    for ship in self.ships.iter_mut() {
+      ship.cost = ship.calculate_costs(&self.missile_types).total();
+      total_points += ship.cost;
+    };
+
+    self.total_points = total_points;
+  }
+
+  /// Copies a ship out of this fleet so that it can be saved on its own as a `.ship` file,
+  /// carrying along the fleet's missile templates that its magazines reference.
+  ///
+  /// The ship's initial formation is dropped, since the ship it was formed on is not carried along.
+  pub fn extract_ship(&self, key: Uuid) -> Option<Ship> {
+    let mut ship = self.ships.iter().find(|ship| ship.key == key)?.clone();
+    let missile_names = ship.iter_missile_names().collect::<Vec<&str>>();
+    let missile_types = self.missile_types.iter()
+      .filter(|missile_template| {
+        missile_template.associated_template_name.as_deref()
+          .is_some_and(|name| missile_names.contains(&name))
+      })
+      .filter(|&missile_template| !ship.missile_types.contains(missile_template))
+      .cloned().collect::<Vec<MissileTemplate>>();
+
+    ship.missile_types.extend(missile_types);
+    ship.initial_formation = None;
+    Some(ship)
+  }
+
+  /// Adds a ship (such as one read from a `.ship` file) to this fleet,
+  /// adding any of its missile templates that this fleet does not already have to the fleet's missile types.
+  ///
+  /// The ship's key is kept as it is, see [`Ship::dupe`] to place a copy of a ship that is already in this fleet.
+  pub fn insert_ship(&mut self, ship: Ship) {
+    for missile_template in ship.missile_types.iter() {
+      let exists = self.missile_types.iter().any(|existing| {
+        existing.associated_template_name == missile_template.associated_template_name
+      });
+
+      if !exists {
+        self.missile_types.push(missile_template.clone());
+      };
+    };
+
+    self.total_points += ship.cost;
+    self.ships.push(ship);
+  }
+
+  /// Serializes this fleet as an element of the given version of the fleet format, see [`migrate::migrate`].
+  pub fn serialize_element_version(self, version: usize) -> Result<Element, MigrationError> {
+    let element = self.serialize_element().unwrap_or_else(|never| match never {});
+    migrate::migrate(element, version)
+  }
 }
 
 impl DeserializeElement for Fleet {
   type Error = FormatError;
 
   fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("Fleet")?;
-    let [name, total_points, faction_key, description, ships, missile_types] = element.children
-      .find_elements(["Name", "TotalPoints", "FactionKey", "Description", "Ships", "MissileTypes"])?;
-
-    let name = name.ok_or(xml::Error::missing_element("Name"))?.children.deserialize::<String>()?;
-    let total_points = total_points.ok_or(xml::Error::missing_element("TotalPoints"))?.children.deserialize::<usize>()?;
-    let faction_key = faction_key.ok_or(xml::Error::missing_element("FactionKey"))?.children.deserialize::<Faction>()?;
-    let description = description.map(|description| description.children.deserialize::<String>()).transpose()?.filter(|d| !d.is_empty());
-    let ships = ships.ok_or(xml::Error::missing_element("Ships"))?.children.deserialize::<Vec<Ship>>()?;
-    let missile_types = missile_types.map(|missile_types| missile_types.children.deserialize::<Vec<MissileTemplate>>()).transpose()?.unwrap_or_else(Vec::new);
-
-    Ok(Fleet { name, total_points, faction_key, description, ships, missile_types })
+    element.located(|element| {
+      element.expect_named("Fleet")?;
+      let element = migrate::upgrade(element)?;
+      const NAMES: [&str; 6] = ["Name", "TotalPoints", "FactionKey", "Description", "Ships", "MissileTypes"];
+      let extra = Extra::collect(&element.attributes, &element.children, &[], &NAMES);
+      let [name, total_points, faction_key, description, ships, missile_types] = element.children.find_elements(NAMES)?;
+
+      let name = name.ok_or(xml::Error::missing_element("Name"))?.children.deserialize::<String>()?;
+      let total_points = total_points.ok_or(xml::Error::missing_element("TotalPoints"))?.children.deserialize::<usize>()?;
+      let faction_key = faction_key.ok_or(xml::Error::missing_element("FactionKey"))?.children.deserialize::<Faction>()?;
+      let description = description.map(|description| description.children.deserialize::<String>()).transpose()?.filter(|d| !d.is_empty());
+      let ships = ships.ok_or(xml::Error::missing_element("Ships"))?.children.deserialize::<Vec<Ship>>()?;
+      let missile_types = missile_types.map(|missile_types| missile_types.children.deserialize::<Vec<MissileTemplate>>()).transpose()?.unwrap_or_else(Vec::new);
+
+      Ok(Fleet { name, total_points, faction_key, description, ships, missile_types, extra })
+    })
   }
 }
 
@@ -171,18 +467,89 @@ impl SerializeElement for Fleet {
   type Error = Infallible;
 
   fn serialize_element(self) -> Result<Element, Self::Error> {
+    // Elements this crate does not otherwise understand are only synthesized if they were not read from a file.
     let name = Element::new("Name", self.name.serialize_nodes()?);
-    let version = Element::new("Version", Nodes::new_text(CURRENT_FLEET_VERISON.to_string()));
+    let version = (!self.extra.contains_element("Version"))
+      .then(|| Element::new("Version", Nodes::new_text(CURRENT_FLEET_VERISON.to_string())));
     let total_points = Element::new("TotalPoints", self.total_points.serialize_nodes()?);
     let faction_key = Element::new("FactionKey", self.faction_key.serialize_nodes()?);
     let description = self.description.map(String::serialize_nodes).transpose()?
       .map(|description| Element::new("Description", description));
-    let sort_override_order = Element::with_attributes("SortOverrideOrder", xml::attributes!("xsi:nil" = "true"), Nodes::new());
+    let sort_override_order = (!self.extra.contains_element("SortOverrideOrder"))
+      .then(|| Element::with_attributes("SortOverrideOrder", xml::attributes!("xsi:nil" = "true"), Nodes::new()));
     let ships = Element::new("Ships", self.ships.serialize_nodes()?);
     let missile_types = Element::new("MissileTypes", self.missile_types.serialize_nodes()?);
 
-    let nodes = Nodes::from_iter(chain_iter!([name, version, total_points, faction_key], description, [sort_override_order, ships, missile_types]));
-    Ok(Element::new("Fleet", nodes))
+    let mut attributes = Vec::new();
+    let mut nodes = chain_iter!([name], version, [total_points, faction_key], description, sort_override_order, [ships, missile_types])
+      .map(Node::from).collect::<Vec<Node>>();
+    self.extra.insert_into(&mut attributes, &mut nodes);
+    Ok(Element::with_attributes("Fleet", Attributes::from(attributes), nodes))
+  }
+}
+
+/// The name, point total, faction and description of a fleet, which can be read from a fleet file
+/// without reading any of its ships, for quickly indexing directories of fleets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct FleetHeader {
+  pub name: String,
+  pub total_points: usize,
+  pub faction_key: Faction,
+  pub description: Option<String>
+}
+
+impl FleetHeader {
+  const NAMES: [&'static str; 4] = ["Name", "TotalPoints", "FactionKey", "Description"];
+
+  /// Reads the header of a fleet file, stopping as soon as the fleet's ships are reached.
+  pub fn read<R: BufRead>(reader: R) -> Result<Self, FormatError> {
+    xml::pull::pull_root(reader)
+  }
+
+  fn from_elements([name, total_points, faction_key, description]: [Option<Element>; 4]) -> Result<Self, FormatError> {
+    let name = name.ok_or(xml::Error::missing_element("Name"))?.children.deserialize::<String>()?;
+    let total_points = total_points.ok_or(xml::Error::missing_element("TotalPoints"))?.children.deserialize::<usize>()?;
+    let faction_key = faction_key.ok_or(xml::Error::missing_element("FactionKey"))?.children.deserialize::<Faction>()?;
+    let description = description.map(|description| description.children.deserialize::<String>()).transpose()?.filter(|d| !d.is_empty());
+    Ok(FleetHeader { name, total_points, faction_key, description })
+  }
+}
+
+impl From<&Fleet> for FleetHeader {
+  fn from(fleet: &Fleet) -> Self {
+    FleetHeader {
+      name: fleet.name.clone(),
+      total_points: fleet.total_points,
+      faction_key: fleet.faction_key,
+      description: fleet.description.clone()
+    }
+  }
+}
+
+impl DeserializeElement for FleetHeader {
+  type Error = FormatError;
+
+  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
+    element.located(|element| {
+      element.expect_named("Fleet")?;
+      Self::from_elements(element.children.find_elements(Self::NAMES)?)
+    })
+  }
+
+  fn pull_element<R: BufRead>(mut element: ElementReader<'_, R>) -> Result<Self, Self::Error> {
+    element.expect_named("Fleet")?;
+    let mut elements: [Option<Element>; 4] = Default::default();
+    while let Some(child) = element.next_element()? {
+      match Self::NAMES.iter().position(|&name| name == child.name.as_ref()) {
+        Some(i) => elements[i] = Some(child.read_element()?),
+        // The header always comes before the ships, so the rest of the file is not read
+        None if child.name.as_ref() == "Ships" => break,
+        None => continue
+      };
+    };
+
+    Self::from_elements(elements)
   }
 }
 
@@ -199,7 +566,9 @@ pub struct Ship {
   pub socket_map: Vec<HullSocket>,
   pub weapon_groups: Vec<WeaponGroup>,
   pub initial_formation: Option<InitialFormation>,
-  pub missile_types: Vec<MissileTemplate>
+  pub missile_types: Vec<MissileTemplate>,
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Extra::is_empty"))]
+  pub extra: Extra
 }
 
 impl Ship {
@@ -256,7 +625,7 @@ impl Ship {
       } else {
         // compounding appears to place more expensive components earlier
         // in the calculation, thus giving them lower compounding costs
-        for (cost, i) in component_costs.into_iter().rev().enumerate() {
+        for (i, cost) in component_costs.into_iter().rev().enumerate() {
           let modifier = if i == 0 { 1 } else { i * multiplier };
           costs.components += cost * modifier;
         };
@@ -266,6 +635,42 @@ impl Ship {
     costs
   }
 
+  pub fn iter_magazines(&self) -> impl Iterator<Item = &MagazineSaveData> + '_ {
+    self.socket_map.iter()
+      .filter_map(|hull_socket| hull_socket.component_data.as_ref())
+      .filter_map(ComponentData::get_load).flatten()
+  }
+
+  pub fn iter_magazines_mut(&mut self) -> impl Iterator<Item = &mut MagazineSaveData> + '_ {
+    self.socket_map.iter_mut()
+      .filter_map(|hull_socket| hull_socket.component_data.as_mut())
+      .filter_map(ComponentData::get_load_mut).flatten()
+  }
+
+  /// Iterates over the names of every missile template referenced by this ship's magazines.
+  pub fn iter_missile_names(&self) -> impl Iterator<Item = &str> + '_ {
+    self.iter_magazines()
+      .filter_map(|magazine_save_data| magazine_save_data.munition_key.missile_key())
+  }
+
+  /// Renames missile templates referenced by this ship's magazines and carried in its own missile types,
+  /// given a map of old names to new names.
+  pub fn rename_missiles(&mut self, renames: &HashMap<String, String>) {
+    for magazine_save_data in self.iter_magazines_mut() {
+      if let MunitionOrMissileKey::MissileKey(missile_key) = &mut magazine_save_data.munition_key {
+        if let Some(new_name) = renames.get(&**missile_key) {
+          *missile_key = new_name.as_str().into();
+        };
+      };
+    };
+
+    for missile_template in self.missile_types.iter_mut() {
+      if let Some(new_name) = missile_template.associated_template_name.as_ref().and_then(|name| renames.get(name)) {
+        missile_template.associated_template_name = Some(new_name.clone());
+      };
+    };
+  }
+
   /// Creates a duplicate of this ship.
   /// Keys will be randomized so that placing this ship into a fleet with the original produces a valid fleet.
   #[cfg(feature = "rand")]
@@ -301,7 +706,8 @@ impl Ship {
       socket_map,
       weapon_groups: self.weapon_groups.clone(),
       initial_formation: self.initial_formation,
-      missile_types: self.missile_types.clone()
+      missile_types: self.missile_types.clone(),
+      extra: self.extra.clone()
     }
   }
 }
@@ -310,23 +716,31 @@ impl DeserializeElement for Ship {
   type Error = FormatError;
 
   fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("Ship")?;
-    let [key, name, cost, callsign, number, hull_type, hull_config, socket_map, weapon_groups, initial_formation, missile_types] = element.children
-      .find_elements(["Key", "Name", "Cost", "Callsign", "Number", "HullType", "HullConfig", "SocketMap", "WeaponGroups", "InitialFormation", "TemplateMissileTypes"])?;
-
-    let key = key.ok_or(xml::Error::missing_element("Key"))?.children.deserialize::<Uuid>()?;
-    let name = name.ok_or(xml::Error::missing_element("Name"))?.children.deserialize::<String>()?;
-    let cost = cost.ok_or(xml::Error::missing_element("Cost"))?.children.deserialize::<usize>()?;
-    let callsign = callsign.map(|callsign| callsign.children.deserialize::<String>()).transpose()?.filter(|c| !c.is_empty());
-    let number = number.ok_or(xml::Error::missing_element("Number"))?.children.deserialize::<usize>()?;
-    let hull_type = hull_type.ok_or(xml::Error::missing_element("HullType"))?.children.deserialize::<HullKey>()?;
-    let hull_config = hull_config.map(|hull_config| hull_config.deserialize::<Box<HullConfig>>()).transpose()?;
-    let socket_map = socket_map.ok_or(xml::Error::missing_element("SocketMap"))?.children.deserialize::<Vec<HullSocket>>()?;
-    let weapon_groups = weapon_groups.ok_or(xml::Error::missing_element("WeaponGroups"))?.children.deserialize::<Vec<WeaponGroup>>()?;
-    let initial_formation = initial_formation.map(InitialFormation::deserialize_element).transpose()?;
-    let missile_types = missile_types.map(|element| element.children.deserialize::<Vec<MissileTemplate>>()).transpose()?.unwrap_or_else(Vec::new);
-
-    Ok(Ship { key, name, cost, callsign, number, hull_type, hull_config, socket_map, weapon_groups, initial_formation, missile_types })
+    element.located(|element| {
+      element.expect_named("Ship")?;
+      const NAMES: [&str; 11] = [
+        "Key", "Name", "Cost", "Callsign", "Number", "HullType", "HullConfig",
+        "SocketMap", "WeaponGroups", "InitialFormation", "TemplateMissileTypes"
+      ];
+
+      let extra = Extra::collect(&element.attributes, &element.children, &[], &NAMES);
+      let [key, name, cost, callsign, number, hull_type, hull_config, socket_map, weapon_groups, initial_formation, missile_types] = element.children
+        .find_elements(NAMES)?;
+
+      let key = key.ok_or(xml::Error::missing_element("Key"))?.children.deserialize::<Uuid>()?;
+      let name = name.ok_or(xml::Error::missing_element("Name"))?.children.deserialize::<String>()?;
+      let cost = cost.ok_or(xml::Error::missing_element("Cost"))?.children.deserialize::<usize>()?;
+      let callsign = callsign.map(|callsign| callsign.children.deserialize::<String>()).transpose()?.filter(|c| !c.is_empty());
+      let number = number.ok_or(xml::Error::missing_element("Number"))?.children.deserialize::<usize>()?;
+      let hull_type = hull_type.ok_or(xml::Error::missing_element("HullType"))?.children.deserialize::<HullKey>()?;
+      let hull_config = hull_config.map(|hull_config| hull_config.deserialize::<Box<HullConfig>>()).transpose()?;
+      let socket_map = socket_map.ok_or(xml::Error::missing_element("SocketMap"))?.children.deserialize::<Vec<HullSocket>>()?;
+      let weapon_groups = weapon_groups.ok_or(xml::Error::missing_element("WeaponGroups"))?.children.deserialize::<Vec<WeaponGroup>>()?;
+      let initial_formation = initial_formation.map(InitialFormation::deserialize_element).transpose()?;
+      let missile_types = missile_types.map(|element| element.children.deserialize::<Vec<MissileTemplate>>()).transpose()?.unwrap_or_else(Vec::new);
+
+      Ok(Ship { key, name, cost, callsign, number, hull_type, hull_config, socket_map, weapon_groups, initial_formation, missile_types, extra })
+    })
   }
 }
 
@@ -334,13 +748,16 @@ impl SerializeElement for Ship {
   type Error = Infallible;
 
   fn serialize_element(self) -> Result<Element, Self::Error> {
-    let save_id = Element::with_attributes("SaveID", xml::attributes!("xsi:nil" = "true"), Nodes::default());
+    // Elements this crate does not otherwise understand are only synthesized if they were not read from a file.
+    let save_id = (!self.extra.contains_element("SaveID"))
+      .then(|| Element::with_attributes("SaveID", xml::attributes!("xsi:nil" = "true"), Nodes::default()));
     let key = Element::new("Key", self.key.serialize_nodes()?);
     let name = Element::new("Name", self.name.serialize_nodes()?);
     let cost = Element::new("Cost", self.cost.serialize_nodes()?);
     let callsign = Element::new("Callsign", self.callsign.map_or(Ok(Nodes::default()), String::serialize_nodes)?);
     let number = Element::new("Number", self.number.serialize_nodes()?);
-    let symbol_option = Element::new("SymbolOption", Nodes::new_text("0"));
+    let symbol_option = (!self.extra.contains_element("SymbolOption"))
+      .then(|| Element::new("SymbolOption", Nodes::new_text("0")));
     let hull_type = Element::new("HullType", self.hull_type.serialize_nodes()?);
     let hull_config = self.hull_config.map(<Box<HullConfig>>::serialize_element).transpose()?;
     let socket_map = Element::new("SocketMap", self.socket_map.serialize_nodes()?);
@@ -348,53 +765,33 @@ impl SerializeElement for Ship {
     let initial_formation = self.initial_formation.map(InitialFormation::serialize_element).transpose()?;
     let missile_types = Element::new("TemplateMissileTypes", self.missile_types.serialize_nodes()?);
 
-    let nodes = Nodes::from_iter(chain_iter!(
-      [save_id, key, name, cost, callsign, number, symbol_option, hull_type], hull_config,
-      [socket_map, weapon_groups], initial_formation, std::iter::once(missile_types)
-    ));
-
-    Ok(Element::new("Ship", nodes))
+    let mut attributes = Vec::new();
+    let mut nodes = chain_iter!(
+      save_id, [key, name, cost, callsign, number], symbol_option, [hull_type], hull_config,
+      [socket_map, weapon_groups], initial_formation, [missile_types]
+    ).map(Node::from).collect::<Vec<Node>>();
+    self.extra.insert_into(&mut attributes, &mut nodes);
+    Ok(Element::with_attributes("Ship", Attributes::from(attributes), nodes))
   }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, DeserializeElement, SerializeElement)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[xml(error = FormatError)]
 pub struct HullSocket {
   pub key: Key,
   pub component_name: ComponentKey,
-  pub component_data: Option<ComponentData>
-}
-
-impl DeserializeElement for HullSocket {
-  type Error = FormatError;
-
-  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    let [key, component_name, component_data] = element.children
-      .find_elements(["Key", "ComponentName", "ComponentData"])?;
-
-    let key = key.ok_or(xml::Error::missing_element("Key"))?.children.deserialize::<Key>()?;
-    let component_name = component_name.ok_or(xml::Error::missing_element("ComponentName"))?.children.deserialize::<ComponentKey>()?;
-    let component_data = component_data.map(Element::deserialize::<ComponentData>).transpose()?;
-
-    Ok(HullSocket { key, component_name, component_data })
-  }
+  #[xml(element)]
+  pub component_data: Option<ComponentData>,
+  #[xml(extra)]
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Extra::is_empty"))]
+  pub extra: Extra
 }
 
-impl SerializeElement for HullSocket {
-  type Error = Infallible;
-
-  fn serialize_element(self) -> Result<Element, Self::Error> {
-    let key = Element::new("Key", self.key.serialize_nodes()?);
-    let component_name = Element::new("ComponentName", Nodes::new_text(self.component_name.save_key()));
-    let component_data = self.component_data.map(ComponentData::serialize_element).transpose()?;
-    let nodes = Nodes::from_iter(chain_iter!([key, component_name], component_data));
-    Ok(Element::new("HullSocket", nodes))
-  }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, DeserializeElement, SerializeElement)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case", tag = "type"))]
+#[xml(error = FormatError, tag = "xsi:type", unknown_type = FormatError::UnknownComponentDataType)]
 pub enum ComponentData {
   BulkMagazineData {
     load: Vec<MagazineSaveData>
@@ -440,109 +837,17 @@ impl ComponentData {
   }
 }
 
-impl DeserializeElement for ComponentData {
-  type Error = FormatError;
-
-  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("ComponentData")?;
-    let [xsi_type] = element.attributes.find_attributes(["xsi:type"])?;
-    let xsi_type = xsi_type.ok_or(xml::Error::missing_attribute("xsi:type"))?;
-
-    match xsi_type.as_ref() {
-      "BulkMagazineData" => {
-        let [load] = element.children.find_elements(["Load"])?;
-        let load = load.ok_or(xml::Error::missing_element("Load"))?
-          .children.deserialize::<Vec<MagazineSaveData>>()?;
-        Ok(ComponentData::BulkMagazineData { load })
-      },
-      "CellLauncherData" => {
-        let [missile_load] = element.children.find_elements(["MissileLoad"])?;
-        let missile_load = missile_load.ok_or(xml::Error::missing_element("MissileLoad"))?
-          .children.deserialize::<Vec<MagazineSaveData>>()?;
-        Ok(ComponentData::CellLauncherData { missile_load })
-      },
-      "ResizableCellLauncherData" => {
-        let [missile_load, configured_size] = element.children.find_elements(["MissileLoad", "ConfiguredSize"])?;
-        let missile_load = missile_load.ok_or(xml::Error::missing_element("MissileLoad"))?
-          .children.deserialize::<Vec<MagazineSaveData>>()?;
-        let configured_size = configured_size.ok_or(xml::Error::missing_element("ConfiguredSize"))?
-          .children.deserialize::<Vector2<usize>>()?;
-        Ok(ComponentData::ResizableCellLauncherData { missile_load, configured_size })
-      },
-      "DeceptionComponentData" => {
-        let [identity_option] = element.children.find_elements(["IdentityOption"])?;
-        let identity_option = identity_option.ok_or(xml::Error::missing_element("IdentityOption"))?
-          .children.deserialize::<usize>()?;
-        Ok(ComponentData::DeceptionComponentData { identity_option })
-      },
-      _ => Err(FormatError::UnknownComponentDataType(xsi_type.clone()))
-    }
-  }
-}
-
-impl SerializeElement for ComponentData {
-  type Error = Infallible;
-
-  fn serialize_element(self) -> Result<Element, Self::Error> {
-    let (xsi_type, nodes) = match self {
-      ComponentData::BulkMagazineData { load } => ("BulkMagazineData", {
-        Nodes::new_one(Element::new("Load", load.serialize_nodes()?))
-      }),
-      ComponentData::CellLauncherData { missile_load } => ("CellLauncherData", {
-        Nodes::new_one(Element::new("MissileLoad", missile_load.serialize_nodes()?))
-      }),
-      ComponentData::ResizableCellLauncherData { missile_load, configured_size } => ("ResizableCellLauncherData", {
-        let missile_load = Element::new("MissileLoad", missile_load.serialize_nodes()?);
-        let configured_size = Element::new("ConfiguredSize", configured_size.serialize_nodes()?);
-        Nodes::from_iter([missile_load, configured_size])
-      }),
-      ComponentData::DeceptionComponentData { identity_option } => ("DeceptionComponentData", {
-        Nodes::new_one(Element::new("IdentityOption", identity_option.serialize_nodes()?))
-      })
-    };
-
-    Ok(Element::with_attributes("ComponentData", xml::attributes!("xsi:type" = xsi_type), nodes))
-  }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, DeserializeElement, SerializeElement)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[xml(name = "MagSaveData", error = FormatError)]
 pub struct MagazineSaveData {
   pub magazine_key: Key,
   // This is here rather than MunitionKey since these can reference custom missiles, which have unique names.
   pub munition_key: MunitionOrMissileKey,
-  pub quantity: usize
-}
-
-impl DeserializeElement for MagazineSaveData {
-  type Error = FormatError;
-
-  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("MagSaveData")?;
-    let [magazine_key, munition_key, quantity] = element.children
-      .find_elements(["MagazineKey", "MunitionKey", "Quantity"])?;
-
-    let magazine_key = magazine_key.ok_or(xml::Error::missing_element("MagazineKey"))?
-      .children.deserialize::<Key>()?;
-    let munition_key = munition_key.ok_or(xml::Error::missing_element("MunitionKey"))?
-      .children.deserialize::<MunitionOrMissileKey>()?;
-    let quantity = quantity.ok_or(xml::Error::missing_element("Quantity"))?
-      .children.deserialize::<usize>()?;
-
-    Ok(MagazineSaveData { magazine_key, munition_key, quantity })
-  }
-}
-
-impl SerializeElement for MagazineSaveData {
-  type Error = Infallible;
-
-  fn serialize_element(self) -> Result<Element, Self::Error> {
-    let magazine_key = Element::new("MagazineKey", self.magazine_key.serialize_nodes()?);
-    let munition_key = Element::new("MunitionKey", self.munition_key.serialize_nodes()?);
-    let quantity = Element::new("Quantity", self.quantity.serialize_nodes()?);
-
-    Ok(Element::new("MagSaveData", Nodes::from_iter([magazine_key, munition_key, quantity])))
-  }
+  pub quantity: usize,
+  #[xml(extra)]
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Extra::is_empty"))]
+  pub extra: Extra
 }
 
 
@@ -627,71 +932,27 @@ impl Serialize for MunitionOrMissileKey {
 xml::impl_deserialize_nodes_parse!(MunitionOrMissileKey);
 xml::impl_serialize_nodes_display!(MunitionOrMissileKey);
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, DeserializeElement, SerializeElement)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[xml(name = "WepGroup", error = FormatError)]
 pub struct WeaponGroup {
+  #[xml(attribute)]
   pub name: String,
-  pub members: Vec<Key>
+  #[xml(rename = "MemberKeys", named_elements = "string")]
+  pub members: Vec<Key>,
+  #[xml(extra)]
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Extra::is_empty"))]
+  pub extra: Extra
 }
 
-impl DeserializeElement for WeaponGroup {
-  type Error = FormatError;
-
-  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("WepGroup")?;
-    let [name] = element.attributes.find_attributes(["Name"])?;
-    let name = name.ok_or(xml::Error::missing_attribute("Name"))?;
-
-    let member_keys = element.children.try_into_one_element()?;
-    member_keys.expect_named("MemberKeys")?;
-
-    let members = member_keys.children.deserialize_named_elements::<Key, Vec<Key>>("string")?;
-
-    Ok(WeaponGroup { name: String::from(name), members })
-  }
-}
-
-impl SerializeElement for WeaponGroup {
-  type Error = Infallible;
-
-  fn serialize_element(self) -> Result<Element, Self::Error> {
-    let member_keys = xml::serialize_named_elements(self.members, "string")?;
-    let nodes = Nodes::new_one(Element::new("MemberKeys", member_keys));
-    let weapon_group = Element::with_attributes("WepGroup", xml::attributes!("Name" = self.name), nodes);
-    Ok(weapon_group)
-  }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, DeserializeElement, SerializeElement)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[xml(error = FormatError)]
 pub struct InitialFormation {
   pub guide_key: Uuid,
   pub relative_position: Vector3<f32>
 }
 
-impl DeserializeElement for InitialFormation {
-  type Error = FormatError;
-
-  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("InitialFormation")?;
-    let [guide_key, relative_position] = element.children.find_elements(["GuideKey", "RelativePosition"])?;
-    let guide_key = guide_key.ok_or(xml::Error::missing_element("GuideKey"))?.children.deserialize::<Uuid>()?;
-    let relative_position = relative_position.ok_or(xml::Error::missing_element("RelativePosition"))?.children.deserialize::<Vector3<f32>>()?;
-    Ok(InitialFormation { guide_key, relative_position })
-  }
-}
-
-impl SerializeElement for InitialFormation {
-  type Error = Infallible;
-
-  fn serialize_element(self) -> Result<Element, Self::Error> {
-    Ok(Element::new("InitialFormation", Nodes::from_iter([
-      Element::new("GuideKey", self.guide_key.serialize_nodes()?),
-      Element::new("RelativePosition", self.relative_position.serialize_nodes()?)
-    ])))
-  }
-}
-
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case", tag = "type"))]
@@ -730,28 +991,30 @@ impl DeserializeElement for HullConfig {
   type Error = FormatError;
 
   fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("HullConfig")?;
-    let [xsi_type] = element.attributes.find_attributes(["xsi:type"])?;
-    let xsi_type = xsi_type.ok_or(xml::Error::missing_attribute("xsi:type"))?;
-
-    match xsi_type.as_ref() {
-      "RandomHullConfiguration" => {
-        let [primary_structure, secondary_structure, hull_tint, texture_variation] = element.children
-          .find_elements(["PrimaryStructure", "SecondaryStructure", "HullTint", "TextureVariation"])?;
-
-        let primary_structure = primary_structure.ok_or(xml::Error::missing_element("PrimaryStructure"))?
-          .children.deserialize::<[SegmentConfiguration; 3]>()?;
-        let secondary_structure = secondary_structure.ok_or(xml::Error::missing_element("SecondaryStructure"))?
-          .children.try_into_one_element()?.deserialize::<SecondaryStructureConfig>()?;
-        let hull_tint = hull_tint.ok_or(xml::Error::missing_element("HullTint"))?
-          .children.deserialize::<Color>()?;
-        let texture_variation = texture_variation.ok_or(xml::Error::missing_element("TextureVariation"))?
-          .children.deserialize::<Vector3<f32>>()?;
-
-        Ok(HullConfig::RandomHullConfiguration { primary_structure, secondary_structure, hull_tint, texture_variation })
-      },
-      _ => Err(FormatError::UnknownHullConfigType(xsi_type.clone()))
-    }
+    element.located(|element| {
+      element.expect_named("HullConfig")?;
+      let [xsi_type] = element.attributes.find_attributes(["xsi:type"])?;
+      let xsi_type = xsi_type.ok_or(xml::Error::missing_attribute("xsi:type"))?;
+
+      match xsi_type.as_ref() {
+        "RandomHullConfiguration" => {
+          let [primary_structure, secondary_structure, hull_tint, texture_variation] = element.children
+            .find_elements(["PrimaryStructure", "SecondaryStructure", "HullTint", "TextureVariation"])?;
+
+          let primary_structure = primary_structure.ok_or(xml::Error::missing_element("PrimaryStructure"))?
+            .children.deserialize::<[SegmentConfiguration; 3]>()?;
+          let secondary_structure = secondary_structure.ok_or(xml::Error::missing_element("SecondaryStructure"))?
+            .children.try_into_one_element()?.deserialize::<SecondaryStructureConfig>()?;
+          let hull_tint = hull_tint.ok_or(xml::Error::missing_element("HullTint"))?
+            .children.deserialize::<Color>()?;
+          let texture_variation = texture_variation.ok_or(xml::Error::missing_element("TextureVariation"))?
+            .children.deserialize::<Vector3<f32>>()?;
+
+          Ok(HullConfig::RandomHullConfiguration { primary_structure, secondary_structure, hull_tint, texture_variation })
+        },
+        _ => Err(FormatError::UnknownHullConfigType(xsi_type.clone()))
+      }
+    })
   }
 }
 
@@ -772,74 +1035,30 @@ impl SerializeElement for HullConfig {
   }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, DeserializeElement, SerializeElement)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[xml(error = FormatError)]
 pub struct SegmentConfiguration {
   pub key: Uuid,
-  pub dressing: Vec<usize>
-}
-
-impl DeserializeElement for SegmentConfiguration {
-  type Error = FormatError;
-
-  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("SegmentConfiguration")?;
-    let [key, dressing] = element.children.find_elements(["Key", "Dressing"])?;
-
-    let key = key.ok_or(xml::Error::missing_element("Key"))?.children.deserialize::<Uuid>()?;
-    let dressing = dressing.ok_or(xml::Error::missing_element("Dressing"))?
-      .children.deserialize_named_elements::<usize, Vec<usize>>("int")?;
-
-    Ok(SegmentConfiguration { key, dressing })
-  }
+  #[xml(named_elements = "int")]
+  pub dressing: Vec<usize>,
+  #[xml(extra)]
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Extra::is_empty"))]
+  pub extra: Extra
 }
 
-impl SerializeElement for SegmentConfiguration {
-  type Error = Infallible;
-
-  fn serialize_element(self) -> Result<Element, Self::Error> {
-    let key = Element::new("Key", self.key.serialize_nodes()?);
-    let dressing = Element::new("Dressing", xml::serialize_named_elements(self.dressing, "int")?);
-    Ok(Element::new("SegmentConfiguration", Nodes::from_iter([key, dressing])))
-  }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeserializeElement, SerializeElement)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[xml(error = FormatError)]
 pub struct SecondaryStructureConfig {
   pub key: Uuid,
   pub segment: usize,
   pub snap_point: usize
 }
 
-impl DeserializeElement for SecondaryStructureConfig {
-  type Error = FormatError;
-
-  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("SecondaryStructureConfig")?;
-    let [key, segment, snap_point] = element.children.find_elements(["Key", "Segment", "SnapPoint"])?;
-
-    let key = key.ok_or(xml::Error::missing_element("Key"))?.children.deserialize::<Uuid>()?;
-    let segment = segment.ok_or(xml::Error::missing_element("Segment"))?.children.deserialize::<usize>()?;
-    let snap_point = snap_point.ok_or(xml::Error::missing_element("SnapPoint"))?.children.deserialize::<usize>()?;
-
-    Ok(SecondaryStructureConfig { key, segment, snap_point })
-  }
-}
-
-impl SerializeElement for SecondaryStructureConfig {
-  type Error = Infallible;
-
-  fn serialize_element(self) -> Result<Element, Self::Error> {
-    let key = Element::new("Key", self.key.serialize_nodes()?);
-    let segment = Element::new("Segment", self.segment.serialize_nodes()?);
-    let snap_point = Element::new("SnapPoint", self.snap_point.serialize_nodes()?);
-    Ok(Element::new("SecondaryStructureConfig", Nodes::from_iter([key, segment, snap_point])))
-  }
-}
-
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, DeserializeElement, SerializeElement)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[xml(error = FormatError)]
 pub struct MissileTemplate {
   pub associated_template_name: Option<String>,
   pub designation: String,
@@ -851,117 +1070,74 @@ pub struct MissileTemplate {
   pub template_key: Uuid,
   pub base_color: Color,
   pub stripe_color: Color,
-  pub sockets: Vec<MissileSocket>
+  pub sockets: Vec<MissileSocket>,
+  #[xml(extra)]
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Extra::is_empty"))]
+  pub extra: Extra
 }
 
 impl MissileTemplate {
+  /// Calculates the point cost of this missile from its body, components and engine settings,
+  /// rounded up to a whole number of points.
   pub fn calculate_cost(&self) -> usize {
-    // TODO: actually calculate the cost, perhaps we should not trust this number
-    self.cost
-  }
+    self.calculate_cost_exact().ceil() as usize
+  }
+
+  /// Calculates the point cost of this missile from its body, components and engine settings.
+  ///
+  /// Warheads cost their base cost per segment, and each engine costs according to its balance settings.
+  /// On hybrid bodies, the first engine socket is the cruise engine and the second is the sprint engine.
+  pub fn calculate_cost_exact(&self) -> f32 {
+    let summary = self.get_summary();
+    let seekers_cost = summary.seekers.as_ref().map_or(0.0, |seekers| seekers.cost());
+    let auxiliary_cost = summary.auxiliary_components.iter()
+      .map(|auxiliary_key| auxiliary_key.cost()).sum::<f32>();
+    let avionics_cost = summary.avionics.map_or(0.0, |avionics| avionics.into_avionics_key().cost());
+    let warheads_cost = summary.warheads.iter()
+      .map(|&(warhead_key, size)| warhead_key.base_cost() * size.get() as f32).sum::<f32>();
+
+    let engines = match self.body_key.missile_body().variant {
+      MissileBodyVariant::Conventional { engine, .. } => [Some(engine), None],
+      MissileBodyVariant::Hybrid { cruise_engine, sprint_engine, .. } => [Some(cruise_engine), Some(sprint_engine)]
+    };
 
-  pub fn get_summary(&self) -> crate::loadout::MissileTemplateSummary {
-    crate::loadout::MissileTemplateSummary::from_iter(self.sockets.iter().copied())
-  }
-}
+    let engines_cost = summary.engines.iter().zip(engines.into_iter().flatten())
+      .map(|(&(engine_settings, size), engine)| engine.setup_info(engine_settings, size.get()).cost)
+      .sum::<f32>();
 
-impl DeserializeElement for MissileTemplate {
-  type Error = FormatError;
+    seekers_cost + auxiliary_cost + avionics_cost + warheads_cost + engines_cost
+  }
 
-  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("MissileTemplate")?;
-
-    let [
-      associated_template_name, designation, nickname, description, long_description,
-      cost, body_key, template_key, base_color, stripe_color, sockets
-    ] = element.children.find_elements([
-      "AssociatedTemplateName", "Designation", "Nickname", "Description", "LongDescription",
-      "Cost", "BodyKey", "TemplateKey", "BaseColor", "StripeColor", "Sockets"
-    ])?;
-
-    let associated_template_name = associated_template_name.map(|element| element.children.deserialize::<String>()).transpose()?;
-    let designation = designation.ok_or(xml::Error::missing_element("Designation"))?.children.deserialize::<String>()?;
-    let nickname = nickname.ok_or(xml::Error::missing_element("Nickname"))?.children.deserialize::<String>()?;
-    let description = description.ok_or(xml::Error::missing_element("Description"))?.children.deserialize::<String>()?;
-    let long_description = long_description.ok_or(xml::Error::missing_element("LongDescription"))?.children.deserialize::<String>()?;
-    let cost = cost.ok_or(xml::Error::missing_element("Cost"))?.children.deserialize::<usize>()?;
-    let body_key = body_key.ok_or(xml::Error::missing_element("BodyKey"))?.children.deserialize::<MissileBodyKey>()?;
-    let template_key = template_key.ok_or(xml::Error::missing_element("TemplateKey"))?.children.deserialize::<Uuid>()?;
-    let base_color = base_color.ok_or(xml::Error::missing_element("BaseColor"))?.children.deserialize::<Color>()?;
-    let stripe_color = stripe_color.ok_or(xml::Error::missing_element("StripeColor"))?.children.deserialize::<Color>()?;
-    let sockets = sockets.ok_or(xml::Error::missing_element("Sockets"))?.children.deserialize::<Vec<MissileSocket>>()?;
-
-    Ok(MissileTemplate {
-      associated_template_name,
-      designation,
-      nickname,
-      description,
-      long_description,
-      cost,
-      body_key,
-      template_key,
-      base_color,
-      stripe_color,
-      sockets
-    })
+  /// Whether this template has the same design as another, ignoring their names and template keys.
+  pub fn is_same_design(&self, other: &Self) -> bool {
+    MissileTemplate { associated_template_name: None, template_key: Uuid::nil(), ..self.clone() } ==
+    MissileTemplate { associated_template_name: None, template_key: Uuid::nil(), ..other.clone() }
   }
-}
 
-impl SerializeElement for MissileTemplate {
-  type Error = Infallible;
+  /// Checks that the cost stored in this template matches its calculated cost.
+  pub fn check_cost(&self) -> Result<(), MissileCostMismatch> {
+    let calculated = self.calculate_cost();
+    if self.cost == calculated {
+      Ok(())
+    } else {
+      Err(MissileCostMismatch { stored: self.cost, calculated })
+    }
+  }
 
-  fn serialize_element(self) -> Result<Element, Self::Error> {
-    let name = self.associated_template_name
-      .map(String::serialize_nodes).transpose()?
-      .map(|nodes| Element::new("AssociatedTemplateName", nodes));
-    let nodes = [
-      Element::new("Designation", self.designation.serialize_nodes()?),
-      Element::new("Nickname", self.nickname.serialize_nodes()?),
-      Element::new("Description", self.description.serialize_nodes()?),
-      Element::new("LongDescription", self.long_description.serialize_nodes()?),
-      Element::new("Cost", self.cost.serialize_nodes()?),
-      Element::new("BodyKey", self.body_key.serialize_nodes()?),
-      Element::new("TemplateKey", self.template_key.serialize_nodes()?),
-      Element::new("BaseColor", self.base_color.serialize_nodes()?),
-      Element::new("StripeColor", self.stripe_color.serialize_nodes()?),
-      Element::new("Sockets", self.sockets.serialize_nodes()?)
-    ];
-
-    Ok(Element::new("MissileTemplate", Nodes::from_iter(chain_iter!(name, nodes))))
+  pub fn get_summary(&self) -> crate::loadout::MissileTemplateSummary {
+    crate::loadout::MissileTemplateSummary::from_iter(self.sockets.iter().copied())
   }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, DeserializeElement, SerializeElement)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[xml(error = FormatError)]
 pub struct MissileSocket {
   pub size: zsize,
+  #[xml(element)]
   pub installed_component: Option<MissileComponent>
 }
 
-impl DeserializeElement for MissileSocket {
-  type Error = FormatError;
-
-  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("MissileSocket")?;
-    let [size, installed_component] = element.children.find_elements(["Size", "InstalledComponent"])?;
-    let size = size.ok_or(xml::Error::missing_element("Size"))?.children.deserialize::<zsize>()?;
-    let installed_component = installed_component.map(MissileComponent::deserialize_element).transpose()?;
-    Ok(MissileSocket { size, installed_component })
-  }
-}
-
-impl SerializeElement for MissileSocket {
-  type Error = Infallible;
-
-  fn serialize_element(self) -> Result<Element, Self::Error> {
-    let size = Element::new("Size", self.size.serialize_nodes()?);
-    let installed_component = self.installed_component.map(MissileComponent::serialize_element).transpose()?;
-
-    let iter = std::iter::once(size).chain(installed_component);
-    Ok(Element::new("MissileSocket", Nodes::from_iter(iter)))
-  }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct MissileComponent {
@@ -974,89 +1150,91 @@ impl DeserializeElement for MissileComponent {
   type Error = FormatError;
 
   fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("InstalledComponent")?;
-    let [xsi_type] = element.attributes.find_attributes(["xsi:type"])?;
-
-    let [
-      component_key, mode, reject_unvalidated, target_type, detect_pd_targets, role, hot_launch,
-      self_destruct_on_lost, maneuvers, defensive_doctrine, approach_angle_control, balance_values
-    ] = element.children.find_elements([
-      "ComponentKey", "Mode", "RejectUnvalidated", "TargetType", "DetectPDTargets", "Role", "HotLaunch",
-      "SelfDestructOnLost", "Maneuvers", "DefensiveDoctrine", "ApproachAngleControl", "BalanceValues"
-    ])?;
-
-    let mode = mode.ok_or(xml::Error::missing_element("Mode"))
-      .and_then(|element| element.children.deserialize::<SeekerMode>());
-    let reject_unvalidated = reject_unvalidated
-      .map(|element| element.children.deserialize::<bool>())
-      .transpose()?.unwrap_or(false);
-    let target_type = target_type.ok_or(xml::Error::missing_element("TargetType"))
-      .and_then(|element| element.children.deserialize::<AntiRadiationTargetType>());
-    let detect_pd_targets = detect_pd_targets.ok_or(xml::Error::missing_element("DetectPDTargets"))
-      .and_then(|element| element.children.deserialize::<bool>());
-    let role = role.ok_or(xml::Error::missing_element("Role"))
-      .and_then(|element| element.children.deserialize::<MissileRole>());
-    let hot_launch = hot_launch.ok_or(xml::Error::missing_element("HotLaunch"))
-      .and_then(|element| element.children.deserialize::<bool>());
-    let self_destruct_on_lost = self_destruct_on_lost.ok_or(xml::Error::missing_element("SelfDestructOnLost"))
-      .and_then(|element| element.children.deserialize::<bool>());
-    let maneuvers = maneuvers.ok_or(xml::Error::missing_element("Maneuvers"))
-      .and_then(|element| element.children.deserialize::<Maneuvers>());
-    let defensive_doctrine = defensive_doctrine.ok_or(xml::Error::missing_element("DefensiveDoctrine"))
-      .map_err(FormatError::from).and_then(DefensiveDoctrine::deserialize_element);
-    let approach_angle_control = approach_angle_control.ok_or(xml::Error::missing_element("ApproachAngleControl"))
-      .and_then(|element| element.children.deserialize::<bool>());
-    let balance_values = balance_values.ok_or(xml::Error::missing_element("BalanceValues"));
-
-    let component_key = component_key.filter(|element| !element.children.is_empty())
-      .map(|element| element.children.deserialize::<MissileComponentKey>()).transpose()?;
-    let settings = xsi_type.as_deref().map(|xsi_type| match xsi_type {
-      "ActiveSeekerSettings" => Ok(MissileComponentSettings::ActiveSeekerSettings {
-        mode: mode?,
-        reject_unvalidated,
-        detect_pd_targets: detect_pd_targets?
-      }),
-      "CommandSeekerSettings" => Ok(MissileComponentSettings::CommandSeekerSettings {
-        mode: mode?
-      }),
-      "DirectGuidanceSettings" => Ok(MissileComponentSettings::DirectGuidanceSettings {
-        hot_launch: hot_launch?,
-        self_destruct_on_lost: self_destruct_on_lost?,
-        maneuvers: maneuvers?,
-        defensive_doctrine: if role? == MissileRole::Defensive { Some(defensive_doctrine?) } else { None },
-        approach_angle_control: approach_angle_control?
-      }),
-      "CruiseGuidanceSettings" => Ok(MissileComponentSettings::CruiseGuidanceSettings {
-        hot_launch: hot_launch?,
-        self_destruct_on_lost: self_destruct_on_lost?,
-        maneuvers: maneuvers?,
-        defensive_doctrine: if role? == MissileRole::Defensive { Some(defensive_doctrine?) } else { None }
-      }),
-      "MissileEngineSettings" => Ok(MissileComponentSettings::MissileEngineSettings {
-        balance_values: balance_values.and_then(|element| {
-          let [a, b, c] = element.children.find_elements(["A", "B", "C"])?;
-          let a = a.ok_or(xml::Error::missing_element("A"))?.children.deserialize::<f32>()?;
-          let b = b.ok_or(xml::Error::missing_element("B"))?.children.deserialize::<f32>()?;
-          let c = c.ok_or(xml::Error::missing_element("C"))?.children.deserialize::<f32>()?;
-          Ok(EngineSettings::from_array([a, b, c]))
-        })?
-      }),
-      "PassiveARHSeekerSettings" => Ok(MissileComponentSettings::PassiveARHSeekerSettings {
-        mode: mode?,
-        reject_unvalidated,
-        home_on_jam: target_type? == AntiRadiationTargetType::JammingOnly
-      }),
-      "PassiveSeekerSettings" => Ok(MissileComponentSettings::PassiveSeekerSettings {
-        mode: mode?,
-        reject_unvalidated,
-        detect_pd_targets: detect_pd_targets?
-      }),
-      _ => Err(FormatError::UnknownMissileSettingsType(xsi_type.into()))
-    }).transpose()?;
-
-    Ok(MissileComponent {
-      component_key,
-      settings
+    element.located(|element| {
+      element.expect_named("InstalledComponent")?;
+      let [xsi_type] = element.attributes.find_attributes(["xsi:type"])?;
+
+      let [
+        component_key, mode, reject_unvalidated, target_type, detect_pd_targets, role, hot_launch,
+        self_destruct_on_lost, maneuvers, defensive_doctrine, approach_angle_control, balance_values
+      ] = element.children.find_elements([
+        "ComponentKey", "Mode", "RejectUnvalidated", "TargetType", "DetectPDTargets", "Role", "HotLaunch",
+        "SelfDestructOnLost", "Maneuvers", "DefensiveDoctrine", "ApproachAngleControl", "BalanceValues"
+      ])?;
+
+      let mode = mode.ok_or(xml::Error::missing_element("Mode"))
+        .and_then(|element| element.children.deserialize::<SeekerMode>());
+      let reject_unvalidated = reject_unvalidated
+        .map(|element| element.children.deserialize::<bool>())
+        .transpose()?.unwrap_or(false);
+      let target_type = target_type.ok_or(xml::Error::missing_element("TargetType"))
+        .and_then(|element| element.children.deserialize::<AntiRadiationTargetType>());
+      let detect_pd_targets = detect_pd_targets.ok_or(xml::Error::missing_element("DetectPDTargets"))
+        .and_then(|element| element.children.deserialize::<bool>());
+      let role = role.ok_or(xml::Error::missing_element("Role"))
+        .and_then(|element| element.children.deserialize::<MissileRole>());
+      let hot_launch = hot_launch.ok_or(xml::Error::missing_element("HotLaunch"))
+        .and_then(|element| element.children.deserialize::<bool>());
+      let self_destruct_on_lost = self_destruct_on_lost.ok_or(xml::Error::missing_element("SelfDestructOnLost"))
+        .and_then(|element| element.children.deserialize::<bool>());
+      let maneuvers = maneuvers.ok_or(xml::Error::missing_element("Maneuvers"))
+        .and_then(|element| element.children.deserialize::<Maneuvers>());
+      let defensive_doctrine = defensive_doctrine.ok_or(xml::Error::missing_element("DefensiveDoctrine"))
+        .map_err(FormatError::from).and_then(DefensiveDoctrine::deserialize_element);
+      let approach_angle_control = approach_angle_control.ok_or(xml::Error::missing_element("ApproachAngleControl"))
+        .and_then(|element| element.children.deserialize::<bool>());
+      let balance_values = balance_values.ok_or(xml::Error::missing_element("BalanceValues"));
+
+      let component_key = component_key.filter(|element| !element.children.is_empty())
+        .map(|element| element.children.deserialize::<MissileComponentKey>()).transpose()?;
+      let settings = xsi_type.as_deref().map(|xsi_type| match xsi_type {
+        "ActiveSeekerSettings" => Ok(MissileComponentSettings::ActiveSeekerSettings {
+          mode: mode?,
+          reject_unvalidated,
+          detect_pd_targets: detect_pd_targets?
+        }),
+        "CommandSeekerSettings" => Ok(MissileComponentSettings::CommandSeekerSettings {
+          mode: mode?
+        }),
+        "DirectGuidanceSettings" => Ok(MissileComponentSettings::DirectGuidanceSettings {
+          hot_launch: hot_launch?,
+          self_destruct_on_lost: self_destruct_on_lost?,
+          maneuvers: maneuvers?,
+          defensive_doctrine: if role? == MissileRole::Defensive { Some(defensive_doctrine?) } else { None },
+          approach_angle_control: approach_angle_control?
+        }),
+        "CruiseGuidanceSettings" => Ok(MissileComponentSettings::CruiseGuidanceSettings {
+          hot_launch: hot_launch?,
+          self_destruct_on_lost: self_destruct_on_lost?,
+          maneuvers: maneuvers?,
+          defensive_doctrine: if role? == MissileRole::Defensive { Some(defensive_doctrine?) } else { None }
+        }),
+        "MissileEngineSettings" => Ok(MissileComponentSettings::MissileEngineSettings {
+          balance_values: balance_values.and_then(|element| {
+            let [a, b, c] = element.children.find_elements(["A", "B", "C"])?;
+            let a = a.ok_or(xml::Error::missing_element("A"))?.children.deserialize::<f32>()?;
+            let b = b.ok_or(xml::Error::missing_element("B"))?.children.deserialize::<f32>()?;
+            let c = c.ok_or(xml::Error::missing_element("C"))?.children.deserialize::<f32>()?;
+            Ok(EngineSettings::from_array([a, b, c]))
+          })?
+        }),
+        "PassiveARHSeekerSettings" => Ok(MissileComponentSettings::PassiveARHSeekerSettings {
+          mode: mode?,
+          reject_unvalidated,
+          home_on_jam: target_type? == AntiRadiationTargetType::JammingOnly
+        }),
+        "PassiveSeekerSettings" => Ok(MissileComponentSettings::PassiveSeekerSettings {
+          mode: mode?,
+          reject_unvalidated,
+          detect_pd_targets: detect_pd_targets?
+        }),
+        _ => Err(FormatError::UnknownMissileSettingsType(xsi_type.into()))
+      }).transpose()?;
+
+      Ok(MissileComponent {
+        component_key,
+        settings
+      })
     })
   }
 }
@@ -1184,10 +1362,12 @@ fn serialize_settings(settings: MissileComponentSettings) -> Result<(&'static st
   })
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeserializeElement, SerializeElement)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[xml(error = FormatError)]
 pub struct DefensiveDoctrine {
   pub target_size_mask: MissileSizeMask,
+  #[xml(default)]
   pub target_type: DefensiveTargetType,
   pub target_size_ordering: Ordering,
   pub salvo_size: usize,
@@ -1208,45 +1388,6 @@ impl Default for DefensiveDoctrine {
   }
 }
 
-impl DeserializeElement for DefensiveDoctrine {
-  type Error = FormatError;
-
-  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
-    element.expect_named("DefensiveDoctrine")?;
-    let [target_size_mask, target_type, target_size_ordering, salvo_size, farthest_first] = element.children
-      .find_elements(["TargetSizeMask", "TargetType", "TargetSizeOrdering", "SalvoSize", "FarthestFirst"])?;
-
-    let target_size_mask = target_size_mask.ok_or(xml::Error::missing_element("TargetSizeMask"))?.children.deserialize::<MissileSizeMask>()?;
-    let target_type = target_type.map(|element| element.children.deserialize::<DefensiveTargetType>()).transpose()?.unwrap_or_default();
-    let target_size_ordering = target_size_ordering.ok_or(xml::Error::missing_element("TargetSizeOrdering"))?.children.deserialize::<Ordering>()?;
-    let salvo_size = salvo_size.ok_or(xml::Error::missing_element("SalvoSize"))?.children.deserialize::<usize>()?;
-    let farthest_first = farthest_first.ok_or(xml::Error::missing_element("FarthestFirst"))?.children.deserialize::<bool>()?;
-
-    Ok(DefensiveDoctrine {
-      target_size_mask,
-      target_type,
-      target_size_ordering,
-      salvo_size,
-      farthest_first
-    })
-  }
-}
-
-impl SerializeElement for DefensiveDoctrine {
-  type Error = Infallible;
-
-  fn serialize_element(self) -> Result<Element, Self::Error> {
-    let target_size_mask = Element::new("TargetSizeMask", self.target_size_mask.serialize_nodes()?);
-    let target_type = Element::new("TargetType", self.target_type.serialize_nodes()?);
-    let target_size_ordering = Element::new("TargetSizeOrdering", self.target_size_ordering.serialize_nodes()?);
-    let salvo_size = Element::new("SalvoSize", self.salvo_size.serialize_nodes()?);
-    let farthest_first = Element::new("FarthestFirst", self.farthest_first.serialize_nodes()?);
-
-    let iter = [target_size_mask, target_type, target_size_ordering, salvo_size, farthest_first];
-    Ok(Element::new("DefensiveDoctrine", Nodes::from_iter(iter)))
-  }
-}
-
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
@@ -1718,6 +1859,12 @@ impl<T> Vector3<T> {
   }
 }
 
+impl Vector3<f32> {
+  pub fn length(self) -> f32 {
+    (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+  }
+}
+
 impl<T> DeserializeNodes for Vector3<T> where T: DeserializeNodes {
   type Error = xml::DeserializeErrorWrapper<T::Error>;
 
@@ -1822,3 +1969,296 @@ impl AddAssign for Costs {
     self.missiles += rhs.missiles;
   }
 }
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn missile_socket(size: usize, component_key: Option<MissileComponentKey>, settings: Option<MissileComponentSettings>) -> MissileSocket {
+    MissileSocket {
+      size: zsize::new(size).unwrap(),
+      installed_component: Some(MissileComponent { component_key, settings })
+    }
+  }
+
+  /// Sockets for a command guided missile costing 6 points.
+  fn command_missile_sockets() -> Vec<MissileSocket> {
+    vec![
+      missile_socket(1, Some(MissileComponentKey::CommandReceiver), Some(MissileComponentSettings::CommandSeekerSettings {
+        mode: SeekerMode::Targeting
+      })),
+      missile_socket(1, Some(MissileComponentKey::CruiseGuidance), Some(MissileComponentSettings::CruiseGuidanceSettings {
+        hot_launch: false,
+        self_destruct_on_lost: false,
+        maneuvers: Maneuvers::None,
+        defensive_doctrine: None
+      })),
+      missile_socket(2, Some(MissileComponentKey::HEImpact), None),
+      missile_socket(4, None, Some(MissileComponentSettings::MissileEngineSettings {
+        balance_values: EngineSettings::default()
+      }))
+    ]
+  }
+
+  #[test]
+  fn test_missile_cost() {
+    let white = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+    let mut missile_template = MissileTemplate {
+      associated_template_name: None,
+      designation: "SGM-1".to_owned(),
+      nickname: "Test".to_owned(),
+      description: String::new(),
+      long_description: String::new(),
+      cost: 4,
+      body_key: MissileBodyKey::SGM1Balestra,
+      template_key: Uuid::nil(),
+      base_color: white,
+      stripe_color: white,
+      sockets: command_missile_sockets(),
+      extra: Extra::new()
+    };
+
+    // command receiver (3.5) + cruise guidance (2.0) + two segments of he impact (0.5)
+    assert_eq!(missile_template.calculate_cost_exact(), 6.0);
+    assert_eq!(missile_template.check_cost(), Err(MissileCostMismatch { stored: 4, calculated: 6 }));
+    missile_template.cost = 6;
+    assert_eq!(missile_template.check_cost(), Ok(()));
+  }
+
+  #[test]
+  fn test_error_location() {
+    let ship = |sockets: &str| format!("<Ship><Key>00000000-0000-0000-0000-000000000000</Key><Name>Ship</Name><Cost>0</Cost><Number>1</Number>\
+      <HullType>Stock/Sprinter Corvette</HullType><SocketMap>{sockets}</SocketMap><WeaponGroups /></Ship>");
+    let socket = |component_data: &str| format!("<HullSocket><Key>wDsRnL5nKkyYvKgD6VcPHg</Key>\
+      <ComponentName>Stock/Bulk Magazine</ComponentName>{component_data}</HullSocket>");
+    let sockets = socket("") + &socket("<ComponentData xsi:type=\"Bogus\" />");
+    let fleet = format!(
+      "<Fleet>\n<Name>Test</Name><TotalPoints>0</TotalPoints><FactionKey>Stock/Alliance</FactionKey>\n<Ships>\n{}\n{}\n</Ships>\n</Fleet>",
+      ship(""), ship(&sockets)
+    );
+
+    let error = Root::<Fleet>::deserialize_nodes(read_nodes(fleet.as_bytes()).unwrap()).unwrap_err();
+    let location = error.location().unwrap();
+    assert_eq!(location.path.as_ref(), "Fleet/Ships/Ship[2]/SocketMap/HullSocket[2]/ComponentData");
+    assert_eq!((location.position.line, location.position.column), (5, 361));
+    assert!(matches!(error, FormatError::Located(_, error) if matches!(*error, FormatError::UnknownComponentDataType(..))));
+  }
+
+  #[test]
+  fn test_roundtrip_extra() {
+    let fleet = "<Fleet Modded=\"true\" xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\
+      <Name>Test</Name><Version>3</Version><Mystery>1</Mystery><TotalPoints>0</TotalPoints><FactionKey>Stock/Alliance</FactionKey>\
+      <SortOverrideOrder><int>1</int></SortOverrideOrder><Ships><Ship><SaveID xsi:nil=\"true\" /><Key>00000000-0000-0000-0000-000000000000</Key>\
+      <Name>Ship</Name><Cost>0</Cost><Callsign /><Number>1</Number><SymbolOption>2</SymbolOption><HullType>Stock/Sprinter Corvette</HullType><SocketMap><HullSocket Flag=\"x\">\
+      <Key>wDsRnL5nKkyYvKgD6VcPHg</Key><ComponentName>Stock/Bulk Magazine</ComponentName><Note>y</Note></HullSocket></SocketMap>\
+      <WeaponGroups /><TemplateMissileTypes /><Trailer /></Ship></Ships><MissileTypes /></Fleet>";
+
+    let nodes = read_nodes(fleet.as_bytes()).unwrap();
+    let root = Root::<Fleet>::deserialize_nodes(nodes.clone()).unwrap();
+    assert!(root.element.extra.contains_element("Mystery"));
+    assert!(root.element.ships[0].extra.contains_element("Trailer"));
+    assert_eq!(root.serialize_nodes().unwrap(), nodes);
+  }
+
+  fn fleet_with_missiles(name: &str, ships: &[(u128, &str)], missiles: &[(&str, usize)]) -> Fleet {
+    let total_points = ships.len() * 10;
+    let ships = ships.iter().map(|&(key, missile)| format!("<Ship><Key>{}</Key><Name>Ship</Name><Cost>10</Cost><Number>1</Number>\
+      <HullType>Stock/Sprinter Corvette</HullType><SocketMap><HullSocket><Key>wDsRnL5nKkyYvKgD6VcPHg</Key><ComponentName>Stock/Bulk Magazine</ComponentName>\
+      <ComponentData xsi:type=\"BulkMagazineData\"><Load><MagSaveData><MagazineKey>wDsRnL5nKkyYvKgD6VcPHg</MagazineKey>\
+      <MunitionKey>$MODMIS$/{missile}</MunitionKey><Quantity>4</Quantity></MagSaveData></Load></ComponentData></HullSocket></SocketMap>\
+      <WeaponGroups /></Ship>", Uuid::from_u128(key))).collect::<String>();
+    let fleet = format!("<Fleet><Name>{name}</Name><TotalPoints>{total_points}</TotalPoints><FactionKey>Stock/Alliance</FactionKey><Ships>{ships}</Ships></Fleet>");
+    let mut fleet = Fleet::deserialize_element(read_nodes(fleet.as_bytes()).unwrap().try_into_one_element().unwrap()).unwrap();
+
+    let white = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+    fleet.missile_types = missiles.iter().map(|&(name, cost)| MissileTemplate {
+      associated_template_name: Some(name.to_owned()),
+      designation: "SGM-1".to_owned(),
+      nickname: name.to_owned(),
+      description: String::new(),
+      long_description: String::new(),
+      cost,
+      body_key: MissileBodyKey::SGM1Balestra,
+      template_key: Uuid::nil(),
+      base_color: white,
+      stripe_color: white,
+      sockets: Vec::new(),
+      extra: Extra::new()
+    }).collect();
+    fleet
+  }
+
+  /// Builds a ship with each component installed in the first empty socket of the hull that it is legal in.
+  fn ship_with_components(key: u128, hull_type: HullKey, components: &[ComponentKey]) -> Ship {
+    let hull = hull_type.hull();
+    let mut socket_map = Vec::<HullSocket>::new();
+    for &component_name in components {
+      let hull_socket = hull.sockets.iter()
+        .filter(|hull_socket| socket_map.iter().all(|installed| installed.key != hull_socket.save_key))
+        .find(|hull_socket| component_name.component().is_legal_in(hull_type, hull_socket))
+        .expect("no socket for component");
+      socket_map.push(HullSocket { key: hull_socket.save_key, component_name, component_data: None, extra: Extra::new() });
+    };
+
+    Ship {
+      key: Uuid::from_u128(key),
+      name: "Ship".to_owned(),
+      cost: 0,
+      callsign: None,
+      number: 1,
+      hull_type,
+      hull_config: None,
+      socket_map,
+      weapon_groups: Vec::new(),
+      initial_formation: None,
+      missile_types: Vec::new(),
+      extra: Extra::new()
+    }
+  }
+
+  #[test]
+  fn test_ship_compounding_costs() {
+    // CICs compound with a multiplier of 1, the most expensive being counted first
+    let ship = ship_with_components(1, HullKey::SolomonBattleship, &[ComponentKey::BasicCIC, ComponentKey::CitadelCIC, ComponentKey::ReinforcedCIC]);
+    let costs = ship.calculate_costs(&[]);
+    assert_eq!(costs.hulls, HullKey::SolomonBattleship.hull().point_cost);
+    assert_eq!(costs.components, 75 + 25 + 10 * 2);
+
+    // Mine launchers compound with a multiplier of 2
+    let ship = ship_with_components(2, HullKey::MoorlineLineShip, &[ComponentKey::ML9MineLauncher; 3]);
+    assert_eq!(ship.calculate_costs(&[]).components, 10 + 10 * 2 + 10 * 4);
+
+    // The first magazine is free, and the rest do not compound
+    let ship = ship_with_components(3, HullKey::SolomonBattleship, &[ComponentKey::BulkMagazine; 3]);
+    let bulk_magazine = ComponentKey::BulkMagazine.component();
+    let magazine_costs = ship.socket_map.iter()
+      .map(|hull_socket| bulk_magazine.cost(HullKey::SolomonBattleship.hull().get_socket(hull_socket.key).unwrap().size).unwrap())
+      .collect::<Vec<usize>>();
+    let expected = magazine_costs.iter().sum::<usize>() - magazine_costs.iter().max().unwrap();
+    assert_eq!(ship.calculate_costs(&[]).components, expected);
+  }
+
+  #[test]
+  fn test_recalculate_costs() {
+    // Stored missile costs are ignored in favour of costs calculated from the missiles' components
+    let mut fleet = fleet_with_missiles("A", &[(1, "Empty"), (2, "Guided")], &[("Empty", 5), ("Guided", 5)]);
+    fleet.missile_types[1].sockets = command_missile_sockets();
+    fleet.ships.push(ship_with_components(3, HullKey::SolomonBattleship, &[ComponentKey::BasicCIC, ComponentKey::BasicCIC]));
+    fleet.recalculate_costs();
+
+    // Sprinters with one free magazine holding four missiles each, and a battleship with two compounding CICs
+    let sprinter = HullKey::SprinterCorvette.hull().point_cost;
+    let solomon = HullKey::SolomonBattleship.hull().point_cost;
+    let costs = fleet.ships.iter().map(|ship| ship.cost).collect::<Vec<usize>>();
+    assert_eq!(costs, [sprinter, sprinter + 4 * 6, solomon + 10 + 10]);
+    assert_eq!(fleet.total_points, costs.iter().sum::<usize>());
+  }
+
+  #[cfg(feature = "rand")]
+  #[test]
+  fn test_merge() {
+    let mut fleet = fleet_with_missiles("A", &[(1, "Shared"), (2, "Clash")], &[("Shared", 1), ("Clash", 1)]);
+    let mut other = fleet_with_missiles("B", &[(1, "Shared"), (3, "Clash")], &[("Shared", 1), ("Clash", 2)]);
+    other.ships[1].initial_formation = Some(InitialFormation { guide_key: Uuid::from_u128(1), relative_position: Vector3 { x: 0.0, y: 0.0, z: 0.0 } });
+    let mut protectorate = other.clone();
+    protectorate.faction_key = Faction::Protectorate;
+    let error = fleet.merge(protectorate, &mut rand::rngs::mock::StepRng::new(1, 1)).unwrap_err();
+    assert_eq!(error, FactionMismatchError { expected: Faction::Alliance, found: Faction::Protectorate });
+    assert_eq!(fleet.ships.len(), 2);
+
+    fleet.merge(other, &mut rand::rngs::mock::StepRng::new(1, 1)).unwrap();
+
+    assert_eq!(fleet.ships.len(), 4);
+    assert_eq!(fleet.total_points, 40);
+    assert_eq!(fleet.ships.iter().map(|ship| ship.key).collect::<HashSet<Uuid>>().len(), 4);
+    let magazine_keys = fleet.ships.iter().flat_map(Ship::iter_magazines).map(|magazine| magazine.magazine_key).collect::<HashSet<Key>>();
+    // The ships of the first fleet share a magazine key, which is left as it is
+    assert_eq!(magazine_keys.len(), 3);
+
+    let missile_names = fleet.missile_types.iter().filter_map(|missile| missile.associated_template_name.as_deref()).collect::<Vec<&str>>();
+    assert_eq!(missile_names, ["Shared", "Clash", "Clash (2)"]);
+    assert_eq!(fleet.ships[2].iter_missile_names().collect::<Vec<&str>>(), ["Shared"]);
+    assert_eq!(fleet.ships[3].iter_missile_names().collect::<Vec<&str>>(), ["Clash (2)"]);
+    assert_eq!(fleet.ships[3].initial_formation.unwrap().guide_key, fleet.ships[2].key);
+  }
+
+  #[cfg(feature = "rand")]
+  #[test]
+  fn test_dupe_ships_beside() {
+    let mut fleet = fleet_with_missiles("A", &[(1, "One"), (2, "One"), (3, "One")], &[]);
+    fleet.ships[1].initial_formation = Some(InitialFormation { guide_key: Uuid::from_u128(1), relative_position: Vector3 { x: 0.0, y: 0.0, z: 100.0 } });
+    let width = fleet.formation_width(Uuid::from_u128(1));
+    assert_eq!(width, (100.0 + HullKey::SprinterCorvette.hull().formation_radius()) * 2.0);
+
+    let copies = fleet.dupe_ships_beside(2, &mut rand::rngs::mock::StepRng::new(1, 1));
+    let formations = copies.iter().map(|ship| ship.initial_formation.unwrap()).collect::<Vec<InitialFormation>>();
+    assert_eq!(formations[0].guide_key, Uuid::from_u128(1));
+    assert_eq!(formations[0].relative_position, Vector3 { x: width * 2.0, y: 0.0, z: 0.0 });
+    assert_eq!(formations[1].guide_key, Uuid::from_u128(1));
+    assert_eq!(formations[1].relative_position, Vector3 { x: width * 2.0, y: 0.0, z: 100.0 });
+    assert_eq!(formations[2].guide_key, Uuid::from_u128(3));
+  }
+
+  #[test]
+  fn test_split() {
+    let mut fleet = fleet_with_missiles("A", &[(1, "One"), (2, "Two"), (3, "Two")], &[("One", 1), ("Two", 1), ("Unused", 1)]);
+    fleet.ships[2].initial_formation = Some(InitialFormation { guide_key: Uuid::from_u128(1), relative_position: Vector3 { x: 0.0, y: 0.0, z: 0.0 } });
+    let (a, b) = fleet.split(|ship| ship.key == Uuid::from_u128(1));
+
+    assert_eq!((a.ships.len(), a.total_points), (1, 10));
+    assert_eq!((b.ships.len(), b.total_points), (2, 20));
+    assert_eq!(a.missile_types.iter().map(|missile| missile.nickname.as_str()).collect::<Vec<&str>>(), ["One"]);
+    assert_eq!(b.missile_types.iter().map(|missile| missile.nickname.as_str()).collect::<Vec<&str>>(), ["Two"]);
+    assert_eq!(b.ships[1].initial_formation, None);
+  }
+
+  #[test]
+  fn test_fleet_header() {
+    // The ships are malformed, but are never read
+    let fleet = "<Fleet><Name>Test</Name><TotalPoints>1500</TotalPoints><FactionKey>Stock/Protectorate</FactionKey>\
+      <Description>A &amp; B</Description><Ships><Ship><Bogus /></Ship></Ships><MissileTypes /></Fleet>";
+    let header = FleetHeader::read(fleet.as_bytes()).unwrap();
+    assert_eq!(header, FleetHeader {
+      name: "Test".to_owned(),
+      total_points: 1500,
+      faction_key: Faction::Protectorate,
+      description: Some("A & B".to_owned())
+    });
+
+    let element = read_nodes(fleet.as_bytes()).unwrap().try_into_one_element().unwrap();
+    assert_eq!(FleetHeader::deserialize_element(element).unwrap(), header);
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_bridge() {
+    let mut fleet = fleet_with_missiles("Serde", &[(1, "Guided")], &[("Guided", 6)]);
+    fleet.missile_types[0].sockets = command_missile_sockets();
+    let mut ship = ship_with_components(2, HullKey::SolomonBattleship, &[ComponentKey::BasicCIC]);
+    ship.initial_formation = Some(InitialFormation { guide_key: Uuid::from_u128(1), relative_position: Vector3 { x: 1.5, y: 0.0, z: -2.0 } });
+    ship.hull_config = Some(Box::new(HullConfig::RandomHullConfiguration {
+      primary_structure: [0, 1, 2].map(|i| SegmentConfiguration { key: Uuid::from_u128(i), dressing: vec![i as usize; i as usize], extra: Extra::new() }),
+      secondary_structure: SecondaryStructureConfig { key: Uuid::from_u128(3), segment: 1, snap_point: 2 },
+      hull_tint: Color { r: 0.5, g: 0.25, b: 0.125, a: 1.0 },
+      texture_variation: Vector3 { x: 1.0, y: 2.0, z: 3.0 }
+    }));
+    fleet.ships.push(ship);
+
+    let element = xml::serde::to_element(&fleet).unwrap();
+    assert_eq!(xml::serde::from_element::<Fleet>(element).unwrap(), fleet);
+    let hull_config = fleet.ships[1].hull_config.as_deref().unwrap();
+    let element = xml::serde::to_element(hull_config).unwrap();
+    assert_eq!(element.attributes.list.first().map(|(name, value)| (&**name, &**value)), Some(("xsi:type", "RandomHullConfiguration")));
+    assert_eq!(xml::serde::from_element::<HullConfig>(element).unwrap(), *hull_config);
+
+    // Types whose serde fields carry the same values as the game's elements are written the same way by both.
+    // Fleets and ships are not, since their hull, component and faction keys are serialized in the readable form used for JSON.
+    let initial_formation = fleet.ships[1].initial_formation.unwrap();
+    assert_eq!(xml::serde::to_element(&initial_formation).unwrap(), initial_formation.serialize_element().unwrap());
+    let HullConfig::RandomHullConfiguration { secondary_structure, hull_tint, .. } = hull_config.clone();
+    assert_eq!(xml::serde::to_element(&secondary_structure).unwrap(), secondary_structure.serialize_element().unwrap());
+    assert_eq!(xml::serde::to_element_named(&hull_tint, "HullTint").unwrap(), Element::new("HullTint", hull_tint.serialize_nodes().unwrap()));
+  }
+}
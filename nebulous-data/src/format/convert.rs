@@ -0,0 +1,157 @@
+//! Conversion of save files between the game's XML and JSON or YAML, enabled with the `convert` feature.
+//!
+//! Converted values keep every element and attribute their XML form had, including ones this crate
+//! does not understand (see [`Extra`][xml::Extra]), so a file converted to JSON or YAML and back again
+//! can still be loaded by the game.
+
+use super::{Fleet, FormatError, MissileTemplate, Root, Ship};
+use super::file::SaveKind;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use xml::{DeserializeElement, DeserializeNodes, SerializeElement, SerializeNodes, Indent, Version};
+
+use std::convert::Infallible;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+
+
+#[derive(Debug, Error)]
+pub enum ConvertError {
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+  #[error(transparent)]
+  Format(#[from] FormatError),
+  #[error(transparent)]
+  Json(#[from] serde_json::Error),
+  #[error(transparent)]
+  Yaml(#[from] serde_yaml::Error)
+}
+
+impl From<xml::Error> for ConvertError {
+  fn from(error: xml::Error) -> Self {
+    ConvertError::Format(FormatError::XmlError(error))
+  }
+}
+
+/// A text format that save files can be converted to and from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextFormat {
+  Json,
+  Yaml
+}
+
+impl TextFormat {
+  pub const fn extension(self) -> &'static str {
+    match self {
+      TextFormat::Json => "json",
+      TextFormat::Yaml => "yaml"
+    }
+  }
+
+  pub fn from_extension(extension: &OsStr) -> Option<Self> {
+    extension.to_str()?.parse().ok()
+  }
+
+  pub fn to_string<T: Serialize>(self, value: &T) -> Result<String, ConvertError> {
+    Ok(match self {
+      TextFormat::Json => serde_json::to_string_pretty(value)? + "\n",
+      TextFormat::Yaml => serde_yaml::to_string(value)?
+    })
+  }
+
+  pub fn from_str<T: DeserializeOwned>(self, text: &str) -> Result<T, ConvertError> {
+    Ok(match self {
+      TextFormat::Json => serde_json::from_str(text)?,
+      TextFormat::Yaml => serde_yaml::from_str(text)?
+    })
+  }
+}
+
+impl FromStr for TextFormat {
+  type Err = ParseTextFormatError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "json" => Ok(TextFormat::Json),
+      "yaml" | "yml" => Ok(TextFormat::Yaml),
+      _ => Err(ParseTextFormatError)
+    }
+  }
+}
+
+impl fmt::Display for TextFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.extension())
+  }
+}
+
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("invalid text format, expected one of json, yaml")]
+pub struct ParseTextFormatError;
+
+impl SaveKind {
+  /// Reads a save file of this kind and writes it out in the given text format.
+  pub fn xml_to_text<R: BufRead>(self, reader: R, format: TextFormat) -> Result<String, ConvertError> {
+    match self {
+      SaveKind::Fleet => xml_to_text::<Fleet, R>(reader, format),
+      SaveKind::Ship => xml_to_text::<Ship, R>(reader, format),
+      SaveKind::MissileTemplate => xml_to_text::<MissileTemplate, R>(reader, format)
+    }
+  }
+
+  /// Reads a value of this kind in the given text format and writes it out as a save file.
+  pub fn text_to_xml<W: Write>(self, text: &str, format: TextFormat, writer: W) -> Result<(), ConvertError> {
+    match self {
+      SaveKind::Fleet => text_to_xml::<Fleet, W>(text, format, writer),
+      SaveKind::Ship => text_to_xml::<Ship, W>(text, format, writer),
+      SaveKind::MissileTemplate => text_to_xml::<MissileTemplate, W>(text, format, writer)
+    }
+  }
+}
+
+/// Reads a save file whose root element is a `T` and writes it out in the given text format.
+pub fn xml_to_text<T, R>(reader: R, format: TextFormat) -> Result<String, ConvertError>
+where T: DeserializeElement<Error = FormatError> + Serialize, R: BufRead {
+  let nodes = xml::read_nodes(reader)?;
+  let root = <Root<T>>::deserialize_nodes(nodes)?;
+  format.to_string(&root.element)
+}
+
+/// Reads a `T` in the given text format and writes it out as a save file that the game can load.
+pub fn text_to_xml<T, W>(text: &str, format: TextFormat, writer: W) -> Result<(), ConvertError>
+where T: SerializeElement<Error = Infallible> + DeserializeOwned, W: Write {
+  let element = format.from_str::<T>(text)?;
+  let nodes = Root { element }.serialize_nodes().unwrap_or_else(|never| match never {});
+  xml::write_nodes(writer, &nodes, Some(Indent::default()), Some(Version::default()))?;
+  Ok(())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip_text() {
+    let fleet = "<Fleet Modded=\"true\" xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\
+      <Name>Test</Name><Version>3</Version><Mystery>1</Mystery><TotalPoints>0</TotalPoints><FactionKey>Stock/Alliance</FactionKey>\
+      <SortOverrideOrder xsi:nil=\"true\" /><Ships><Ship><SaveID xsi:nil=\"true\" /><Key>00000000-0000-0000-0000-000000000000</Key>\
+      <Name>Ship</Name><Cost>0</Cost><Callsign /><Number>1</Number><SymbolOption>0</SymbolOption><HullType>Stock/Sprinter Corvette</HullType>\
+      <SocketMap><HullSocket Flag=\"x\"><Key>wDsRnL5nKkyYvKgD6VcPHg</Key><ComponentName>Stock/Bulk Magazine</ComponentName><Note>y</Note></HullSocket></SocketMap>\
+      <WeaponGroups /><TemplateMissileTypes /><Trailer /></Ship></Ships><MissileTypes /></Fleet>";
+    let nodes = xml::read_nodes(fleet.as_bytes()).unwrap();
+
+    for format in [TextFormat::Json, TextFormat::Yaml] {
+      let text = SaveKind::Fleet.xml_to_text(fleet.as_bytes(), format).unwrap();
+      let mut buffer = Vec::new();
+      SaveKind::Fleet.text_to_xml(&text, format, &mut buffer).unwrap();
+      let root = <Root<Fleet>>::deserialize_nodes(xml::read_nodes(buffer.as_slice()).unwrap()).unwrap();
+      assert_eq!(root.serialize_nodes().unwrap(), nodes, "failed to round-trip through {format}");
+    };
+  }
+}
@@ -0,0 +1,226 @@
+//! Reading and writing whole save files: `.fleet`, `.ship` and `.missile` files.
+//!
+//! Each kind of save file holds a single root element, written with the namespace declarations the game expects
+//! (see [`Root`]). [`SaveFile`] reads any of them, telling them apart by their root element.
+
+use super::{Fleet, FormatError, MissileTemplate, Root, Ship};
+
+use xml::{DeserializeElement, DeserializeNodes, SerializeElement, SerializeNodes, Indent, Nodes, Version};
+
+use std::convert::Infallible;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+
+
+#[derive(Debug, Error)]
+pub enum FileError {
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+  #[error(transparent)]
+  Format(#[from] FormatError),
+  #[error("unknown save file root element {0:?}")]
+  UnknownRoot(Box<str>)
+}
+
+impl From<xml::Error> for FileError {
+  fn from(error: xml::Error) -> Self {
+    FileError::Format(FormatError::XmlError(error))
+  }
+}
+
+/// The kind of a save file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SaveKind {
+  Fleet,
+  Ship,
+  MissileTemplate
+}
+
+impl SaveKind {
+  pub const fn extension(self) -> &'static str {
+    match self {
+      SaveKind::Fleet => "fleet",
+      SaveKind::Ship => "ship",
+      SaveKind::MissileTemplate => "missile"
+    }
+  }
+
+  pub fn from_extension(extension: &OsStr) -> Option<Self> {
+    match extension.to_str()?.to_ascii_lowercase().as_str() {
+      "fleet" => Some(SaveKind::Fleet),
+      "ship" => Some(SaveKind::Ship),
+      "missile" => Some(SaveKind::MissileTemplate),
+      _ => None
+    }
+  }
+
+  /// The name of the root element of save files of this kind.
+  pub const fn root_name(self) -> &'static str {
+    match self {
+      SaveKind::Fleet => "Fleet",
+      SaveKind::Ship => "Ship",
+      SaveKind::MissileTemplate => "MissileTemplate"
+    }
+  }
+
+  pub fn from_root_name(name: &str) -> Option<Self> {
+    match name {
+      "Fleet" => Some(SaveKind::Fleet),
+      "Ship" => Some(SaveKind::Ship),
+      "MissileTemplate" => Some(SaveKind::MissileTemplate),
+      _ => None
+    }
+  }
+}
+
+/// The contents of a save file of any kind.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveFile {
+  Fleet(Fleet),
+  Ship(Ship),
+  MissileTemplate(MissileTemplate)
+}
+
+impl SaveFile {
+  pub const fn kind(&self) -> SaveKind {
+    match self {
+      SaveFile::Fleet(..) => SaveKind::Fleet,
+      SaveFile::Ship(..) => SaveKind::Ship,
+      SaveFile::MissileTemplate(..) => SaveKind::MissileTemplate
+    }
+  }
+
+  /// Reads a save file, detecting its kind from its root element.
+  pub fn read<R: BufRead>(reader: R) -> Result<Self, FileError> {
+    let element = xml::read_nodes(reader)?.try_into_one_element()?;
+    match SaveKind::from_root_name(&element.name) {
+      Some(SaveKind::Fleet) => read_root(Nodes::new_one(element)).map(SaveFile::Fleet),
+      Some(SaveKind::Ship) => read_root(Nodes::new_one(element)).map(SaveFile::Ship),
+      Some(SaveKind::MissileTemplate) => read_root(Nodes::new_one(element)).map(SaveFile::MissileTemplate),
+      None => Err(FileError::UnknownRoot(element.name))
+    }
+  }
+
+  pub fn write<W: Write>(&self, writer: W) -> Result<(), FileError> {
+    match self {
+      SaveFile::Fleet(fleet) => fleet.write(writer),
+      SaveFile::Ship(ship) => ship.write(writer),
+      SaveFile::MissileTemplate(missile_template) => missile_template.write(writer)
+    }
+  }
+
+  /// Reads a save file, detecting its kind from its root element rather than its extension.
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, FileError> {
+    Self::read(BufReader::new(File::open(path)?))
+  }
+
+  pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FileError> {
+    save(path, |writer| self.write(writer))
+  }
+}
+
+macro_rules! impl_save_file {
+  ($Type:ident, $kind:literal) => {
+    impl $Type {
+      #[doc = concat!("Reads a `.", $kind, "` file.")]
+      pub fn read<R: BufRead>(reader: R) -> Result<Self, FileError> {
+        read::<Self, R>(reader)
+      }
+
+      #[doc = concat!("Writes this as a `.", $kind, "` file.")]
+      pub fn write<W: Write>(&self, writer: W) -> Result<(), FileError> {
+        write(self.clone(), writer)
+      }
+
+      #[doc = concat!("Reads a `.", $kind, "` file from the given path.")]
+      pub fn load(path: impl AsRef<Path>) -> Result<Self, FileError> {
+        Self::read(BufReader::new(File::open(path)?))
+      }
+
+      #[doc = concat!("Writes this as a `.", $kind, "` file to the given path.")]
+      pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FileError> {
+        save(path, |writer| self.write(writer))
+      }
+    }
+  };
+}
+
+impl_save_file!(Fleet, "fleet");
+impl_save_file!(Ship, "ship");
+impl_save_file!(MissileTemplate, "missile");
+
+fn read<T, R>(reader: R) -> Result<T, FileError>
+where T: DeserializeElement<Error = FormatError>, R: BufRead {
+  read_root(xml::read_nodes(reader)?)
+}
+
+fn read_root<T>(nodes: Nodes) -> Result<T, FileError>
+where T: DeserializeElement<Error = FormatError> {
+  Ok(<Root<T>>::deserialize_nodes(nodes)?.element)
+}
+
+fn write<T, W>(element: T, writer: W) -> Result<(), FileError>
+where T: SerializeElement<Error = Infallible>, W: Write {
+  let nodes = Root { element }.serialize_nodes().unwrap_or_else(|never| match never {});
+  xml::write_nodes(writer, &nodes, Some(Indent::default()), Some(Version::default()))?;
+  Ok(())
+}
+
+fn save(path: impl AsRef<Path>, f: impl FnOnce(&mut BufWriter<File>) -> Result<(), FileError>) -> Result<(), FileError> {
+  let mut writer = BufWriter::new(File::create(path)?);
+  f(&mut writer)?;
+  writer.flush()?;
+  Ok(())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::data::missiles::bodies::MissileBodyKey;
+  use crate::format::{Color, Extra, Uuid};
+
+  #[test]
+  fn test_extract_ship() {
+    let fleet = "<Fleet><Name>Test</Name><TotalPoints>0</TotalPoints><FactionKey>Stock/Alliance</FactionKey><Ships>\
+      <Ship><Key>00000000-0000-0000-0000-000000000001</Key><Name>Ship</Name><Cost>0</Cost><Number>1</Number><HullType>Stock/Sprinter Corvette</HullType>\
+      <SocketMap><HullSocket><Key>wDsRnL5nKkyYvKgD6VcPHg</Key><ComponentName>Stock/Bulk Magazine</ComponentName>\
+      <ComponentData xsi:type=\"BulkMagazineData\"><Load><MagSaveData><MagazineKey>wDsRnL5nKkyYvKgD6VcPHg</MagazineKey>\
+      <MunitionKey>$MODMIS$/Test</MunitionKey><Quantity>4</Quantity></MagSaveData></Load></ComponentData></HullSocket></SocketMap>\
+      <WeaponGroups /></Ship></Ships></Fleet>";
+    let white = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+    let missile_template = |name: &str| MissileTemplate {
+      associated_template_name: Some(name.to_owned()),
+      designation: "SGM-1".to_owned(),
+      nickname: name.to_owned(),
+      description: String::new(),
+      long_description: String::new(),
+      cost: 0,
+      body_key: MissileBodyKey::SGM1Balestra,
+      template_key: Uuid::nil(),
+      base_color: white,
+      stripe_color: white,
+      sockets: Vec::new(),
+      extra: Extra::new()
+    };
+
+    let mut fleet = Fleet::read(fleet.as_bytes()).unwrap();
+    fleet.missile_types = vec![missile_template("Test"), missile_template("Unused")];
+    let ship = fleet.extract_ship(Uuid::from_u128(1)).unwrap();
+    assert_eq!(ship.missile_types, [missile_template("Test")]);
+
+    let mut buffer = Vec::new();
+    ship.write(&mut buffer).unwrap();
+    let SaveFile::Ship(ship) = SaveFile::read(buffer.as_slice()).unwrap() else { panic!("expected a ship") };
+
+    fleet.ships.clear();
+    fleet.missile_types = vec![missile_template("Unused")];
+    fleet.insert_ship(ship);
+    assert_eq!(fleet.ships.len(), 1);
+    assert_eq!(fleet.missile_types, [missile_template("Unused"), missile_template("Test")]);
+  }
+}
@@ -231,7 +231,7 @@ const fn encode_byte(ch: u8) -> u8 {
 
 const fn decode_byte(ch: u8) -> Option<u8> {
   match ch {
-    b'A'..=b'Z' => Some((ch - b'A') + 0),
+    b'A'..=b'Z' => Some(ch - b'A'),
     b'a'..=b'z' => Some((ch - b'a') + 26),
     b'0'..=b'9' => Some((ch - b'0') + 52),
     b'-' => Some(62),
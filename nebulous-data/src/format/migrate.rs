@@ -0,0 +1,180 @@
+//! Migration of fleets between versions of the fleet file format, keyed on a fleet's `Version` element.
+//!
+//! Migrations operate on the raw [`Element`] of a fleet, so that fleets from older versions of the game
+//! can be brought up to [`CURRENT_FLEET_VERISON`] before they are deserialized, and so that a fleet
+//! can be written out for an older version when nothing would be lost by doing so.
+
+use super::CURRENT_FLEET_VERISON;
+
+use xml::{Element, Node, Nodes};
+
+
+
+/// The oldest fleet version that can be migrated to and from.
+pub const OLDEST_FLEET_VERSION: usize = 1;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum MigrationError {
+  #[error("invalid fleet version {0:?}")]
+  InvalidVersion(Box<str>),
+  #[error("unsupported fleet version {0}, only versions {OLDEST_FLEET_VERSION} through {CURRENT_FLEET_VERISON} are supported")]
+  UnsupportedVersion(usize),
+  #[error("cannot write fleet version {version}: {reason}")]
+  Downgrade { version: usize, reason: &'static str }
+}
+
+struct Migration {
+  /// Rewrites a fleet of the previous version as a fleet of this version.
+  upgrade: fn(&mut Element),
+  /// Rewrites a fleet of this version as a fleet of the previous version,
+  /// failing with a reason if the fleet uses anything the previous version cannot represent.
+  downgrade: fn(&mut Element) -> Result<(), &'static str>
+}
+
+impl Migration {
+  /// A step between two versions that read and write the elements this crate understands the same way.
+  const UNCHANGED: Migration = Migration { upgrade: |_| (), downgrade: |_| Ok(()) };
+}
+
+/// `MIGRATIONS[i]` migrates between versions `OLDEST_FLEET_VERSION + i` and `OLDEST_FLEET_VERSION + i + 1`.
+///
+/// No differences between the versions are documented yet, so these steps only change the version number.
+/// A step that rewrites fleets should be added together with a fleet saved by that version of the game as a test fixture.
+const MIGRATIONS: [Migration; CURRENT_FLEET_VERISON - OLDEST_FLEET_VERSION] = [
+  Migration::UNCHANGED,
+  Migration::UNCHANGED
+];
+
+/// Reads the `Version` element of a fleet.
+/// Fleets without one are assumed to be of the current version.
+pub fn fleet_version(element: &Element) -> Result<usize, MigrationError> {
+  match find_child(element, "Version") {
+    Some(version) => {
+      let text = version.children.clone().try_into_text()
+        .map_err(|_| MigrationError::InvalidVersion("".into()))?;
+      text.trim().parse::<usize>().map_err(|_| MigrationError::InvalidVersion(text.into()))
+    },
+    None => Ok(CURRENT_FLEET_VERISON)
+  }
+}
+
+/// Migrates a fleet element from whatever version it declares to the given version.
+pub fn migrate(mut element: Element, version: usize) -> Result<Element, MigrationError> {
+  let from = fleet_version(&element)?;
+  check_supported(from)?;
+  check_supported(version)?;
+
+  if from < version {
+    for migration in &MIGRATIONS[from - OLDEST_FLEET_VERSION..version - OLDEST_FLEET_VERSION] {
+      (migration.upgrade)(&mut element);
+    };
+  } else {
+    for migration in MIGRATIONS[version - OLDEST_FLEET_VERSION..from - OLDEST_FLEET_VERSION].iter().rev() {
+      (migration.downgrade)(&mut element).map_err(|reason| MigrationError::Downgrade { version, reason })?;
+    };
+  };
+
+  set_version(&mut element, version);
+  Ok(element)
+}
+
+/// Brings a fleet from an older version up to the current version before it is read.
+/// Fleets of the current version or a newer one are returned unchanged, so that fleets saved by a newer version
+/// of the game are still read as well as they can be.
+pub fn upgrade(element: Element) -> Result<Element, MigrationError> {
+  if fleet_version(&element)? < CURRENT_FLEET_VERISON {
+    migrate(element, CURRENT_FLEET_VERISON)
+  } else {
+    Ok(element)
+  }
+}
+
+fn check_supported(version: usize) -> Result<(), MigrationError> {
+  match version {
+    OLDEST_FLEET_VERSION..=CURRENT_FLEET_VERISON => Ok(()),
+    version => Err(MigrationError::UnsupportedVersion(version))
+  }
+}
+
+fn set_version(element: &mut Element, version: usize) {
+  match find_child_mut(element, "Version") {
+    Some(element) => element.children = Nodes::new_text(version.to_string()),
+    None => insert_after(element, &["Name"], Element::new("Version", Nodes::new_text(version.to_string())))
+  };
+}
+
+
+
+fn find_child<'a>(element: &'a Element, name: &str) -> Option<&'a Element> {
+  element.children.iter().find_map(|node| match node {
+    Node::Element(child) if child.name.as_ref() == name => Some(child),
+    _ => None
+  })
+}
+
+fn find_child_mut<'a>(element: &'a mut Element, name: &str) -> Option<&'a mut Element> {
+  element.children.iter_mut().find_map(|node| match node {
+    Node::Element(child) if child.name.as_ref() == name => Some(child),
+    _ => None
+  })
+}
+
+
+
+
+/// Inserts `child` after the last child element named in `after`, or first if there are none.
+fn insert_after(element: &mut Element, after: &[&str], child: Element) {
+  let mut children = Vec::from(Box::<[Node]>::from(std::mem::take(&mut element.children)));
+  let index = children.iter()
+    .rposition(|node| matches!(node, Node::Element(element) if after.contains(&element.name.as_ref())))
+    .map_or(0, |index| index + 1);
+  children.insert(index, Node::Element(child));
+  element.children = Nodes::from(children);
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::format::Fleet;
+  use xml::{DeserializeElement, SerializeElement, read_nodes};
+
+  fn fleet_element(version: &str) -> Element {
+    let fleet = format!("<Fleet><Name>Test</Name><Version>{version}</Version><TotalPoints>0</TotalPoints>\
+      <FactionKey>Stock/Alliance</FactionKey><Ships><Ship><SaveID xsi:nil=\"true\" />\
+      <Key>00000000-0000-0000-0000-000000000000</Key><Name>Ship</Name><Cost>0</Cost><Callsign /><Number>1</Number>\
+      <HullType>Stock/Sprinter Corvette</HullType><SocketMap /><WeaponGroups /></Ship></Ships></Fleet>");
+    read_nodes(fleet.as_bytes()).unwrap().try_into_one_element().unwrap()
+  }
+
+  #[test]
+  fn test_migrate_roundtrip() {
+    let element = fleet_element("1");
+    assert_eq!(fleet_version(&upgrade(element.clone()).unwrap()), Ok(CURRENT_FLEET_VERISON));
+    let fleet = Fleet::deserialize_element(element).unwrap();
+    assert_eq!(fleet.ships.len(), 1);
+
+    let current = fleet.clone().serialize_element().unwrap();
+    assert_eq!(fleet_version(&current), Ok(CURRENT_FLEET_VERISON));
+    let old = fleet.serialize_element_version(1).unwrap();
+    assert_eq!(fleet_version(&old), Ok(1));
+    let read = Fleet::deserialize_element(old.clone()).unwrap();
+    assert_eq!(read.serialize_element_version(1), Ok(old));
+  }
+
+  #[test]
+  fn test_migrate_newer() {
+    // Fleets from newer versions of the game are read and written back as they are
+    let newer = (CURRENT_FLEET_VERISON + 1).to_string();
+    let element = fleet_element(&newer);
+    assert_eq!(upgrade(element.clone()), Ok(element.clone()));
+    let fleet = Fleet::deserialize_element(element).unwrap();
+    assert_eq!(fleet_version(&fleet.clone().serialize_element().unwrap()), Ok(CURRENT_FLEET_VERISON + 1));
+
+    assert_eq!(migrate(fleet_element("1"), CURRENT_FLEET_VERISON + 1), Err(MigrationError::UnsupportedVersion(CURRENT_FLEET_VERISON + 1)));
+    assert_eq!(migrate(fleet_element("0"), CURRENT_FLEET_VERISON), Err(MigrationError::UnsupportedVersion(0)));
+    assert_eq!(upgrade(fleet_element("x")), Err(MigrationError::InvalidVersion("x".into())));
+  }
+}
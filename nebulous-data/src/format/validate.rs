@@ -0,0 +1,240 @@
+//! Structured validation of fleets and ships, reporting problems the game would reject or silently fix.
+
+use crate::data::Faction;
+use crate::data::hulls::HullKey;
+use crate::loadout::{ShipLoadout, ShipLoadoutError};
+use super::key::Key;
+use super::{Fleet, Ship, Uuid};
+
+use std::collections::HashSet;
+use std::fmt;
+
+
+
+/// A single problem found while validating a fleet or ship.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+  /// The key and name of the ship this problem was found on, if any.
+  pub ship: Option<(Uuid, String)>,
+  /// The hull socket this problem was found on, if any.
+  pub socket: Option<Key>,
+  pub kind: DiagnosticKind
+}
+
+impl Diagnostic {
+  fn fleet(kind: DiagnosticKind) -> Self {
+    Diagnostic { ship: None, socket: None, kind }
+  }
+
+  fn ship(ship: &Ship, kind: DiagnosticKind) -> Self {
+    Diagnostic { ship: Some((ship.key, ship.name.clone())), socket: None, kind }
+  }
+
+  fn socket(ship: &Ship, socket: Key, kind: DiagnosticKind) -> Self {
+    Diagnostic { ship: Some((ship.key, ship.name.clone())), socket: Some(socket), kind }
+  }
+
+  fn loadout(ship: &Ship, error: ShipLoadoutError) -> Self {
+    Diagnostic { ship: Some((ship.key, ship.name.clone())), socket: error.socket(), kind: DiagnosticKind::Loadout(error) }
+  }
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if let Some((_, name)) = &self.ship {
+      write!(f, "ship {name:?}: ")?;
+    };
+
+    // Loadout errors already name the socket they were found on
+    if let (Some(socket), false) = (self.socket, matches!(self.kind, DiagnosticKind::Loadout(..))) {
+      write!(f, "socket {socket}: ")?;
+    };
+
+    fmt::Display::fmt(&self.kind, f)
+  }
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum DiagnosticKind {
+  #[error("hull {hull} cannot be used by faction {faction}")]
+  HullWrongFaction { hull: HullKey, faction: Faction },
+  #[error("hull {hull} has no socket with this key")]
+  UnknownSocket { hull: HullKey },
+  /// A problem with an installed component, magazine or launcher, as found by [`ShipLoadout::validate`].
+  #[error(transparent)]
+  Loadout(ShipLoadoutError),
+  #[error("fleet costs {total_points} points, which is over the budget of {point_budget}")]
+  OverBudget { total_points: usize, point_budget: usize },
+  #[error("ship key {0} is used by more than one ship")]
+  DuplicateShipKey(Uuid),
+  #[error("initial formation refers to guide ship {0}, which is not in the fleet")]
+  DanglingGuideKey(Uuid),
+  #[error("weapon group {group:?} refers to socket {member}, which has no component")]
+  MissingWeaponGroupMember { group: String, member: Key }
+}
+
+impl Fleet {
+  /// Checks this fleet and all of its ships for problems, returning every one that was found.
+  ///
+  /// If `point_budget` is given, the fleet's calculated cost is checked against it.
+  pub fn validate(&self, point_budget: Option<usize>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    if let Some(point_budget) = point_budget {
+      let total_points = self.calculate_costs(&self.missile_types).total();
+      if total_points > point_budget {
+        diagnostics.push(Diagnostic::fleet(DiagnosticKind::OverBudget { total_points, point_budget }));
+      };
+    };
+
+    let ship_keys = self.ships.iter().map(|ship| ship.key).collect::<HashSet<Uuid>>();
+    let mut seen_ship_keys = HashSet::with_capacity(self.ships.len());
+    for ship in self.ships.iter() {
+      if !seen_ship_keys.insert(ship.key) {
+        diagnostics.push(Diagnostic::ship(ship, DiagnosticKind::DuplicateShipKey(ship.key)));
+      };
+
+      let hull = ship.hull_type.hull();
+      if hull.faction != self.faction_key {
+        let kind = DiagnosticKind::HullWrongFaction { hull: ship.hull_type, faction: self.faction_key };
+        diagnostics.push(Diagnostic::ship(ship, kind));
+      };
+
+      if let Some(initial_formation) = &ship.initial_formation {
+        if !ship_keys.contains(&initial_formation.guide_key) {
+          let kind = DiagnosticKind::DanglingGuideKey(initial_formation.guide_key);
+          diagnostics.push(Diagnostic::ship(ship, kind));
+        };
+      };
+
+      diagnostics.extend(ship.validate());
+    };
+
+    diagnostics
+  }
+}
+
+impl Ship {
+  /// Checks this ship's sockets, magazines and weapon groups for problems, returning every one that was found.
+  ///
+  /// Installed components, magazines and launchers are checked by [`ShipLoadout::validate`].
+  pub fn validate(&self) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let hull = self.hull_type.hull();
+    for hull_socket in self.socket_map.iter() {
+      if hull.get_socket(hull_socket.key).is_none() {
+        let kind = DiagnosticKind::UnknownSocket { hull: self.hull_type };
+        diagnostics.push(Diagnostic::socket(self, hull_socket.key, kind));
+      };
+    };
+
+    let errors = ShipLoadout::from_ship(self).map_or_else(|error| vec![error], |loadout| loadout.validate());
+    diagnostics.extend(errors.into_iter().map(|error| Diagnostic::loadout(self, error)));
+
+    for weapon_group in self.weapon_groups.iter() {
+      for &member in weapon_group.members.iter() {
+        if !self.socket_map.iter().any(|hull_socket| hull_socket.key == member) {
+          let kind = DiagnosticKind::MissingWeaponGroupMember { group: weapon_group.name.clone(), member };
+          diagnostics.push(Diagnostic::ship(self, kind));
+        };
+      };
+    };
+
+    diagnostics
+  }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::data::components::{ComponentKey, ComponentKind};
+  use crate::format::{HullSocket, InitialFormation, Vector3, WeaponGroup};
+  use xml::Extra;
+
+  fn ship(key: Uuid, socket_map: Vec<HullSocket>) -> Ship {
+    Ship {
+      key,
+      name: format!("Ship {}", key.as_u128()),
+      cost: 0,
+      callsign: None,
+      number: 0,
+      hull_type: HullKey::SprinterCorvette,
+      hull_config: None,
+      socket_map,
+      weapon_groups: Vec::new(),
+      initial_formation: None,
+      missile_types: Vec::new(),
+      extra: Extra::new()
+    }
+  }
+
+  #[test]
+  fn test_validate() {
+    let mount = key!("wDsRnL5nKkyYvKgD6VcPHg");
+    let unknown = key!("AAAAAAAAAAAAAAAAAAAAAA");
+    let mut first = ship(Uuid::from_u128(1), vec![
+      HullSocket { key: mount, component_name: ComponentKey::BulkMagazine, component_data: None, extra: Extra::new() },
+      HullSocket { key: unknown, component_name: ComponentKey::BulkMagazine, component_data: None, extra: Extra::new() }
+    ]);
+    first.weapon_groups.push(WeaponGroup { name: "Guns".to_owned(), members: vec![key!("Z48ot_dQfkWb6AVYjaM_gA")], extra: Extra::new() });
+    let mut second = ship(Uuid::from_u128(1), Vec::new());
+    second.initial_formation = Some(InitialFormation {
+      guide_key: Uuid::from_u128(2),
+      relative_position: Vector3 { x: 0.0, y: 0.0, z: 0.0 }
+    });
+
+    let fleet = Fleet {
+      name: "Test".to_owned(),
+      total_points: 0,
+      faction_key: Faction::Alliance,
+      description: None,
+      ships: vec![first, second],
+      missile_types: Vec::new(),
+      extra: Extra::new()
+    };
+
+    let diagnostics = fleet.validate(Some(100));
+    assert_eq!(diagnostics[2].to_string(), format!(
+      "ship \"Ship 1\": socket {mount}: component Stock/Bulk Magazine is a Compartment, but the socket expects a Mount"
+    ));
+
+    let kinds = diagnostics.into_iter()
+      .map(|diagnostic| (diagnostic.socket, diagnostic.kind))
+      .collect::<Vec<_>>();
+    assert_eq!(kinds, [
+      (None, DiagnosticKind::OverBudget { total_points: 150, point_budget: 100 }),
+      (Some(unknown), DiagnosticKind::UnknownSocket { hull: HullKey::SprinterCorvette }),
+      (Some(mount), DiagnosticKind::Loadout(ShipLoadoutError::ComponentWrongKind {
+        socket: mount,
+        component: ComponentKey::BulkMagazine,
+        expected: ComponentKind::Mount,
+        found: ComponentKind::Compartment
+      })),
+      (None, DiagnosticKind::MissingWeaponGroupMember { group: "Guns".to_owned(), member: key!("Z48ot_dQfkWb6AVYjaM_gA") }),
+      (None, DiagnosticKind::DuplicateShipKey(Uuid::from_u128(1))),
+      (None, DiagnosticKind::DanglingGuideKey(Uuid::from_u128(2)))
+    ]);
+  }
+
+  #[test]
+  fn test_validate_hull_faction() {
+    // The Ocello may be fitted with components of either faction, but is still a Protectorate hull
+    let mut ocello = ship(Uuid::from_u128(1), Vec::new());
+    ocello.hull_type = HullKey::OcelloCommandCruiser;
+    let fleet = Fleet {
+      name: "Test".to_owned(),
+      total_points: 0,
+      faction_key: Faction::Alliance,
+      description: None,
+      ships: vec![ocello],
+      missile_types: Vec::new(),
+      extra: Extra::new()
+    };
+
+    let kinds = fleet.validate(None).into_iter().map(|diagnostic| diagnostic.kind).collect::<Vec<_>>();
+    assert_eq!(kinds, [DiagnosticKind::HullWrongFaction { hull: HullKey::OcelloCommandCruiser, faction: Faction::Alliance }]);
+  }
+}
@@ -14,6 +14,10 @@ pub extern crate nebulous_xml as xml;
 extern crate rand;
 #[cfg(feature = "serde")]
 extern crate serde;
+#[cfg(feature = "convert")]
+extern crate serde_json;
+#[cfg(feature = "convert")]
+extern crate serde_yaml;
 #[macro_use]
 extern crate thiserror;
 pub extern crate uuid;
@@ -28,6 +32,7 @@ pub mod utils;
 pub mod data;
 pub mod format;
 pub mod loadout;
+pub mod stats;
 
 pub use crate::format::key::Key;
 pub use crate::utils::Size;
@@ -1,18 +1,21 @@
-use crate::data::components::{ComponentKey, ComponentVariant};
+use crate::data::components::{ComponentKey, ComponentKind, ComponentVariant};
 use crate::data::hulls::{HullKey, HullSocket as HullSocketDefinition};
 use crate::data::hulls::config::Variant;
 use crate::data::missiles::engines::EngineSettings;
 use crate::data::missiles::{AuxiliaryKey, AvionicsKey, Maneuvers, WarheadKey};
 use crate::data::missiles::seekers::{SeekerKey, SeekerMode, SeekerStrategy, SeekerStrategyFull};
 use crate::data::missiles::bodies::MissileBodyKey;
+use crate::data::munitions::{MunitionFamily, MunitionKey};
 use crate::format::*;
 use crate::format::key::Key;
+use crate::utils::Size;
 
 use indexmap::IndexMap;
 #[cfg(feature = "rand")]
 use rand::Rng;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use xml::Extra;
 
 use std::collections::HashMap;
 use std::num::NonZeroUsize as zsize;
@@ -32,8 +35,38 @@ pub struct ShipAdditional {
   pub missile_types: Vec<MissileTemplate>
 }
 
-#[derive(Debug, Error, Clone, Copy)]
-pub enum ShipLoadoutError {}
+#[derive(Debug, Error, Clone, Copy, PartialEq)]
+pub enum ShipLoadoutError {
+  #[error("hull {hull} has {expected} sockets, but the loadout has {found}")]
+  SocketCountMismatch { hull: HullKey, expected: usize, found: usize },
+  #[error("socket {socket}: component {component} does not fit in a socket of size {}x{}x{}", .socket_size.x, .socket_size.y, .socket_size.z)]
+  ComponentDoesNotFit { socket: Key, component: ComponentKey, socket_size: Size },
+  #[error("socket {socket}: component {component} is a {found:?}, but the socket expects a {expected:?}")]
+  ComponentWrongKind { socket: Key, component: ComponentKey, expected: ComponentKind, found: ComponentKind },
+  #[error("socket {socket}: component {component} cannot be used on hull {hull}")]
+  ComponentWrongFaction { socket: Key, component: ComponentKey, hull: HullKey },
+  #[error("socket {socket}: magazine holds {volume} units of volume, but only has room for {capacity}")]
+  MagazineOverCapacity { socket: Key, volume: f32, capacity: usize },
+  #[error("socket {socket}: launcher holds {count} missiles, but only has {capacity} cells")]
+  LauncherOverCapacity { socket: Key, count: usize, capacity: usize },
+  #[error("socket {socket}: munition {munition} cannot be fired by any weapon on this ship")]
+  IncompatibleMunition { socket: Key, munition: MunitionKey }
+}
+
+impl ShipLoadoutError {
+  /// The key of the hull socket this error was found on, if any.
+  pub const fn socket(&self) -> Option<Key> {
+    match *self {
+      Self::SocketCountMismatch { .. } => None,
+      Self::ComponentDoesNotFit { socket, .. } => Some(socket),
+      Self::ComponentWrongKind { socket, .. } => Some(socket),
+      Self::ComponentWrongFaction { socket, .. } => Some(socket),
+      Self::MagazineOverCapacity { socket, .. } => Some(socket),
+      Self::LauncherOverCapacity { socket, .. } => Some(socket),
+      Self::IncompatibleMunition { socket, .. } => Some(socket)
+    }
+  }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
@@ -64,6 +97,92 @@ impl ShipLoadout {
     })
   }
 
+  /// Checks every installed component, magazine and launcher for problems, returning every one that was found.
+  ///
+  /// Components are checked with [`Component::is_legal_in`][crate::data::components::Component::is_legal_in],
+  /// and any that are not legal where they are installed are reported with each reason why.
+  /// Magazine volume and munition compatibility are only checked for stock munitions,
+  /// since custom missiles are not known to the loadout.
+  pub fn validate(&self) -> Vec<ShipLoadoutError> {
+    let hull = self.hull_type.hull();
+    if hull.sockets.len() != self.sockets.len() {
+      return vec![ShipLoadoutError::SocketCountMismatch {
+        hull: self.hull_type,
+        expected: hull.sockets.len(),
+        found: self.sockets.len()
+      }];
+    };
+
+    let munition_families = self.sockets.iter().flatten()
+      .filter_map(|socket| socket.component_key.component().munition_family())
+      .collect::<Vec<MunitionFamily>>();
+
+    let mut errors = Vec::new();
+    for (hull_socket, socket) in hull.sockets.iter().zip(self.sockets.iter()) {
+      let socket = match socket { Some(socket) => socket, None => continue };
+      let component_key = socket.component_key;
+      let component = component_key.component();
+      let socket_key = hull_socket.save_key;
+
+      if !component.is_legal_in(self.hull_type, hull_socket) {
+        if !component.is_usable_on(self.hull_type) {
+          errors.push(ShipLoadoutError::ComponentWrongFaction { socket: socket_key, component: component_key, hull: self.hull_type });
+        };
+
+        if component.kind != hull_socket.kind {
+          errors.push(ShipLoadoutError::ComponentWrongKind {
+            socket: socket_key,
+            component: component_key,
+            expected: hull_socket.kind,
+            found: component.kind
+          });
+        };
+
+        if !component.can_fit_in(hull_socket.size) {
+          errors.push(ShipLoadoutError::ComponentDoesNotFit { socket: socket_key, component: component_key, socket_size: hull_socket.size });
+          continue;
+        };
+      };
+
+      let magazine_contents = match socket.variant.as_ref().and_then(ShipLoadoutSocketVariant::get_magazine_contents) {
+        Some(magazine_contents) => magazine_contents,
+        None => continue
+      };
+      let munitions = magazine_contents.iter().filter_map(|(munition_key, &quantity)| match munition_key {
+        MunitionOrMissileKey::MunitionKey(munition_key) => Some((*munition_key, quantity)),
+        MunitionOrMissileKey::MissileKey(..) => None
+      });
+
+      let mut volume = 0.0;
+      for (munition_key, quantity) in munitions {
+        let munition = munition_key.munition();
+        volume += munition.storage_volume * quantity as f32;
+        if !munition_families.contains(&munition.family) {
+          errors.push(ShipLoadoutError::IncompatibleMunition { socket: socket_key, munition: munition_key });
+        };
+      };
+
+      match component.variant {
+        Some(ComponentVariant::Magazine { available_volume }) => {
+          let capacity = available_volume * component.tiling_quantity(hull_socket.size);
+          if volume > capacity as f32 {
+            errors.push(ShipLoadoutError::MagazineOverCapacity { socket: socket_key, volume, capacity });
+          };
+        },
+        Some(ComponentVariant::WeaponMissileBank { cells, .. }) => {
+          let capacity = cells.get_count(hull_socket.size, component.size).unwrap_or(0);
+          let count = magazine_contents.values().sum::<usize>();
+          if count > capacity {
+            errors.push(ShipLoadoutError::LauncherOverCapacity { socket: socket_key, count, capacity });
+          };
+        },
+        _ => ()
+      };
+    };
+
+    errors
+  }
+
   #[cfg(feature = "rand")]
   pub fn to_ship<R: Rng + ?Sized>(&self, additional: ShipAdditional, rng: &mut R) -> Ship {
     let hull = self.hull_type.hull();
@@ -91,6 +210,7 @@ impl ShipLoadout {
       weapon_groups: additional.weapon_groups,
       initial_formation: additional.initial_formation,
       missile_types: additional.missile_types,
+      extra: Extra::new()
     }
   }
 }
@@ -141,7 +261,8 @@ impl ShipLoadoutSocket {
     HullSocket {
       key: hull_socket_definition.save_key,
       component_name: self.component_key,
-      component_data
+      component_data,
+      extra: Extra::new()
     }
   }
 }
@@ -208,7 +329,8 @@ fn get_load<R: Rng + ?Sized>(magazine_contents: &IndexMap<MunitionOrMissileKey,
       MagazineSaveData {
         magazine_key: rng.gen(),
         munition_key: munition_key.clone(),
-        quantity
+        quantity,
+        extra: Extra::new()
       }
     })
     .collect()
@@ -264,7 +386,8 @@ impl MissileLoadout {
       template_key: additional.template_key,
       base_color: additional.base_color,
       stripe_color: additional.stripe_color,
-      sockets
+      sockets,
+      extra: Extra::new()
     }
   }
 
@@ -687,3 +810,51 @@ impl FromIterator<MissileSocket> for MissileTemplateSummary {
     }))
   }
 }
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_ship_loadout_validate() {
+    let hull_type = HullKey::SprinterCorvette;
+    let hull = hull_type.hull();
+    let magazine_contents = IndexMap::from([(MunitionOrMissileKey::MunitionKey(MunitionKey::A100mmAP), 3000)]);
+    let mut sockets = vec![None; hull.sockets.len()].into_boxed_slice();
+    sockets[0] = Some(ShipLoadoutSocket { component_key: ComponentKey::Berthing, variant: None });
+    // 3x1x3 compartment, with room for 135 units of volume
+    sockets[6] = Some(ShipLoadoutSocket {
+      component_key: ComponentKey::BulkMagazine,
+      variant: Some(ShipLoadoutSocketVariant::MagazineComponent { magazine_contents })
+    });
+    sockets[9] = Some(ShipLoadoutSocket { component_key: ComponentKey::FM200Drive, variant: None });
+    // 23 cells, counting custom missiles as well as stock ones
+    let launcher = (1..hull.sockets.len())
+      .find(|&i| ComponentKey::VLS123Launcher.component().is_legal_in(hull_type, &hull.sockets[i]))
+      .unwrap();
+    let magazine_contents = IndexMap::from([(MunitionOrMissileKey::MissileKey("Custom".into()), 24)]);
+    sockets[launcher] = Some(ShipLoadoutSocket {
+      component_key: ComponentKey::VLS123Launcher,
+      variant: Some(ShipLoadoutSocketVariant::MagazineComponent { magazine_contents })
+    });
+
+    let loadout = ShipLoadout { hull_type, hull_config: None, sockets };
+    assert_eq!(loadout.validate(), [
+      ShipLoadoutError::ComponentWrongKind {
+        socket: hull.sockets[0].save_key,
+        component: ComponentKey::Berthing,
+        expected: ComponentKind::Mount,
+        found: ComponentKind::Compartment
+      },
+      ShipLoadoutError::LauncherOverCapacity { socket: hull.sockets[launcher].save_key, count: 24, capacity: 23 },
+      ShipLoadoutError::IncompatibleMunition { socket: hull.sockets[6].save_key, munition: MunitionKey::A100mmAP },
+      ShipLoadoutError::MagazineOverCapacity { socket: hull.sockets[6].save_key, volume: 150.0, capacity: 135 }
+    ]);
+
+    let legal_components = hull_type.legal_components(hull.sockets[9].save_key).unwrap().collect::<Vec<_>>();
+    assert!(legal_components.contains(&ComponentKey::FM200Drive));
+    assert!(!legal_components.contains(&ComponentKey::BulkMagazine));
+  }
+}
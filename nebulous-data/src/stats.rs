@@ -0,0 +1,164 @@
+//! Aggregated performance figures for a single ship design.
+
+use crate::data::Buffs;
+use crate::data::components::{Component, ComponentKey};
+use crate::data::hulls::{HullKey, HullSocket as HullSocketDefinition};
+use crate::format::Ship;
+use crate::loadout::ShipLoadout;
+use crate::utils::Size;
+
+
+
+/// Combined stats of a hull and every component installed on it.
+///
+/// Component power and crew follow the convention of [`Component`]:
+/// positive values are produced, and negative values are consumed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShipStats {
+  /// The mass of the hull plus all installed components, counting every tile of tiling components.
+  pub mass: f32,
+  /// The hull's top speed after [`Buffs::top_speed`] is applied.
+  pub top_speed: f32,
+  /// The hull's turn rate after [`Buffs::turn_rate`] is applied.
+  pub turn_rate: f32,
+  /// The hull's linear motor after [`Buffs::linear_thrust`] is applied.
+  pub linear_motor: f32,
+  /// The hull's angular motor after [`Buffs::angular_thrust`] is applied.
+  pub angular_motor: f32,
+  /// Power produced by reactors and drives, after [`Buffs::powerplant_efficiency`] is applied.
+  pub power_produced: usize,
+  /// Power consumed by all other components.
+  pub power_consumed: usize,
+  /// Crew provided by the hull's base complement and any berthings.
+  pub crew_available: usize,
+  /// Crew required to operate every installed component.
+  pub crew_required: usize,
+  /// Buffs from the hull and every installed component, stacked.
+  pub buffs: Buffs
+}
+
+impl ShipStats {
+  pub fn from_ship(ship: &Ship) -> Self {
+    let hull = ship.hull_type.hull();
+    let components = ship.socket_map.iter().filter_map(|hull_socket| {
+      hull.get_socket(hull_socket.key).map(|hull_socket_definition| (hull_socket_definition, hull_socket.component_name))
+    });
+
+    Self::calculate(ship.hull_type, components)
+  }
+
+  pub fn from_loadout(loadout: &ShipLoadout) -> Self {
+    let hull = loadout.hull_type.hull();
+    let components = hull.sockets.iter().zip(loadout.sockets.iter())
+      .filter_map(|(hull_socket_definition, socket)| {
+        socket.as_ref().map(|socket| (hull_socket_definition, socket.component_key))
+      });
+
+    Self::calculate(loadout.hull_type, components)
+  }
+
+  /// Calculates stats for a hull, given every socket that has a component installed in it.
+  /// Components that do not fit inside of their socket are ignored.
+  pub fn calculate<'a, I>(hull_key: HullKey, components: I) -> Self
+  where I: IntoIterator<Item = (&'a HullSocketDefinition, ComponentKey)> {
+    let hull = hull_key.hull();
+    let components = components.into_iter()
+      .map(|(hull_socket_definition, component_key)| (hull_socket_definition.size, component_key.component()))
+      .filter(|&(socket_size, component)| component.can_fit_in(socket_size))
+      .collect::<Vec<_>>();
+
+    let buffs = hull.buffs.iter().copied()
+      .chain(components.iter().flat_map(|&(_, component)| component.buffs.iter().copied()))
+      .collect::<Buffs>();
+
+    let mut mass = hull.mass;
+    let mut power_produced = 0;
+    let mut power_consumed = 0;
+    let mut crew_available = hull.base_crew_complement;
+    let mut crew_required = 0;
+    for &(socket_size, component) in components.iter() {
+      mass += component_mass(component, socket_size);
+
+      if component.power >= 0 {
+        power_produced += component.power.unsigned_abs();
+      } else {
+        power_consumed += component.power.unsigned_abs();
+      };
+
+      let crew = component.crew(socket_size);
+      if crew >= 0 {
+        crew_available += crew.unsigned_abs();
+      } else {
+        crew_required += crew.unsigned_abs();
+      };
+    };
+
+    let power_produced = (power_produced as f32 * (1.0 + buffs.powerplant_efficiency)).round() as usize;
+
+    ShipStats {
+      mass,
+      top_speed: hull.max_speed * (1.0 + buffs.top_speed),
+      turn_rate: hull.max_turn_speed * (1.0 + buffs.turn_rate),
+      linear_motor: hull.linear_motor * (1.0 + buffs.linear_thrust),
+      angular_motor: hull.angular_motor * (1.0 + buffs.angular_thrust),
+      power_produced,
+      power_consumed,
+      crew_available,
+      crew_required,
+      buffs
+    }
+  }
+
+  /// Power left over after all components are powered, or negative if there is a deficit.
+  pub const fn net_power(&self) -> isize {
+    self.power_produced as isize - self.power_consumed as isize
+  }
+
+  /// Crew left over after all components are crewed, or negative if there is a shortfall.
+  pub const fn net_crew(&self) -> isize {
+    self.crew_available as isize - self.crew_required as isize
+  }
+
+  pub const fn is_underpowered(&self) -> bool {
+    self.power_consumed > self.power_produced
+  }
+
+  pub const fn is_undercrewed(&self) -> bool {
+    self.crew_required > self.crew_available
+  }
+}
+
+fn component_mass(component: &Component, socket_size: Size) -> f32 {
+  if component.can_tile() {
+    component.mass * component.tiling_quantity(socket_size) as f32
+  } else {
+    component.mass
+  }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::loadout::ShipLoadoutSocket;
+
+  #[test]
+  fn test_ship_stats() {
+    let hull_type = HullKey::SprinterCorvette;
+    let hull = hull_type.hull();
+    let mut sockets = vec![None; hull.sockets.len()].into_boxed_slice();
+    // the first compartment socket is 4x1x8, fitting 32 berthings
+    sockets[4] = Some(ShipLoadoutSocket { component_key: ComponentKey::Berthing, variant: None });
+    sockets[9] = Some(ShipLoadoutSocket { component_key: ComponentKey::FM200Drive, variant: None });
+    let stats = ShipStats::from_loadout(&ShipLoadout { hull_type, hull_config: None, sockets });
+
+    assert_eq!(stats.mass, hull.mass + 32.0 * 0.5 + 35.0);
+    assert_eq!(stats.top_speed, hull.max_speed);
+    assert_eq!(stats.power_produced, 500);
+    assert_eq!(stats.power_consumed, 0);
+    assert_eq!(stats.crew_available, hull.base_crew_complement + 96);
+    assert_eq!(stats.crew_required, 10);
+    assert!(!stats.is_underpowered() && !stats.is_undercrewed());
+  }
+}
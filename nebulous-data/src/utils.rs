@@ -93,6 +93,13 @@ impl<T: Contiguous> ContiguousValues<T> {
   }
 }
 
+impl<T: Contiguous> Default for ContiguousValues<T> {
+  #[inline]
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl<T> Iterator for ContiguousValues<T>
 where T: Contiguous, RangeInclusive<T::Int>: Iterator<Item = T::Int> {
   type Item = T;
@@ -171,7 +178,7 @@ macro_rules! zsize {
 macro_rules! size_op {
   ($vis:vis fn $name:ident [$t:tt]) => (
     #[inline] $vis const fn $name(self, rhs: Self) -> Self {
-      Size { x: self.x $t rhs.x, y: self.x $t rhs.y, z: self.x $t rhs.z }
+      Size { x: self.x $t rhs.x, y: self.y $t rhs.y, z: self.z $t rhs.z }
     }
   );
 }
@@ -357,13 +364,32 @@ macro_rules! union_cast {
 }
 
 #[inline]
-pub(crate) const unsafe fn cast_ref<'a, T, U>(r: &'a T) -> &'a U {
+pub(crate) const unsafe fn cast_ref<T, U>(r: &T) -> &U {
   debug_assert!(std::mem::size_of::<T>() == std::mem::size_of::<U>());
   &*(r as *const T as *const U)
 }
 
 #[inline]
-pub(crate) unsafe fn cast_ref_mut<'a, T, U>(r: &'a mut T) -> &'a mut U {
+pub(crate) unsafe fn cast_ref_mut<T, U>(r: &mut T) -> &mut U {
   debug_assert!(std::mem::size_of::<T>() == std::mem::size_of::<U>());
   &mut *(r as *mut T as *mut U)
 }
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_size_ops() {
+    let a = Size::new(12, 8, 6);
+    let b = Size::new(3, 2, 4);
+    assert_eq!(a.add(b), Size::new(15, 10, 10));
+    assert_eq!(a.sub(b), Size::new(9, 6, 2));
+    assert_eq!(a.mul(b), Size::new(36, 16, 24));
+    assert_eq!(a.div(b), Size::new(4, 4, 1));
+    assert_eq!(a.rem(b), Size::new(0, 0, 2));
+    assert_eq!(a.volume(), 576);
+  }
+}
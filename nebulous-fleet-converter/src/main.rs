@@ -0,0 +1,78 @@
+use nebulous_data::format::SaveKind;
+use nebulous_data::format::convert::{ConvertError, TextFormat};
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "\
+usage:
+  nebulous-fleet-converter <input.fleet|ship|missile> [output.json|yaml]
+  nebulous-fleet-converter <input.fleet|ship|missile.json|yaml> [output.fleet|ship|missile]";
+
+fn main() {
+  let mut args = std::env::args_os().skip(1);
+  let in_path = PathBuf::from(args.next().expect(USAGE));
+  let out_path = args.next().map(PathBuf::from);
+  let in_extension = in_path.extension().expect(USAGE);
+
+  if let Some(kind) = SaveKind::from_extension(in_extension) {
+    // `example.fleet` is converted to `example.fleet.json` by default
+    let out_path = out_path.unwrap_or_else(|| with_appended_extension(&in_path, TextFormat::Json.extension()));
+    let format = out_path.extension().and_then(TextFormat::from_extension).expect("invalid output extension");
+
+    println!("reading {} from {}", kind.extension(), in_path.display());
+    let reader = BufReader::new(File::open(&in_path).expect("failed to open file"));
+    let text = kind.xml_to_text(reader, format).expect("failed to convert file");
+
+    println!("writing {format} to {}", out_path.display());
+    std::fs::write(&out_path, text).expect("failed to write file");
+    println!("successfully wrote {format} to {}", out_path.display());
+  } else if let Some(format) = TextFormat::from_extension(in_extension) {
+    // `example.fleet.json` is converted to `example.fleet` by default
+    let out_path = out_path.unwrap_or_else(|| in_path.with_extension(""));
+    let kind = out_path.extension().and_then(SaveKind::from_extension).expect("invalid output extension");
+
+    println!("reading {format} from {}", in_path.display());
+    let text = std::fs::read_to_string(&in_path).expect("failed to read file");
+
+    println!("writing {} to {}", kind.extension(), out_path.display());
+    write_save_file(kind, format, &text, &out_path).expect("failed to convert file");
+    println!("successfully wrote {} to {}", kind.extension(), out_path.display());
+  } else {
+    panic!("{USAGE}");
+  };
+}
+
+/// Converts the text to a save file before creating the output, so that a bad input never truncates an existing save.
+fn write_save_file(kind: SaveKind, format: TextFormat, text: &str, out_path: &Path) -> Result<(), ConvertError> {
+  let mut buffer = Vec::new();
+  kind.text_to_xml(text, format, &mut buffer)?;
+  std::fs::write(out_path, buffer)?;
+  Ok(())
+}
+
+fn with_appended_extension(path: &Path, extension: &str) -> PathBuf {
+  let mut path = OsString::from(path);
+  path.push(".");
+  path.push(extension);
+  PathBuf::from(path)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bad_input_keeps_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let out_path = dir.path().join("example.fleet");
+    std::fs::write(&out_path, "<Fleet />").unwrap();
+
+    assert!(write_save_file(SaveKind::Fleet, TextFormat::Json, "{ \"name\": ", &out_path).is_err());
+    assert_eq!(std::fs::read_to_string(&out_path).unwrap(), "<Fleet />");
+  }
+}
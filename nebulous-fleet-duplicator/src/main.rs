@@ -1,62 +1,137 @@
-use nebulous_data::format::{Fleet, Root};
-use nebulous_data::xml::{DeserializeNodes, SerializeNodes, Indent, Version, read_nodes, write_nodes};
-use rand::SeedableRng;
+use nebulous_data::format::{Fleet, Ship};
+use rand::{Rng, SeedableRng};
 use rand::rngs::OsRng;
 use rand_xoshiro::Xoroshiro128StarStar;
 
-use std::fs::File;
 use std::ffi::OsString;
-use std::io::{BufReader, BufWriter};
 use std::path::PathBuf;
+use std::process::ExitCode;
 
 type Random = Xoroshiro128StarStar;
 
-fn main() {
+const USAGE: &str = "\
+usage:
+  nebulous-fleet-duplicator <input> [output] [--multiplier <n>] [--point-cap <points> [--trim]]";
+
+fn main() -> ExitCode {
   let mut args = std::env::args_os().skip(1);
-  let in_path = PathBuf::from(args.next().expect("no input path provided"));
-  let out_path = args.next().map(PathBuf::from).unwrap_or_else(|| {
+  let mut in_path = None;
+  let mut out_path = None;
+  let mut multiplier = 2;
+  let mut point_cap = None;
+  let mut trim = false;
+  while let Some(arg) = args.next() {
+    match arg.to_str() {
+      Some("--multiplier") => {
+        multiplier = args.next().expect(USAGE)
+          .to_str().and_then(|multiplier| multiplier.parse::<usize>().ok())
+          .filter(|&multiplier| multiplier >= 1)
+          .expect("invalid multiplier");
+      },
+      Some("--point-cap") => {
+        point_cap = Some(args.next().expect(USAGE)
+          .to_str().and_then(|point_cap| point_cap.parse::<usize>().ok())
+          .expect("invalid point cap"));
+      },
+      Some("--trim") => trim = true,
+      _ if in_path.is_none() => in_path = Some(PathBuf::from(arg)),
+      _ if out_path.is_none() => out_path = Some(PathBuf::from(arg)),
+      _ => panic!("{USAGE}")
+    };
+  };
+
+  let in_path = in_path.expect(USAGE);
+  println!("reading fleet from {}", in_path.display());
+  let mut fleet = Fleet::load(&in_path).expect("failed to read fleet");
+  println!("successfully read fleet from {}", in_path.display());
+
+  let original_name = fleet.name.clone();
+  let original_ship_count = fleet.ships.len();
+
+  let mut rng = Random::from_rng(OsRng).expect("failed to seed prng");
+  let copies = multiply_fleet(&mut fleet, multiplier, point_cap.filter(|_| trim), &mut rng);
+  if let Some(point_cap) = point_cap.filter(|_| copies < multiplier) {
+    println!("left out {} copies to fit under the point cap of {point_cap}", multiplier - copies);
+  };
+
+  println!("multiplied fleet {original_name:?} of {original_ship_count} ships by {copies}");
+
+  let out_path = out_path.unwrap_or_else(|| {
     let file_stem = in_path.file_stem().expect("invalid path");
     let extension = in_path.extension().expect("invalid path");
 
     let file_stem_str = file_stem.to_str().expect("invalid_path");
-    let mut file_name = OsString::from(apply_double_suffix(file_stem_str));
+    let mut file_name = OsString::from(apply_multiplier_suffix(file_stem_str, copies));
     file_name.push(".");
     file_name.push(extension);
 
     in_path.with_file_name(file_name)
   });
 
-  println!("reading fleet from {}", in_path.display());
-  let reader = BufReader::new(File::open(&in_path).expect("failed to open file"));
-  let nodes = read_nodes(reader).expect("failed to read file");
-  let mut root = <Root<Fleet>>::deserialize_nodes(nodes).expect("failed to deserialize nodes");
-  println!("successfully read fleet from {}", in_path.display());
-
-  let original_name = root.element.name.clone();
-  let original_ship_count = root.element.ships.len();
+  let diagnostics = fleet.validate(point_cap);
+  for diagnostic in diagnostics.iter() {
+    println!("error: {diagnostic}");
+  };
 
-  let mut rng = Random::from_rng(OsRng).expect("failed to seed prng");
-  root.element.ships.extend(root.element.dupe_ships(&mut rng));
-  root.element.name = apply_double_suffix(&root.element.name);
-  root.element.total_points *= 2;
+  if fleet.ships.len() > Fleet::MAX_SHIPS {
+    println!("error: the resulting fleet has {} ships, which is more than the limit of {}", fleet.ships.len(), Fleet::MAX_SHIPS);
+  };
 
-  println!("doubled fleet {original_name:?} of {original_ship_count} ships");
-  if original_ship_count * 2 > 10 {
-    println!("warning: the resulting fleet has more than 10 ships, it may not load correctly");
+  if !diagnostics.is_empty() || fleet.ships.len() > Fleet::MAX_SHIPS {
+    println!("the resulting fleet is invalid, not writing it to {}", out_path.display());
+    pause();
+    return ExitCode::FAILURE;
   };
 
   println!("writing fleet to {}", out_path.display());
-  let writer = BufWriter::new(File::create(&out_path).expect("failed to create file"));
-  let nodes = root.serialize_nodes().expect("failed to serialize nodes");
-  write_nodes(writer, &nodes, Some(Indent::default()), Some(Version::default())).expect("failed to write nodes");
+  fleet.save(&out_path).expect("failed to write fleet");
   println!("successfully wrote fleet to {}", out_path.display());
 
   pause();
+  ExitCode::SUCCESS
 }
 
-fn apply_double_suffix(s: &str) -> String {
+/// Adds copies of the fleet's ships beside the originals until there are `multiplier` copies of it, counting the original,
+/// and returns the number of copies that were kept.
+///
+/// If a point cap is given, whole copies are left out, last first, until the fleet fits under it.
+/// When more than one copy is kept, every copy has its weapon groups numbered, starting from the original.
+fn multiply_fleet<R: Rng + ?Sized>(fleet: &mut Fleet, multiplier: usize, point_cap: Option<usize>, rng: &mut R) -> usize {
+  let mut copies = (1..multiplier)
+    .map(|copy| fleet.dupe_ships_beside(copy, rng))
+    .collect::<Vec<Vec<Ship>>>();
+
+  if let Some(point_cap) = point_cap {
+    let mut total_points = fleet.ships.iter().chain(copies.iter().flatten()).map(|ship| ship.cost).sum::<usize>();
+    while total_points > point_cap {
+      let Some(copy) = copies.pop() else { break };
+      total_points -= copy.iter().map(|ship| ship.cost).sum::<usize>();
+    };
+  };
+
+  let count = copies.len() + 1;
+  if count > 1 {
+    for (copy, ships) in std::iter::once(&mut fleet.ships).chain(copies.iter_mut()).enumerate() {
+      ships.iter_mut().for_each(|ship| rename_weapon_groups(ship, copy + 1));
+    };
+  };
+
+  fleet.ships.extend(copies.into_iter().flatten());
+  fleet.name = apply_multiplier_suffix(&fleet.name, count);
+  fleet.total_points = fleet.ships.iter().map(|ship| ship.cost).sum();
+  count
+}
+
+/// Renames each of a ship's weapon groups after the copy of the fleet it belongs to.
+fn rename_weapon_groups(ship: &mut Ship, copy_number: usize) {
+  for weapon_group in ship.weapon_groups.iter_mut() {
+    weapon_group.name = format!("{} {copy_number}", weapon_group.name);
+  };
+}
+
+fn apply_multiplier_suffix(s: &str, multiplier: usize) -> String {
   let (s, mul) = strip_multiplier(s).unwrap_or((s, 1));
-  format!("{s} ({}x)", mul * 2)
+  format!("{s} ({}x)", mul * multiplier)
 }
 
 fn strip_multiplier(s: &str) -> Option<(&str, usize)> {
@@ -69,7 +144,69 @@ fn strip_multiplier(s: &str) -> Option<(&str, usize)> {
   })
 }
 
+// Only waits for input, so the number of bytes read does not matter
+#[allow(clippy::unused_io_amount)]
 fn pause() {
   use std::io::{Read, stdin};
   stdin().lock().read(&mut []).unwrap();
 }
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use nebulous_data::format::read_nodes;
+  use nebulous_data::xml::DeserializeElement;
+  use rand::rngs::mock::StepRng;
+
+  fn test_fleet(name: &str, costs: &[usize]) -> Fleet {
+    let ships = costs.iter().enumerate().map(|(i, cost)| format!("<Ship><Key>{}</Key><Name>Ship</Name><Cost>{cost}</Cost>\
+      <Number>1</Number><HullType>Stock/Sprinter Corvette</HullType><SocketMap />\
+      <WeaponGroups><WepGroup Name=\"Guns\"><MemberKeys /></WepGroup></WeaponGroups></Ship>", nebulous_data::format::Uuid::from_u128(i as u128 + 1)))
+      .collect::<String>();
+    let total_points = costs.iter().sum::<usize>();
+    let fleet = format!("<Fleet><Name>{name}</Name><TotalPoints>{total_points}</TotalPoints><FactionKey>Stock/Alliance</FactionKey><Ships>{ships}</Ships></Fleet>");
+    Fleet::deserialize_element(read_nodes(fleet.as_bytes()).unwrap().try_into_one_element().unwrap()).unwrap()
+  }
+
+  fn weapon_group_names(fleet: &Fleet) -> Vec<&str> {
+    fleet.ships.iter().flat_map(|ship| ship.weapon_groups.iter()).map(|weapon_group| weapon_group.name.as_str()).collect()
+  }
+
+  #[test]
+  fn multiply() {
+    let mut fleet = test_fleet("Fleet (2x)", &[100, 50]);
+    assert_eq!(multiply_fleet(&mut fleet, 3, None, &mut StepRng::new(1, 1)), 3);
+    assert_eq!(fleet.name, "Fleet (6x)");
+    assert_eq!(fleet.ships.len(), 6);
+    assert_eq!(fleet.total_points, 450);
+    assert_eq!(weapon_group_names(&fleet), ["Guns 1", "Guns 1", "Guns 2", "Guns 2", "Guns 3", "Guns 3"]);
+  }
+
+  #[test]
+  fn multiply_under_point_cap() {
+    // A third copy would take the fleet to 450 points, so it is left out whole
+    let mut fleet = test_fleet("Fleet", &[100, 50]);
+    assert_eq!(multiply_fleet(&mut fleet, 3, Some(400), &mut StepRng::new(1, 1)), 2);
+    assert_eq!(fleet.name, "Fleet (2x)");
+    assert_eq!(fleet.ships.len(), 4);
+    assert_eq!(fleet.total_points, 300);
+    assert_eq!(weapon_group_names(&fleet), ["Guns 1", "Guns 1", "Guns 2", "Guns 2"]);
+
+    // The original is always kept, and its weapon groups are left alone if it is the only copy
+    let mut fleet = test_fleet("Fleet", &[100, 50]);
+    assert_eq!(multiply_fleet(&mut fleet, 2, Some(100), &mut StepRng::new(1, 1)), 1);
+    assert_eq!(fleet.name, "Fleet (1x)");
+    assert_eq!(fleet.total_points, 150);
+    assert_eq!(weapon_group_names(&fleet), ["Guns", "Guns"]);
+  }
+
+  #[test]
+  fn multiplier_suffix() {
+    assert_eq!(apply_multiplier_suffix("Fleet", 2), "Fleet (2x)");
+    assert_eq!(apply_multiplier_suffix("Fleet (3x)", 2), "Fleet (6x)");
+    assert_eq!(apply_multiplier_suffix("Fleet ( 2 X )", 2), "Fleet (4x)");
+    assert_eq!(strip_multiplier("Fleet (Blue)"), None);
+  }
+}
@@ -0,0 +1,656 @@
+use crate::library::Library;
+use crate::model::{FleetStrategy, MissileState, MissileType, ShipState};
+use crate::optimizer::{OptimizerOptions, PlannedShip};
+
+use nebulous_data::data::components::ComponentVariant;
+use nebulous_data::format::{Fleet, MunitionOrMissileKey};
+use nebulous_data::loadout::ShipLoadoutSocketVariant;
+use nebulous_data::xml::Extra;
+use rand::{Rng, SeedableRng};
+use rand::distributions::WeightedIndex;
+use rand::seq::SliceRandom;
+use rand_xoshiro::Xoroshiro128StarStar;
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+
+
+#[derive(Debug, Error)]
+pub enum GeneratorError {
+  #[error("no ship in the library could be selected by the strategy within {0} points")]
+  NoShipsSelected(usize),
+  #[error("missile type {0:?} is used by a selected ship but is not present in the library")]
+  MissingMissileType(String),
+  #[error("no ship in the library could be added to satisfy the constraint `{0}` within the remaining {1} points")]
+  ConstraintUnsatisfiable(String, usize),
+  #[error("the selected ships do not satisfy the constraint `{0}`")]
+  ConstraintUnsatisfied(String)
+}
+
+/// The PRNG used for seeded generation.
+///
+/// Its output for a given seed is fixed by its algorithm, so it will not change between versions of `rand`.
+pub type Random = Xoroshiro128StarStar;
+
+/// A ship from the library, along with its id there.
+pub type ShipEntry<'l> = (&'l str, &'l ShipState);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratorOptions {
+  pub fleet_name: String,
+  pub point_budget: usize,
+  pub fill_mode: FillMode
+}
+
+/// How ships are chosen to fill the point budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillMode {
+  /// Ships are picked one at a time with [`select_ships`], stopping when nothing else fits.
+  #[default]
+  Random,
+  /// Ships are chosen with [`crate::optimizer::optimize_ships`] to use as much of the budget as possible.
+  Optimal,
+  /// Like [`FillMode::Optimal`], but magazines may be trimmed to fit more ships.
+  OptimalWithTrims
+}
+
+impl FillMode {
+  pub const fn to_str(self) -> &'static str {
+    match self {
+      Self::Random => "random",
+      Self::Optimal => "optimal",
+      Self::OptimalWithTrims => "optimal_trims"
+    }
+  }
+}
+
+impl fmt::Display for FillMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.to_str())
+  }
+}
+
+impl FromStr for FillMode {
+  type Err = InvalidFillMode;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "random" => Ok(Self::Random),
+      "optimal" => Ok(Self::Optimal),
+      "optimal_trims" => Ok(Self::OptimalWithTrims),
+      _ => Err(InvalidFillMode)
+    }
+  }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid fill mode, expected one of `random`, `optimal` or `optimal_trims`")]
+pub struct InvalidFillMode;
+
+const MANIFEST_PREFIX: &str = "nebulous-fleet-generator:";
+
+/// Everything needed to regenerate a fleet exactly.
+///
+/// This is embedded as a single line in the generated fleet's description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerationManifest {
+  pub seed: u64,
+  pub point_budget: usize,
+  pub fill_mode: FillMode,
+  /// The [`crate::utils::hash_bytes`] hash of the strategy file.
+  pub strategy_hash: u64,
+  pub library_revision: u64,
+  /// The [`Library::content_hash`] of the library, or `None` in manifests written before it was recorded.
+  pub library_hash: Option<u64>
+}
+
+impl GenerationManifest {
+  pub fn new(seed: u64, point_budget: usize, fill_mode: FillMode, strategy_source: &[u8], library: &Library) -> Self {
+    GenerationManifest {
+      seed,
+      point_budget,
+      fill_mode,
+      strategy_hash: crate::utils::hash_bytes(strategy_source),
+      library_revision: library.revision,
+      library_hash: Some(library.content_hash())
+    }
+  }
+
+  /// Whether the library this manifest was made from differs from the one `current` was made from.
+  ///
+  /// Manifests that did not record a library hash can only be compared by revision.
+  pub fn library_differs(&self, current: &GenerationManifest) -> bool {
+    match (self.library_hash, current.library_hash) {
+      (Some(library_hash), Some(current_library_hash)) => library_hash != current_library_hash,
+      _ => self.library_revision != current.library_revision
+    }
+  }
+
+  /// Finds and parses the manifest line in a fleet description.
+  pub fn from_description(description: &str) -> Option<Self> {
+    description.lines().find_map(|line| line.trim().parse().ok())
+  }
+}
+
+impl fmt::Display for GenerationManifest {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f, "{MANIFEST_PREFIX} seed={} budget={} fill={} strategy={:016x} library={}",
+      self.seed, self.point_budget, self.fill_mode, self.strategy_hash, self.library_revision
+    )?;
+
+    if let Some(library_hash) = self.library_hash {
+      write!(f, " library_hash={library_hash:016x}")?;
+    };
+
+    Ok(())
+  }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid generation manifest")]
+pub struct InvalidGenerationManifest;
+
+impl FromStr for GenerationManifest {
+  type Err = InvalidGenerationManifest;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let fields = s.strip_prefix(MANIFEST_PREFIX).ok_or(InvalidGenerationManifest)?;
+    let (mut seed, mut point_budget, mut strategy_hash, mut library_revision) = (None, None, None, None);
+    let mut library_hash = None;
+    // manifests written before fill modes were added always used random filling
+    let mut fill_mode = Some(FillMode::Random);
+    for field in fields.split_whitespace() {
+      let (key, value) = field.split_once('=').ok_or(InvalidGenerationManifest)?;
+      match key {
+        "seed" => seed = value.parse().ok(),
+        "budget" => point_budget = value.parse().ok(),
+        "fill" => fill_mode = value.parse().ok(),
+        "strategy" => strategy_hash = u64::from_str_radix(value, 16).ok(),
+        "library" => library_revision = value.parse().ok(),
+        "library_hash" => library_hash = Some(u64::from_str_radix(value, 16).map_err(|_| InvalidGenerationManifest)?),
+        _ => return Err(InvalidGenerationManifest)
+      };
+    };
+
+    Ok(GenerationManifest {
+      seed: seed.ok_or(InvalidGenerationManifest)?,
+      point_budget: point_budget.ok_or(InvalidGenerationManifest)?,
+      fill_mode: fill_mode.ok_or(InvalidGenerationManifest)?,
+      strategy_hash: strategy_hash.ok_or(InvalidGenerationManifest)?,
+      library_revision: library_revision.ok_or(InvalidGenerationManifest)?,
+      library_hash
+    })
+  }
+}
+
+/// Generates a fleet using [`Random`] seeded from the manifest, and records the manifest in the fleet's description.
+///
+/// Given the same library contents, strategy and fleet name, this always produces the same fleet.
+pub fn generate_fleet_seeded(
+  library: &Library,
+  strategy: &FleetStrategy,
+  fleet_name: String,
+  manifest: &GenerationManifest
+) -> Result<Fleet, GeneratorError> {
+  let options = GeneratorOptions { fleet_name, point_budget: manifest.point_budget, fill_mode: manifest.fill_mode };
+  let mut rng = Random::seed_from_u64(manifest.seed);
+  let mut fleet = generate_fleet(library, strategy, &options, &mut rng)?;
+  fleet.description = Some(manifest.to_string());
+  Ok(fleet)
+}
+
+/// Selects ships from the library according to the strategy and assembles them into a fleet.
+pub fn generate_fleet<R: Rng + ?Sized>(
+  library: &Library,
+  strategy: &FleetStrategy,
+  options: &GeneratorOptions,
+  rng: &mut R
+) -> Result<Fleet, GeneratorError> {
+  let library = &*assign_missiles(library, strategy, rng);
+  let ship_states: Vec<Cow<'_, ShipState>> = match options.fill_mode {
+    FillMode::Random => {
+      select_ships(library, strategy, options.point_budget, rng)?
+        .into_iter().map(|(_, ship_state)| Cow::Borrowed(ship_state)).collect()
+    },
+    FillMode::Optimal | FillMode::OptimalWithTrims => {
+      let mut pool = library.ships.iter().collect::<Vec<ShipEntry<'_>>>();
+      pool.shuffle(rng);
+
+      let required = select_required_ships(&pool, strategy, options.point_budget, rng)?
+        .into_iter()
+        .map(|(_, ship_state)| {
+          let selection = strategy.selections.iter()
+            .position(|selection| selection.weight_initial > 0 && selection.predicates.accepts(ship_state))
+            .expect("required ships are accepted by a selection");
+          PlannedShip { ship_state, selection, trims: Vec::new() }
+        })
+        .collect::<Vec<PlannedShip<'_>>>();
+      let allow_trims = options.fill_mode == FillMode::OptimalWithTrims;
+      let optimizer_options = OptimizerOptions { point_budget: options.point_budget, allow_trims };
+      let pool = pool.into_iter().map(|(_, ship_state)| ship_state).collect::<Vec<&ShipState>>();
+      crate::optimizer::optimize_ships(&pool, required, library, strategy, &optimizer_options)
+        .iter().map(PlannedShip::to_ship_state).collect()
+    }
+  };
+
+  if ship_states.is_empty() {
+    return Err(GeneratorError::NoShipsSelected(options.point_budget));
+  };
+
+  let ship_states = ship_states.iter().map(Cow::as_ref).collect::<Vec<&ShipState>>();
+  if let Some(constraint) = strategy.constraints.iter().find(|constraint| !constraint.test(&ship_states)) {
+    return Err(GeneratorError::ConstraintUnsatisfied(constraint.to_string()));
+  };
+
+  assemble_fleet(library, strategy, options, &ship_states, rng)
+}
+
+/// Refits every ship in the library with missiles chosen according to the strategy's missile selections,
+/// so that ships are selected by what they will cost once they have been refitted.
+///
+/// If the strategy has no missile selections, the library is returned as it is and `rng` is left untouched.
+pub fn assign_missiles<'l, R: Rng + ?Sized>(
+  library: &'l Library,
+  strategy: &FleetStrategy,
+  rng: &mut R
+) -> Cow<'l, Library> {
+  if strategy.missiles.is_empty() {
+    return Cow::Borrowed(library);
+  };
+
+  let mut refitted = library.clone();
+  for id in library.ships.ids() {
+    let ship_state = refitted.ships.get_mut(id).expect("ship ids are shared between clones");
+    assign_ship_missiles(ship_state, library, strategy, rng);
+  };
+
+  Cow::Owned(refitted)
+}
+
+/// Replaces the missiles in a ship's launchers and magazines, using a single missile for each missile type.
+///
+/// Cell launchers are filled to capacity, even if they were empty. Magazines only have the missiles they already
+/// hold replaced, since they may be shared with other munitions.
+fn assign_ship_missiles<R: Rng + ?Sized>(
+  ship_state: &mut ShipState,
+  library: &Library,
+  strategy: &FleetStrategy,
+  rng: &mut R
+) {
+  let mut chosen = BTreeMap::new();
+  let mut choose = |missile_type: MissileType, rng: &mut R| -> Option<MunitionOrMissileKey> {
+    chosen.entry(missile_type)
+      .or_insert_with(|| choose_missile(library, strategy, missile_type, rng))
+      .map(|missile_state| MunitionOrMissileKey::MissileKey(missile_state.template_name().into_boxed_str()))
+  };
+
+  let hull = ship_state.loadout.hull_type.hull();
+  for (socket, hull_socket) in ship_state.loadout.sockets.iter_mut().zip(hull.sockets.iter()) {
+    let Some(socket) = socket else { continue };
+    let component = socket.component_key.component();
+    match component.variant {
+      Some(ComponentVariant::WeaponMissileBank { munition_family, cells, .. }) => {
+        let Some(missile_type) = MissileType::from_munition_family(munition_family) else { continue };
+        let Some(count) = cells.get_count(hull_socket.size, component.size) else { continue };
+        let Some(missile_key) = choose(missile_type, rng) else { continue };
+        let magazine_contents = [(missile_key, count)].into_iter().collect();
+        socket.variant = Some(ShipLoadoutSocketVariant::MagazineComponent { magazine_contents });
+      },
+      Some(ComponentVariant::Magazine { .. }) => {
+        let Some(ShipLoadoutSocketVariant::MagazineComponent { magazine_contents }) = socket.variant.as_mut() else { continue };
+        for (munition_key, quantity) in std::mem::take(magazine_contents) {
+          let munition_key = missile_type_of(&munition_key, library)
+            .and_then(|missile_type| choose(missile_type, rng))
+            .unwrap_or(munition_key);
+          *magazine_contents.entry(munition_key).or_default() += quantity;
+        };
+      },
+      Some(..) | None => ()
+    };
+  };
+
+  // Missile costs are recalculated from the library, just as they will be when the fleet is assembled
+  let missile_cost = ship_state.loadout.sockets.iter().flatten()
+    .filter_map(|socket| socket.variant.as_ref()?.get_magazine_contents())
+    .flatten()
+    .filter_map(|(munition_key, quantity)| match munition_key {
+      MunitionOrMissileKey::MissileKey(missile_key) => library.find_missile(missile_key).map(|missile_state| missile_state.cost * quantity),
+      MunitionOrMissileKey::MunitionKey(..) => None
+    })
+    .sum::<usize>();
+  ship_state.cost_budget_total = ship_state.cost_budget_total - ship_state.cost_budget_spare + missile_cost;
+  ship_state.cost_budget_spare = missile_cost;
+}
+
+/// Picks a missile for launchers of the given type from the library, using the strategy's missile selection for it.
+fn choose_missile<'l, R: Rng + ?Sized>(
+  library: &'l Library,
+  strategy: &FleetStrategy,
+  missile_type: MissileType,
+  rng: &mut R
+) -> Option<&'l MissileState> {
+  let predicates = &strategy.missile_selection(missile_type)?.predicates;
+  let candidates = library.missiles.values()
+    .filter(|missile_state| missile_state.missile_type() == missile_type)
+    .filter(|missile_state| strategy.is_missile_usable(missile_state) && predicates.accepts(missile_state))
+    .collect::<Vec<&MissileState>>();
+  let prioritized = candidates.iter().copied()
+    .filter(|missile_state| predicates.prioritizes(missile_state))
+    .collect::<Vec<&MissileState>>();
+
+  let pool = if prioritized.is_empty() { candidates } else { prioritized };
+  pool.choose(rng).copied()
+}
+
+/// The type of a missile held in a magazine, if it is a stock missile or a missile from the library.
+fn missile_type_of(munition_key: &MunitionOrMissileKey, library: &Library) -> Option<MissileType> {
+  match munition_key {
+    MunitionOrMissileKey::MunitionKey(munition_key) => MissileType::from_munition_family(munition_key.munition().family),
+    MunitionOrMissileKey::MissileKey(missile_key) => library.find_missile(missile_key).map(MissileState::missile_type)
+  }
+}
+
+/// Picks ships for each of the strategy's constraints that needs more of its quantity,
+/// so that they are satisfied before any other ships are chosen.
+///
+/// Ships are only picked if they are accepted by one of the strategy's selections,
+/// and if they would not take the fleet over the upper bound of any constraint.
+pub fn select_required_ships<'l, R: Rng + ?Sized>(
+  pool: &[ShipEntry<'l>],
+  strategy: &FleetStrategy,
+  point_budget: usize,
+  rng: &mut R
+) -> Result<Vec<ShipEntry<'l>>, GeneratorError> {
+  let mut selected = Vec::new();
+  let mut selected_states = Vec::new();
+  let mut remaining = point_budget;
+  while let Some(constraint) = strategy.constraints.iter().find(|constraint| constraint.needs_more(&selected_states)) {
+    if selected.len() >= Fleet::MAX_SHIPS {
+      return Err(GeneratorError::ConstraintUnsatisfiable(constraint.to_string(), remaining));
+    };
+
+    let candidates = pool.iter().copied()
+      // zero-cost ships could be added indefinitely
+      .filter(|(_, ship_state)| ship_state.cost_budget_total > 0 && ship_state.cost_budget_total <= remaining)
+      .filter(|(_, ship_state)| constraint.quantity.measure(ship_state) > 0)
+      .filter(|(_, ship_state)| strategy.is_usable(ship_state) && strategy.selections.iter().any(|selection| {
+        selection.weight_initial > 0 && selection.predicates.accepts(ship_state)
+      }))
+      .filter(|&(_, ship_state)| {
+        let mut with = selected_states.clone();
+        with.push(ship_state);
+        !strategy.constraints.iter().any(|constraint| constraint.has_too_many(&with))
+      })
+      .collect::<Vec<ShipEntry<'l>>>();
+
+    let Some(&(id, ship_state)) = candidates.choose(rng) else {
+      return Err(GeneratorError::ConstraintUnsatisfiable(constraint.to_string(), remaining));
+    };
+
+    remaining -= ship_state.cost_budget_total;
+    selected.push((id, ship_state));
+    selected_states.push(ship_state);
+  };
+
+  Ok(selected)
+}
+
+/// Picks the ships required by the strategy's constraints with [`select_required_ships`], then repeatedly picks
+/// a selection from the strategy by weight and a ship matching that selection's predicates,
+/// until no selection can contribute a ship that fits in the remaining budget or the fleet has [`Fleet::MAX_SHIPS`].
+///
+/// Ships that would break a constraint which the fleet satisfies so far are never picked.
+/// The picked ships are returned along with their ids in the library.
+pub fn select_ships<'l, R: Rng + ?Sized>(
+  library: &'l Library,
+  strategy: &FleetStrategy,
+  point_budget: usize,
+  rng: &mut R
+) -> Result<Vec<ShipEntry<'l>>, GeneratorError> {
+  let pool = library.ships.iter().collect::<Vec<ShipEntry<'l>>>();
+  let mut selected = select_required_ships(&pool, strategy, point_budget, rng)?;
+  let mut selected_states = selected.iter().map(|&(_, ship_state)| ship_state).collect::<Vec<&ShipState>>();
+  let mut remaining = point_budget - selected_states.iter().map(|ship_state| ship_state.cost_budget_total).sum::<usize>();
+  let mut selection_counts = vec![0usize; strategy.selections.len()];
+  let mut selection_exhausted = vec![false; strategy.selections.len()];
+  for ship_state in selected_states.iter() {
+    if let Some(index) = strategy.selections.iter().position(|selection| selection.predicates.accepts(ship_state)) {
+      selection_counts[index] += 1;
+    };
+  };
+
+  while selected.len() < Fleet::MAX_SHIPS {
+    let weights = strategy.selections.iter().enumerate().map(|(i, selection)| {
+      if selection_exhausted[i] { 0 }
+      else if selection_counts[i] == 0 { selection.weight_initial }
+      else { selection.weight_additional }
+    });
+
+    // `WeightedIndex` fails when every weight is zero, meaning no selection can be picked
+    let Ok(distribution) = WeightedIndex::new(weights) else { break };
+    let index = rng.sample(distribution);
+    let predicates = &strategy.selections[index].predicates;
+
+    let candidates = pool.iter().copied()
+      // zero-cost ships could be added indefinitely
+      .filter(|(_, ship_state)| ship_state.cost_budget_total > 0 && ship_state.cost_budget_total <= remaining)
+      .filter(|(_, ship_state)| strategy.is_usable(ship_state) && predicates.accepts(ship_state))
+      .filter(|(_, ship_state)| !strategy.breaks_constraints(&selected_states, ship_state))
+      .collect::<Vec<ShipEntry<'l>>>();
+    let prioritized = candidates.iter().copied()
+      .filter(|(_, ship_state)| predicates.prioritizes(ship_state))
+      .collect::<Vec<ShipEntry<'l>>>();
+
+    let pool = if prioritized.is_empty() { candidates } else { prioritized };
+    if let Some(&(id, ship_state)) = pool.choose(rng) {
+      remaining -= ship_state.cost_budget_total;
+      selection_counts[index] += 1;
+      selected.push((id, ship_state));
+      selected_states.push(ship_state);
+    } else {
+      selection_exhausted[index] = true;
+    };
+  };
+
+  Ok(selected)
+}
+
+/// Converts the selected ships into a fleet, including every missile template they reference.
+pub fn assemble_fleet<R: Rng + ?Sized>(
+  library: &Library,
+  strategy: &FleetStrategy,
+  options: &GeneratorOptions,
+  ship_states: &[&ShipState],
+  rng: &mut R
+) -> Result<Fleet, GeneratorError> {
+  let mut missile_names = HashSet::new();
+  let mut missile_types = Vec::new();
+  for missile_name in ship_states.iter().flat_map(|ship_state| ship_state.iter_missile_names()) {
+    if missile_names.insert(missile_name) {
+      let missile_state = library.find_missile(missile_name)
+        .ok_or_else(|| GeneratorError::MissingMissileType(missile_name.to_owned()))?;
+      missile_types.push(missile_state.to_missile_template(rng));
+    };
+  };
+
+  let ships = ship_states.iter()
+    .map(|ship_state| ship_state.to_ship(&missile_types, &strategy.weapon_groups, rng))
+    .collect::<Vec<_>>();
+
+  let mut fleet = Fleet {
+    name: options.fleet_name.clone(),
+    total_points: 0,
+    faction_key: strategy.faction,
+    description: None,
+    ships,
+    missile_types,
+    extra: Extra::new()
+  };
+
+  fleet.recalculate_costs();
+  Ok(fleet)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::model::{FleetStrategySelection, MissileStrategyPredicates, MissileStrategySelection};
+  use crate::test_utils::{missile_state, ship_state};
+  use nebulous_data::data::{Faction, MissileSize};
+  use nebulous_data::data::components::ComponentKey;
+  use nebulous_data::data::hulls::HullKey;
+  use nebulous_data::loadout::ShipLoadoutSocket;
+
+  #[test]
+  fn assign_missiles_fills_launchers() {
+    let launcher = ComponentKey::VLS123Launcher;
+    let mut ship_state = ship_state("A", HullKey::RainesFrigate, 500);
+    let socket = HullKey::RainesFrigate.hull().sockets.iter()
+      .position(|socket| socket.kind == launcher.component().kind && launcher.component().can_fit_in(socket.size))
+      .unwrap();
+    ship_state.loadout.sockets[socket] = Some(ShipLoadoutSocket { component_key: launcher, variant: None });
+
+    let mut library = Library::new();
+    let id = library.ships.insert(ship_state);
+    library.missiles.insert(missile_state("Cheap", &[], 10));
+    library.missiles.insert(missile_state("Fancy", &["fancy"], 20));
+    library.missiles.insert(missile_state("Broken", &["fancy", "broken"], 30));
+    let strategy = FleetStrategy {
+      faction: Faction::Alliance,
+      selections: Vec::new(),
+      constraints: Vec::new(),
+      weapon_groups: Default::default(),
+      missiles: vec![MissileStrategySelection {
+        missile_type: MissileType::StandardMissile(MissileSize::Size1),
+        predicates: MissileStrategyPredicates {
+          reject: Some("tag/broken".parse().unwrap()),
+          require: None,
+          prioritize: Some("tag/fancy".parse().unwrap())
+        }
+      }]
+    };
+
+    let library = assign_missiles(&library, &strategy, &mut Random::seed_from_u64(0));
+    let ship_state = library.ships.get(&id).unwrap();
+    let magazine_contents = ship_state.loadout.sockets[socket].as_ref()
+      .and_then(|socket| socket.variant.as_ref()?.get_magazine_contents())
+      .unwrap();
+    let missile_key = MunitionOrMissileKey::MissileKey("SGM-1 Fancy".into());
+    assert_eq!(magazine_contents.get(&missile_key), Some(&23));
+    assert_eq!(magazine_contents.len(), 1);
+    assert_eq!(ship_state.cost_budget_spare, 23 * 20);
+    assert_eq!(ship_state.cost_budget_total, 500 + 23 * 20);
+  }
+
+  #[test]
+  fn generation_satisfies_constraints() {
+    let mut library = Library::new();
+    library.ships.insert(ship_state("A", HullKey::SprinterCorvette, 300));
+    library.ships.insert(ship_state("B", HullKey::RainesFrigate, 500));
+    library.ships.insert(ship_state("C", HullKey::KeystoneDestroyer, 800));
+    let mut strategy = FleetStrategy {
+      faction: Faction::Alliance,
+      selections: vec![FleetStrategySelection {
+        weight_initial: 1,
+        weight_additional: 1,
+        predicates: Default::default()
+      }],
+      missiles: Vec::new(),
+      constraints: vec![
+        "count(hull_key/keystone) >= 2".parse().unwrap(),
+        "count(hull_key/sprinter) <= 1".parse().unwrap()
+      ],
+      weapon_groups: Default::default()
+    };
+
+    for fill_mode in [FillMode::Random, FillMode::Optimal, FillMode::OptimalWithTrims] {
+      for seed in 0..8 {
+        let manifest = GenerationManifest::new(seed, 3000, fill_mode, b"{}", &library);
+        let fleet = generate_fleet_seeded(&library, &strategy, "Test".to_owned(), &manifest).unwrap();
+        let count = |hull_type: HullKey| fleet.ships.iter().filter(|ship| ship.hull_type == hull_type).count();
+        assert!(count(HullKey::KeystoneDestroyer) >= 2, "{fill_mode} with seed {seed}");
+        assert!(count(HullKey::SprinterCorvette) <= 1, "{fill_mode} with seed {seed}");
+      };
+    };
+
+    strategy.constraints.push("count(hull_key/keystone) >= 4".parse().unwrap());
+    let manifest = GenerationManifest::new(0, 3000, FillMode::Random, b"{}", &library);
+    let error = generate_fleet_seeded(&library, &strategy, "Test".to_owned(), &manifest).unwrap_err();
+    assert!(matches!(error, GeneratorError::ConstraintUnsatisfiable(constraint, 600) if constraint == "count(hull_key/keystone) >= 4"));
+  }
+
+  #[test]
+  fn selection_ends() {
+    let mut library = Library::new();
+    library.ships.insert(ship_state("Free", HullKey::SprinterCorvette, 0));
+    library.ships.insert(ship_state("A", HullKey::SprinterCorvette, 100));
+    let strategy = FleetStrategy {
+      faction: Faction::Alliance,
+      selections: vec![FleetStrategySelection {
+        weight_initial: 1,
+        weight_additional: 1,
+        predicates: Default::default()
+      }],
+      missiles: Vec::new(),
+      constraints: Vec::new(),
+      weapon_groups: Default::default()
+    };
+
+    let selected = select_ships(&library, &strategy, 3000, &mut Random::seed_from_u64(0)).unwrap();
+    assert_eq!(selected.len(), Fleet::MAX_SHIPS);
+    assert!(selected.iter().all(|&(id, _)| id == "a"));
+  }
+
+  #[test]
+  fn manifest_round_trip() {
+    let manifest = GenerationManifest {
+      seed: 1234, point_budget: 3000, fill_mode: FillMode::Optimal,
+      strategy_hash: 0xdeadbeef, library_revision: 7, library_hash: Some(0xfeed)
+    };
+    let description = format!("Some fleet\n{manifest}\n");
+    assert_eq!(GenerationManifest::from_description(&description), Some(manifest));
+    assert_eq!(GenerationManifest::from_description("Some fleet"), None);
+
+    // Manifests from before the library hash was recorded are compared by revision alone
+    let old = "nebulous-fleet-generator: seed=1234 budget=3000 fill=optimal strategy=00000000deadbeef library=7"
+      .parse::<GenerationManifest>().unwrap();
+    assert_eq!(old, GenerationManifest { library_hash: None, ..manifest });
+    assert!(!old.library_differs(&manifest));
+    assert!(manifest.library_differs(&GenerationManifest { library_hash: Some(0xbeef), ..manifest }));
+  }
+
+  #[test]
+  fn seeded_generation_is_reproducible() {
+    let mut library = Library::new();
+    library.ships.insert(ship_state("A", HullKey::SprinterCorvette, 300));
+    library.ships.insert(ship_state("B", HullKey::RainesFrigate, 500));
+    library.ships.insert(ship_state("C", HullKey::VauxhallLightCruiser, 1200));
+    let strategy = FleetStrategy {
+      faction: Faction::Alliance,
+      selections: vec![FleetStrategySelection {
+        weight_initial: 1,
+        weight_additional: 1,
+        predicates: Default::default()
+      }],
+      missiles: Vec::new(),
+      constraints: Vec::new(),
+      weapon_groups: Default::default()
+    };
+
+    for fill_mode in [FillMode::Random, FillMode::Optimal] {
+      let manifest = GenerationManifest::new(42, 3000, fill_mode, b"{}", &library);
+      let fleet1 = generate_fleet_seeded(&library, &strategy, "Test".to_owned(), &manifest).unwrap();
+      let fleet2 = generate_fleet_seeded(&library, &strategy, "Test".to_owned(), &manifest).unwrap();
+      assert_eq!(fleet1, fleet2);
+      assert!(fleet1.ships.iter().all(|ship| ship.cost == ship.hull_type.hull().point_cost));
+      assert_eq!(fleet1.total_points, fleet1.ships.iter().map(|ship| ship.cost).sum::<usize>());
+      assert_eq!(fleet1.description.as_deref().and_then(GenerationManifest::from_description), Some(manifest));
+    };
+  }
+}
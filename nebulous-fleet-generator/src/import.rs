@@ -0,0 +1,220 @@
+//! Importing ship and missile designs from a NEBULOUS `Saves` directory into a [`Library`].
+
+use crate::library::Library;
+use crate::model::{MissileState, ModelError, ShipState};
+use crate::utils::slugify;
+
+use nebulous_data::format::{FileError, MissileCostMismatch, MissileTemplate, SaveFile, SaveKind};
+use nebulous_data::loadout::ShipLoadoutError;
+use walkdir::WalkDir;
+
+use std::path::{Path, PathBuf};
+
+
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+  #[error(transparent)]
+  WalkDir(#[from] walkdir::Error),
+  #[error(transparent)]
+  File(#[from] FileError),
+  #[error("failed to convert ship {0:?}: {1}")]
+  Ship(String, ShipLoadoutError),
+  #[error("failed to convert missile {0:?}: {1}")]
+  Missile(String, ModelError)
+}
+
+/// An error encountered while importing a single file.
+#[derive(Debug)]
+pub struct ImportFailure {
+  pub path: PathBuf,
+  pub error: ImportError
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+  /// The number of save files that were successfully parsed.
+  pub files_read: usize,
+  /// The ids of ships newly added to the library.
+  pub ships_added: Vec<String>,
+  /// The number of ships whose loadout was already present in the library.
+  pub ships_merged: usize,
+  /// The ids of missiles newly added to the library.
+  pub missiles_added: Vec<String>,
+  /// The number of missiles that were already present in the library.
+  pub missiles_merged: usize,
+  pub failures: Vec<ImportFailure>,
+  /// Missile templates whose stored cost differs from their calculated cost.
+  /// These are still imported, using the calculated cost.
+  pub cost_mismatches: Vec<(PathBuf, String, MissileCostMismatch)>
+}
+
+/// Imports every `.fleet`, `.ship` and `.missile` file found in `saves_dir` (recursively) into the library.
+///
+/// Ships with a loadout identical to one already in the library are merged into the existing entry,
+/// adding their name to its list of names. Missiles are merged when their name and loadout match.
+/// Entries that come from a fleet are tagged with [`fleet_tag`] of that fleet's name.
+///
+/// A file that fails to parse or convert is recorded in the report, and does not stop the import.
+pub fn import_saves(library: &mut Library, saves_dir: impl AsRef<Path>) -> ImportReport {
+  let saves_dir = saves_dir.as_ref();
+  let mut report = ImportReport::default();
+
+  let mut save_files = Vec::new();
+  for result in WalkDir::new(saves_dir).sort_by_file_name() {
+    let entry = match result {
+      Ok(entry) => entry,
+      Err(error) => {
+        let path = error.path().unwrap_or(saves_dir).to_owned();
+        report.failures.push(ImportFailure { path, error: error.into() });
+        continue;
+      }
+    };
+
+    if !entry.file_type().is_file() { continue };
+    let path = entry.into_path();
+    if path.extension().and_then(SaveKind::from_extension).is_none() { continue };
+    match SaveFile::load(&path) {
+      Ok(save_file) => save_files.push((path, save_file)),
+      Err(error) => report.failures.push(ImportFailure { path, error: error.into() })
+    };
+  };
+
+  report.files_read = save_files.len();
+
+  // Ships are costed against every missile template that was found,
+  // since standalone ship files may refer to templates saved separately
+  let missile_templates = save_files.iter()
+    .flat_map(|(_, save_file)| match save_file {
+      SaveFile::Fleet(fleet) => fleet.missile_types.iter()
+        .chain(fleet.ships.iter().flat_map(|ship| ship.missile_types.iter()))
+        .collect::<Vec<_>>(),
+      SaveFile::Ship(ship) => ship.missile_types.iter().collect(),
+      SaveFile::MissileTemplate(missile_template) => vec![missile_template]
+    })
+    .cloned()
+    .collect::<Vec<MissileTemplate>>();
+
+  for (path, save_file) in save_files.iter() {
+    let (tag, ships, missile_templates_local) = match save_file {
+      SaveFile::Fleet(fleet) => (Some(fleet_tag(&fleet.name)), fleet.ships.as_slice(), fleet.missile_types.as_slice()),
+      SaveFile::Ship(ship) => (None, std::slice::from_ref(ship), &[][..]),
+      SaveFile::MissileTemplate(missile_template) => (None, &[][..], std::slice::from_ref(missile_template))
+    };
+
+    let missile_templates_local = missile_templates_local.iter()
+      .chain(ships.iter().flat_map(|ship| ship.missile_types.iter()));
+    for missile_template in missile_templates_local {
+      if let Err(mismatch) = missile_template.check_cost() {
+        let name = format!("{} {}", missile_template.designation, missile_template.nickname);
+        report.cost_mismatches.push((path.clone(), name, mismatch));
+      };
+
+      match MissileState::from_missile_template(missile_template) {
+        Ok(missile_state) => import_missile(library, missile_state, tag.as_deref(), &mut report),
+        Err(error) => report.failures.push(ImportFailure {
+          path: path.clone(),
+          error: ImportError::Missile(missile_template.designation.clone(), error)
+        })
+      };
+    };
+
+    for ship in ships {
+      match ShipState::from_ship(ship, &missile_templates) {
+        Ok(ship_state) => import_ship(library, ship_state, tag.as_deref(), &mut report),
+        Err(error) => report.failures.push(ImportFailure {
+          path: path.clone(),
+          error: ImportError::Ship(ship.name.clone(), error)
+        })
+      };
+    };
+  };
+
+  report
+}
+
+fn import_ship(library: &mut Library, mut ship_state: ShipState, tag: Option<&str>, report: &mut ImportReport) {
+  let existing = library.ships.ids()
+    .find(|&id| library.ships.get(id).is_some_and(|existing| existing.loadout == ship_state.loadout))
+    .map(str::to_owned);
+  if let Some(existing) = existing.and_then(|id| library.ships.get_mut(&id)) {
+    for name in ship_state.name {
+      if !existing.name.contains(&name) {
+        existing.name.push(name);
+      };
+    };
+
+    existing.tags.extend(tag.map(str::to_owned));
+    report.ships_merged += 1;
+  } else {
+    ship_state.tags.extend(tag.map(str::to_owned));
+    report.ships_added.push(library.ships.insert(ship_state));
+  };
+}
+
+fn import_missile(library: &mut Library, mut missile_state: MissileState, tag: Option<&str>, report: &mut ImportReport) {
+  let existing = library.missiles.ids()
+    .find(|&id| library.missiles.get(id).is_some_and(|existing| {
+      existing.designation == missile_state.designation &&
+      existing.nickname == missile_state.nickname &&
+      existing.loadout == missile_state.loadout
+    }))
+    .map(str::to_owned);
+  if let Some(existing) = existing.and_then(|id| library.missiles.get_mut(&id)) {
+    existing.tags.extend(tag.map(str::to_owned));
+    report.missiles_merged += 1;
+  } else {
+    missile_state.tags.extend(tag.map(str::to_owned));
+    report.missiles_added.push(library.missiles.insert(missile_state));
+  };
+}
+
+/// Converts a fleet name into a tag that can be matched by the `tag/<name>` predicate,
+/// by lowercasing it and replacing every run of other characters with an underscore.
+pub fn fleet_tag(fleet_name: &str) -> String {
+  slugify(fleet_name, '_')
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn import_fixture_saves() {
+    let saves_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/saves");
+    let mut library = Library::new();
+    let report = import_saves(&mut library, &saves_dir);
+
+    assert_eq!(report.files_read, 3);
+    assert_eq!(report.failures.len(), 1);
+    assert!(report.failures[0].path.ends_with("Broken.fleet"));
+
+    // "Vanguard" and "Outrider" share a loadout, as do "Bulwark" and "Bulwark II"
+    assert_eq!(report.ships_added.len(), 2);
+    assert_eq!(report.ships_merged, 2);
+    assert_eq!(report.missiles_added.len(), 1);
+    assert_eq!(report.missiles_merged, 1);
+    // the fixture missile stores a cost of 10, but its components only add up to 4 points
+    assert_eq!(report.cost_mismatches.len(), 2);
+    assert_eq!(report.cost_mismatches[0].2, MissileCostMismatch { stored: 10, calculated: 4 });
+
+    let (_, bulwark) = library.ships.find_by_name("Bulwark").next().unwrap();
+    assert_eq!(bulwark.name, ["Bulwark", "Bulwark II"]);
+    assert!(bulwark.tags.contains("test_fleet"));
+    let (_, vanguard) = library.ships.find_by_name("Outrider").next().unwrap();
+    assert_eq!(vanguard.name, ["Vanguard", "Outrider"]);
+    assert!(library.find_missile("SGM-1 Lance").is_some_and(|missile| missile.tags.contains("test_fleet")));
+
+    // importing again changes nothing
+    let report = import_saves(&mut library, &saves_dir);
+    assert!(report.ships_added.is_empty() && report.missiles_added.is_empty());
+  }
+
+  #[test]
+  fn fleet_tags() {
+    assert_eq!(fleet_tag("Test Fleet"), "test_fleet");
+    assert_eq!(fleet_tag("[ANS] Heavy Line (v2)"), "ans_heavy_line_v2");
+  }
+}
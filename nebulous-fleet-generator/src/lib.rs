@@ -0,0 +1,25 @@
+// `chumsky` parsers are built out of large nested types and return large errors by design
+#![allow(clippy::result_large_err, clippy::type_complexity)]
+
+pub mod generator;
+pub mod import;
+pub mod library;
+pub mod model;
+pub mod optimizer;
+pub mod repl;
+pub mod utils;
+#[cfg(test)]
+mod test_utils;
+
+extern crate chumsky;
+extern crate nebulous_data;
+extern crate rand;
+extern crate rand_xoshiro;
+extern crate rustyline;
+#[macro_use]
+extern crate serde;
+extern crate singlefile;
+extern crate singlefile_formats;
+#[macro_use]
+extern crate thiserror;
+extern crate walkdir;
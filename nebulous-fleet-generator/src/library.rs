@@ -0,0 +1,521 @@
+//! A versioned, on-disk pool of ship and missile designs.
+//!
+//! A library is stored as a directory, laid out so that it can be shared and reviewed in version control:
+//!
+//! ```text
+//! <library>/library.json       manifest: schema version, revision and the ids of every entry
+//! <library>/ships/<id>.json    one file per ship entry
+//! <library>/missiles/<id>.json one file per missile entry
+//! ```
+//!
+//! Every file is written atomically, and the manifest is written last,
+//! so an interrupted save never leaves the manifest pointing at a missing entry.
+
+use crate::model::{MissileState, ShipState};
+use crate::utils::{slugify, FileError, JSON};
+
+use singlefile::FileFormat;
+use singlefile_formats::json_serde::Json;
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry as BTreeMapEntry;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+
+
+/// The newest library schema version understood by this crate.
+pub const LIBRARY_SCHEMA_VERSION: u32 = 1;
+
+const MANIFEST_FILE_NAME: &str = "library.json";
+const SHIPS_DIR_NAME: &str = "ships";
+const MISSILES_DIR_NAME: &str = "missiles";
+
+pub type JsonError = <Json as FileFormat<LibraryManifest>>::FormatError;
+
+#[derive(Debug, Error)]
+pub enum LibraryError {
+  #[error("failed to access {}: {1}", .0.display())]
+  Io(PathBuf, std::io::Error),
+  #[error("failed to parse {}: {1}", .0.display())]
+  Format(PathBuf, JsonError),
+  #[error("library schema version {0} is not supported (expected at most {LIBRARY_SCHEMA_VERSION})")]
+  UnsupportedSchemaVersion(u32),
+  #[error("manifest refers to {kind} entry {id:?}, but its file {} is missing", .path.display())]
+  MissingEntry { kind: &'static str, id: String, path: PathBuf },
+  #[error("manifest refers to {kind} entry {id:?}, which is not a valid id")]
+  InvalidEntryId { kind: &'static str, id: String }
+}
+
+impl LibraryError {
+  fn from_file_error(path: impl Into<PathBuf>, error: FileError<JsonError>) -> Self {
+    match error {
+      FileError::Io(error) => LibraryError::Io(path.into(), error),
+      FileError::Format(error) => LibraryError::Format(path.into(), error)
+    }
+  }
+}
+
+/// The contents of `library.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LibraryManifest {
+  pub schema_version: u32,
+  /// Incremented every time the library is saved.
+  pub revision: u64,
+  #[serde(default)]
+  pub ships: Vec<String>,
+  #[serde(default)]
+  pub missiles: Vec<String>
+}
+
+/// A design that can be stored in a library.
+pub trait LibraryEntry {
+  /// The kind of entry, used in diagnostics.
+  const KIND: &'static str;
+
+  /// Every name this design may be given.
+  fn names(&self) -> impl Iterator<Item = &str>;
+  /// The slug that the entry's id is derived from, which may be empty.
+  fn slug(&self) -> String;
+  fn author(&self) -> Option<&str>;
+  fn has_tag(&self, tag: &str) -> bool;
+}
+
+impl LibraryEntry for ShipState {
+  const KIND: &'static str = "ship";
+
+  fn names(&self) -> impl Iterator<Item = &str> {
+    self.name.iter().map(String::as_str)
+  }
+
+  fn slug(&self) -> String {
+    self.name.first().map_or_else(String::new, |name| slugify(name, '-'))
+  }
+
+  fn author(&self) -> Option<&str> {
+    self.author.as_deref()
+  }
+
+  fn has_tag(&self, tag: &str) -> bool {
+    self.tags.contains(tag)
+  }
+}
+
+impl LibraryEntry for MissileState {
+  const KIND: &'static str = "missile";
+
+  fn names(&self) -> impl Iterator<Item = &str> {
+    [self.designation.as_str(), self.nickname.as_str()].into_iter()
+  }
+
+  /// Missiles that share a designation are told apart by their nickname, as in their template name.
+  fn slug(&self) -> String {
+    slugify(&self.template_name(), '-')
+  }
+
+  fn author(&self) -> Option<&str> {
+    self.author.as_deref()
+  }
+
+  fn has_tag(&self, tag: &str) -> bool {
+    self.tags.contains(tag)
+  }
+}
+
+/// A collection of library entries keyed by a stable id, which also serves as the entry's file name.
+///
+/// Entries are kept ordered by id, so iteration order does not depend on insertion order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryEntries<T> {
+  entries: BTreeMap<String, T>
+}
+
+impl<T: LibraryEntry> LibraryEntries<T> {
+  pub const fn new() -> Self {
+    LibraryEntries { entries: BTreeMap::new() }
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  pub fn get(&self, id: &str) -> Option<&T> {
+    self.entries.get(id)
+  }
+
+  pub fn get_mut(&mut self, id: &str) -> Option<&mut T> {
+    self.entries.get_mut(id)
+  }
+
+  /// Adds an entry, returning the id it was assigned.
+  /// The id is derived from the entry's [`LibraryEntry::slug`], and made unique within this collection.
+  pub fn insert(&mut self, entry: T) -> String {
+    let base = entry.slug();
+    let base = if base.is_empty() { T::KIND.to_owned() } else { base };
+
+    let mut id = base.clone();
+    let mut n = 1;
+    loop {
+      match self.entries.entry(id) {
+        BTreeMapEntry::Vacant(vacant) => {
+          let id = vacant.key().clone();
+          vacant.insert(entry);
+          return id;
+        },
+        BTreeMapEntry::Occupied(..) => {
+          n += 1;
+          id = format!("{base}-{n}");
+        }
+      };
+    };
+  }
+
+  /// Adds or replaces an entry under a specific id.
+  pub fn insert_with_id(&mut self, id: impl Into<String>, entry: T) -> Option<T> {
+    self.entries.insert(id.into(), entry)
+  }
+
+  pub fn remove(&mut self, id: &str) -> Option<T> {
+    self.entries.remove(id)
+  }
+
+  pub fn ids(&self) -> impl DoubleEndedIterator<Item = &str> + Clone {
+    self.entries.keys().map(String::as_str)
+  }
+
+  pub fn values(&self) -> impl DoubleEndedIterator<Item = &T> + Clone {
+    self.entries.values()
+  }
+
+  pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&str, &T)> + Clone {
+    self.entries.iter().map(|(id, entry)| (id.as_str(), entry))
+  }
+
+  /// Finds entries with a name matching `name`, ignoring ASCII case.
+  pub fn find_by_name<'a>(&'a self, name: &'a str) -> impl Iterator<Item = (&'a str, &'a T)> + 'a {
+    self.iter().filter(move |(_, entry)| entry.names().any(|n| n.eq_ignore_ascii_case(name)))
+  }
+
+  /// Finds entries whose author matches `author`, ignoring ASCII case.
+  pub fn find_by_author<'a>(&'a self, author: &'a str) -> impl Iterator<Item = (&'a str, &'a T)> + 'a {
+    self.iter().filter(move |(_, entry)| entry.author().is_some_and(|a| a.eq_ignore_ascii_case(author)))
+  }
+
+  /// Finds entries that have every one of the given tags.
+  pub fn find_by_tags<'a>(&'a self, tags: &'a [&'a str]) -> impl Iterator<Item = (&'a str, &'a T)> + 'a {
+    self.iter().filter(move |(_, entry)| tags.iter().all(|tag| entry.has_tag(tag)))
+  }
+}
+
+impl<T: LibraryEntry> Default for LibraryEntries<T> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// A pool of ship and missile designs that the generator may choose from.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Library {
+  /// The revision of the library as of the last load or save.
+  pub revision: u64,
+  pub ships: LibraryEntries<ShipState>,
+  pub missiles: LibraryEntries<MissileState>
+}
+
+impl Library {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Loads a library from its directory.
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, LibraryError> {
+    let path = path.as_ref();
+    let manifest_path = path.join(MANIFEST_FILE_NAME);
+    let manifest: LibraryManifest = crate::utils::read_file(&manifest_path, JSON)
+      .map_err(|error| LibraryError::from_file_error(&manifest_path, error))?;
+    if manifest.schema_version > LIBRARY_SCHEMA_VERSION {
+      return Err(LibraryError::UnsupportedSchemaVersion(manifest.schema_version));
+    };
+
+    Ok(Library {
+      revision: manifest.revision,
+      ships: load_entries(&path.join(SHIPS_DIR_NAME), &manifest.ships)?,
+      missiles: load_entries(&path.join(MISSILES_DIR_NAME), &manifest.missiles)?
+    })
+  }
+
+  /// Loads a library from its directory, or returns an empty library if the directory has no manifest.
+  pub fn load_or_new(path: impl AsRef<Path>) -> Result<Self, LibraryError> {
+    let path = path.as_ref();
+    if path.join(MANIFEST_FILE_NAME).exists() {
+      Self::load(path)
+    } else {
+      Ok(Self::new())
+    }
+  }
+
+  /// Saves the library to its directory, incrementing its revision.
+  ///
+  /// Entry files that are no longer part of the library are deleted after the new manifest has been written.
+  pub fn save(&mut self, path: impl AsRef<Path>) -> Result<(), LibraryError> {
+    let path = path.as_ref();
+    let ships_path = path.join(SHIPS_DIR_NAME);
+    let missiles_path = path.join(MISSILES_DIR_NAME);
+    save_entries(&ships_path, &self.ships)?;
+    save_entries(&missiles_path, &self.missiles)?;
+
+    let manifest = LibraryManifest {
+      schema_version: LIBRARY_SCHEMA_VERSION,
+      revision: self.revision + 1,
+      ships: self.ships.ids().map(str::to_owned).collect(),
+      missiles: self.missiles.ids().map(str::to_owned).collect()
+    };
+
+    let manifest_path = path.join(MANIFEST_FILE_NAME);
+    crate::utils::write_file_atomic(&manifest_path, JSON, &manifest)
+      .map_err(|error| LibraryError::from_file_error(&manifest_path, error))?;
+    self.revision = manifest.revision;
+
+    remove_stale_entries(&ships_path, &self.ships)?;
+    remove_stale_entries(&missiles_path, &self.missiles)?;
+    Ok(())
+  }
+
+  /// A [`crate::utils::hash_bytes`] hash of the id and contents of every entry.
+  ///
+  /// Unlike the revision, this tells apart libraries that have been saved the same number of times
+  /// but hold different designs, such as two copies of a shared library that were each changed once.
+  pub fn content_hash(&self) -> u64 {
+    let mut buffer = Vec::new();
+    write_entries_for_hash(&mut buffer, &self.ships);
+    write_entries_for_hash(&mut buffer, &self.missiles);
+    crate::utils::hash_bytes(&buffer)
+  }
+
+  /// Finds the missile that ships refer to by the given template name.
+  pub fn find_missile(&self, template_name: &str) -> Option<&MissileState> {
+    self.missiles.values().find(|missile_state| missile_state.template_name() == template_name)
+  }
+}
+
+/// Whether an id is a plain slug, so that it cannot name a file outside of its entry directory.
+fn is_valid_id(id: &str) -> bool {
+  !id.is_empty() && id.bytes().all(|b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_'))
+}
+
+fn entry_path(dir: &Path, id: &str) -> PathBuf {
+  dir.join(format!("{id}.json"))
+}
+
+fn load_entries<T>(dir: &Path, ids: &[String]) -> Result<LibraryEntries<T>, LibraryError>
+where T: LibraryEntry, Json: FileFormat<T, FormatError = JsonError> {
+  let mut entries = LibraryEntries::new();
+  for id in ids {
+    if !is_valid_id(id) {
+      return Err(LibraryError::InvalidEntryId { kind: T::KIND, id: id.clone() });
+    };
+
+    let path = entry_path(dir, id);
+    let entry = crate::utils::read_file(&path, JSON).map_err(|error| match error {
+      FileError::Io(error) if error.kind() == std::io::ErrorKind::NotFound => {
+        LibraryError::MissingEntry { kind: T::KIND, id: id.clone(), path: path.clone() }
+      },
+      error => LibraryError::from_file_error(&path, error)
+    })?;
+
+    entries.insert_with_id(id.clone(), entry);
+  };
+
+  Ok(entries)
+}
+
+fn save_entries<T>(dir: &Path, entries: &LibraryEntries<T>) -> Result<(), LibraryError>
+where T: LibraryEntry, Json: FileFormat<T, FormatError = JsonError> {
+  std::fs::create_dir_all(dir).map_err(|error| LibraryError::Io(dir.to_owned(), error))?;
+  for (id, entry) in entries.iter() {
+    let path = entry_path(dir, id);
+    crate::utils::write_file_atomic(&path, JSON, entry)
+      .map_err(|error| LibraryError::from_file_error(&path, error))?;
+  };
+
+  Ok(())
+}
+
+fn write_entries_for_hash<T>(buffer: &mut Vec<u8>, entries: &LibraryEntries<T>)
+where T: LibraryEntry, Json: FileFormat<T, FormatError = JsonError> {
+  // Neither ids nor json can contain a nul byte, so it separates them unambiguously
+  buffer.extend_from_slice(T::KIND.as_bytes());
+  buffer.push(0);
+  for (id, entry) in entries.iter() {
+    buffer.extend_from_slice(id.as_bytes());
+    buffer.push(0);
+    JSON.to_writer(&mut *buffer, entry).expect("library entries are always serializable");
+    buffer.push(0);
+  };
+}
+
+fn remove_stale_entries<T: LibraryEntry>(dir: &Path, entries: &LibraryEntries<T>) -> Result<(), LibraryError> {
+  let read_dir = std::fs::read_dir(dir).map_err(|error| LibraryError::Io(dir.to_owned(), error))?;
+  for dir_entry in read_dir {
+    let path = dir_entry.map_err(|error| LibraryError::Io(dir.to_owned(), error))?.path();
+    if path.extension() != Some(OsStr::new("json")) { continue };
+    let Some(id) = path.file_stem().and_then(OsStr::to_str) else { continue };
+    if entries.get(id).is_none() {
+      std::fs::remove_file(&path).map_err(|error| LibraryError::Io(path.clone(), error))?;
+    };
+  };
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_utils::missile_state;
+  use nebulous_data::data::hulls::HullKey;
+
+  fn ship_state(name: &str, author: Option<&str>, tags: &[&str]) -> ShipState {
+    ShipState {
+      author: author.map(str::to_owned),
+      tags: tags.iter().map(|&tag| tag.to_owned()).collect(),
+      ..crate::test_utils::ship_state(name, HullKey::SprinterCorvette, 100)
+    }
+  }
+
+  fn read_manifest(path: &Path) -> LibraryManifest {
+    crate::utils::read_file(path.join(MANIFEST_FILE_NAME), JSON).unwrap()
+  }
+
+  #[test]
+  fn entries_lookup() {
+    let mut entries = LibraryEntries::new();
+    assert_eq!(entries.insert(ship_state("Vanguard", Some("Ann"), &["line", "brawler"])), "vanguard");
+    assert_eq!(entries.insert(ship_state("vanguard", None, &["line"])), "vanguard-2");
+    assert_eq!(entries.insert(ship_state("???", Some("Bob"), &["picket"])), "ship");
+    assert_eq!(entries.len(), 3);
+
+    let ids = |iter: &mut dyn Iterator<Item = (&str, &ShipState)>| iter.map(|(id, _)| id.to_owned()).collect::<Vec<String>>();
+    assert_eq!(ids(&mut entries.find_by_name("VANGUARD")), ["vanguard", "vanguard-2"]);
+    assert_eq!(ids(&mut entries.find_by_author("ann")), ["vanguard"]);
+    assert_eq!(ids(&mut entries.find_by_tags(&["line"])), ["vanguard", "vanguard-2"]);
+    assert_eq!(ids(&mut entries.find_by_tags(&["line", "brawler"])), ["vanguard"]);
+
+    assert!(entries.remove("vanguard").is_some());
+    assert!(entries.remove("vanguard").is_none());
+    assert!(entries.get("vanguard").is_none());
+    assert_eq!(entries.ids().collect::<Vec<&str>>(), ["ship", "vanguard-2"]);
+  }
+
+  #[test]
+  fn save_and_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut library = Library::load_or_new(dir.path()).unwrap();
+    assert_eq!(library, Library::new());
+
+    library.ships.insert(ship_state("Vanguard", Some("Ann"), &["line"]));
+    library.ships.insert(ship_state("Shrike", None, &[]));
+    assert_eq!(library.missiles.insert(missile_state("Needle", &[], 10)), "sgm-1-needle");
+    assert_eq!(library.missiles.insert(missile_state("Lance", &[], 10)), "sgm-1-lance");
+    library.save(dir.path()).unwrap();
+    assert_eq!(library.revision, 1);
+    assert!(dir.path().join("ships/vanguard.json").is_file());
+    assert!(dir.path().join("missiles/sgm-1-needle.json").is_file());
+
+    let manifest = read_manifest(dir.path());
+    assert_eq!(manifest.schema_version, LIBRARY_SCHEMA_VERSION);
+    assert_eq!(manifest.ships, ["shrike", "vanguard"]);
+    assert_eq!(Library::load(dir.path()).unwrap(), library);
+
+    library.save(dir.path()).unwrap();
+    assert_eq!(Library::load(dir.path()).unwrap().revision, 2);
+  }
+
+  #[test]
+  fn content_hash() {
+    let mut a = Library::new();
+    a.ships.insert(ship_state("Vanguard", None, &[]));
+    let mut b = a.clone();
+    assert_eq!(a.content_hash(), b.content_hash());
+
+    // Both libraries are at the same revision, but no longer hold the same designs
+    a.ships.insert(ship_state("Shrike", None, &[]));
+    b.ships.get_mut("vanguard").unwrap().tags.insert("line".to_owned());
+    assert_eq!(a.revision, b.revision);
+    assert_ne!(a.content_hash(), b.content_hash());
+    assert_ne!(a.content_hash(), Library::new().content_hash());
+  }
+
+  #[test]
+  fn save_removes_stale_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut library = Library::new();
+    library.ships.insert(ship_state("Vanguard", None, &[]));
+    library.ships.insert(ship_state("Shrike", None, &[]));
+    library.save(dir.path()).unwrap();
+
+    // Files that are not entries are left alone
+    std::fs::write(dir.path().join("ships/README.md"), "notes").unwrap();
+    library.ships.remove("shrike");
+    library.save(dir.path()).unwrap();
+    assert!(!dir.path().join("ships/shrike.json").exists());
+    assert!(dir.path().join("ships/vanguard.json").is_file());
+    assert!(dir.path().join("ships/README.md").is_file());
+    assert_eq!(Library::load(dir.path()).unwrap().ships.ids().collect::<Vec<&str>>(), ["vanguard"]);
+  }
+
+  #[test]
+  fn save_writes_manifest_last() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut library = Library::new();
+    library.ships.insert(ship_state("Vanguard", None, &[]));
+    library.save(dir.path()).unwrap();
+
+    // A directory in the way of the new entry's temporary file makes its write fail
+    std::fs::create_dir(dir.path().join("ships/shrike.json.tmp")).unwrap();
+    library.ships.insert(ship_state("Shrike", None, &[]));
+    assert!(matches!(library.save(dir.path()), Err(LibraryError::Io(..))));
+
+    let manifest = read_manifest(dir.path());
+    assert_eq!((manifest.revision, manifest.ships), (1, vec!["vanguard".to_owned()]));
+    assert_eq!(library.revision, 1);
+    assert!(Library::load(dir.path()).is_ok());
+  }
+
+  #[test]
+  fn load_rejects_newer_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = LibraryManifest { schema_version: LIBRARY_SCHEMA_VERSION + 1, revision: 1, ships: Vec::new(), missiles: Vec::new() };
+    crate::utils::write_file_atomic(dir.path().join(MANIFEST_FILE_NAME), JSON, &manifest).unwrap();
+    let error = Library::load(dir.path()).unwrap_err();
+    assert!(matches!(error, LibraryError::UnsupportedSchemaVersion(version) if version == LIBRARY_SCHEMA_VERSION + 1));
+  }
+
+  #[test]
+  fn load_reports_missing_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut library = Library::new();
+    library.ships.insert(ship_state("Vanguard", None, &[]));
+    library.save(dir.path()).unwrap();
+
+    std::fs::remove_file(dir.path().join("ships/vanguard.json")).unwrap();
+    let error = Library::load(dir.path()).unwrap_err();
+    assert!(matches!(error, LibraryError::MissingEntry { kind: "ship", ref id, .. } if id == "vanguard"));
+  }
+
+  #[test]
+  fn load_rejects_invalid_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("library/ships")).unwrap();
+    crate::utils::write_file_atomic(dir.path().join("x.json"), JSON, &ship_state("Vanguard", None, &[])).unwrap();
+    for id in ["../../x", "Vanguard", ""] {
+      let manifest = LibraryManifest { schema_version: LIBRARY_SCHEMA_VERSION, revision: 1, ships: vec![id.to_owned()], missiles: Vec::new() };
+      crate::utils::write_file_atomic(dir.path().join("library").join(MANIFEST_FILE_NAME), JSON, &manifest).unwrap();
+      let error = Library::load(dir.path().join("library")).unwrap_err();
+      assert!(matches!(error, LibraryError::InvalidEntryId { kind: "ship", id: ref invalid } if invalid == id));
+    };
+  }
+}
@@ -1,12 +1,234 @@
-mod utils;
-mod model;
-
-extern crate chumsky;
 extern crate nebulous_data;
+extern crate nebulous_fleet_generator;
 extern crate rand;
-#[macro_use]
-extern crate serde;
-#[macro_use]
-extern crate thiserror;
+extern crate singlefile;
+
+use nebulous_fleet_generator::generator::{FillMode, GenerationManifest};
+use nebulous_fleet_generator::library::{Library, LibraryEntries, LibraryEntry};
+use nebulous_fleet_generator::model::FleetStrategy;
+use nebulous_fleet_generator::repl::Repl;
+use nebulous_fleet_generator::utils::JSON;
+
+use nebulous_data::format::Fleet;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use singlefile::FileFormat;
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "\
+usage:
+  nebulous-fleet-generator generate <library> <strategy> <point budget> <output> [seed] [--fill <random|optimal|optimal_trims>]
+  nebulous-fleet-generator regenerate <library> <strategy> <fleet> <output>
+  nebulous-fleet-generator import <library> <saves dir>
+  nebulous-fleet-generator list <library> [name|author|tag <value>]
+  nebulous-fleet-generator remove <library> <ship|missile> <id>
+  nebulous-fleet-generator validate <fleet> [point budget]
+  nebulous-fleet-generator repl <library> [strategy]";
+
+fn main() {
+  let mut args = std::env::args_os().skip(1);
+  match args.next().as_ref().and_then(|command| command.to_str()) {
+    Some("generate") => generate(args),
+    Some("regenerate") => regenerate(args),
+    Some("import") => import(args),
+    Some("list") => list(args),
+    Some("remove") => remove(args),
+    Some("validate") => validate(args),
+    Some("repl") => repl(args),
+    _ => panic!("{USAGE}")
+  };
+}
+
+fn generate(mut args: impl Iterator<Item = OsString>) {
+  let library_path = PathBuf::from(args.next().expect(USAGE));
+  let strategy_path = PathBuf::from(args.next().expect(USAGE));
+  let point_budget = args.next().expect(USAGE)
+    .to_str().and_then(|point_budget| point_budget.parse::<usize>().ok())
+    .expect("invalid point budget");
+  let out_path = PathBuf::from(args.next().expect(USAGE));
+  let mut seed = None;
+  let mut fill_mode = FillMode::default();
+  while let Some(arg) = args.next() {
+    let arg = arg.into_string().expect(USAGE);
+    if arg == "--fill" {
+      fill_mode = args.next().expect(USAGE)
+        .to_str().and_then(|fill_mode| fill_mode.parse::<FillMode>().ok())
+        .expect("invalid fill mode");
+    } else {
+      seed = Some(arg.parse::<u64>().expect("invalid seed"));
+    };
+  };
+
+  let library = load_library(&library_path);
+  let (strategy, strategy_source) = load_strategy(&strategy_path);
+
+  let seed = seed.unwrap_or_else(|| OsRng.next_u64());
+  let manifest = GenerationManifest::new(seed, point_budget, fill_mode, &strategy_source, &library);
+  let fleet_name = out_path.file_stem().expect("invalid path")
+    .to_str().expect("invalid path").to_owned();
+  let fleet = nebulous_fleet_generator::generator::generate_fleet_seeded(&library, &strategy, fleet_name, &manifest)
+    .expect("failed to generate fleet");
+  println!("generated fleet {:?} of {} ships ({}/{} points) with seed {seed}", fleet.name, fleet.ships.len(), fleet.total_points, point_budget);
+
+  write_fleet(&out_path, fleet);
+}
+
+fn regenerate(mut args: impl Iterator<Item = OsString>) {
+  let library_path = PathBuf::from(args.next().expect(USAGE));
+  let strategy_path = PathBuf::from(args.next().expect(USAGE));
+  let fleet_path = PathBuf::from(args.next().expect(USAGE));
+  let out_path = PathBuf::from(args.next().expect(USAGE));
+
+  let original = read_fleet(&fleet_path);
+  let manifest = original.description.as_deref()
+    .and_then(GenerationManifest::from_description)
+    .expect("fleet has no generation manifest in its description");
+
+  let library = load_library(&library_path);
+  let (strategy, strategy_source) = load_strategy(&strategy_path);
+  let current = GenerationManifest::new(manifest.seed, manifest.point_budget, manifest.fill_mode, &strategy_source, &library);
+  if manifest.library_differs(&current) {
+    println!(
+      "warning: library differs from the one the fleet was generated with (revision {} then, {} now)",
+      manifest.library_revision, current.library_revision
+    );
+  };
+
+  if current.strategy_hash != manifest.strategy_hash {
+    println!("warning: strategy file differs from the one the fleet was generated with");
+  };
+
+  let fleet = nebulous_fleet_generator::generator::generate_fleet_seeded(&library, &strategy, original.name, &manifest)
+    .expect("failed to generate fleet");
+  println!("regenerated fleet {:?} of {} ships with seed {}", fleet.name, fleet.ships.len(), manifest.seed);
+
+  write_fleet(&out_path, fleet);
+}
+
+fn import(mut args: impl Iterator<Item = OsString>) {
+  let library_path = PathBuf::from(args.next().expect(USAGE));
+  let saves_path = PathBuf::from(args.next().expect(USAGE));
+
+  let mut library = Library::load_or_new(&library_path).expect("failed to read library");
+  println!("importing saves from {}", saves_path.display());
+  let report = nebulous_fleet_generator::import::import_saves(&mut library, &saves_path);
+  for failure in report.failures.iter() {
+    println!("failed to import {}: {}", failure.path.display(), failure.error);
+  };
+
+  for (path, name, mismatch) in report.cost_mismatches.iter() {
+    println!("warning: missile {name:?} in {}: {mismatch}", path.display());
+  };
+
+  println!(
+    "read {} files: added {} ships ({} duplicates), added {} missiles ({} duplicates)",
+    report.files_read,
+    report.ships_added.len(), report.ships_merged,
+    report.missiles_added.len(), report.missiles_merged
+  );
+
+  library.save(&library_path).expect("failed to save library");
+  println!("successfully saved library revision {} to {}", library.revision, library_path.display());
+}
+
+fn list(mut args: impl Iterator<Item = OsString>) {
+  let library_path = PathBuf::from(args.next().expect(USAGE));
+  let filter = args.next().map(|filter| filter.into_string().expect("invalid filter"));
+  let value = filter.as_ref().map(|_| args.next().expect(USAGE).into_string().expect("invalid filter value"));
+
+  let library = load_library(&library_path);
+  println!("library revision {}", library.revision);
+  list_entries(&library.ships, filter.as_deref(), value.as_deref());
+  list_entries(&library.missiles, filter.as_deref(), value.as_deref());
+}
+
+fn list_entries<T: LibraryEntry>(entries: &LibraryEntries<T>, filter: Option<&str>, value: Option<&str>) {
+  let matches: Vec<(&str, &T)> = match (filter, value) {
+    (Some("name"), Some(name)) => entries.find_by_name(name).collect(),
+    (Some("author"), Some(author)) => entries.find_by_author(author).collect(),
+    (Some("tag"), Some(tag)) => entries.iter().filter(|(_, entry)| entry.has_tag(tag)).collect(),
+    (None, None) => entries.iter().collect(),
+    _ => panic!("{USAGE}")
+  };
+
+  for (id, entry) in matches {
+    let names = entry.names().collect::<Vec<&str>>().join(" / ");
+    match entry.author() {
+      Some(author) => println!("{} {id}: {names} (by {author})", T::KIND),
+      None => println!("{} {id}: {names}", T::KIND)
+    };
+  };
+}
+
+fn remove(mut args: impl Iterator<Item = OsString>) {
+  let library_path = PathBuf::from(args.next().expect(USAGE));
+  let kind = args.next().expect(USAGE).into_string().expect(USAGE);
+  let id = args.next().expect(USAGE).into_string().expect("invalid id");
+
+  let mut library = load_library(&library_path);
+  let removed = match kind.as_str() {
+    "ship" => library.ships.remove(&id).is_some(),
+    "missile" => library.missiles.remove(&id).is_some(),
+    _ => panic!("{USAGE}")
+  };
+
+  if !removed {
+    println!("no {kind} with id {id:?} in library");
+    return;
+  };
+
+  library.save(&library_path).expect("failed to save library");
+  println!("removed {kind} {id:?}, library is now at revision {}", library.revision);
+}
+
+fn validate(mut args: impl Iterator<Item = OsString>) {
+  let fleet_path = PathBuf::from(args.next().expect(USAGE));
+  let point_budget = args.next().map(|point_budget| {
+    point_budget.to_str().and_then(|point_budget| point_budget.parse::<usize>().ok())
+      .expect("invalid point budget")
+  });
+
+  let fleet = read_fleet(&fleet_path);
+  let diagnostics = fleet.validate(point_budget);
+  for diagnostic in diagnostics.iter() {
+    println!("{diagnostic}");
+  };
+
+  println!("found {} problems in fleet {:?}", diagnostics.len(), fleet.name);
+}
+
+fn repl(mut args: impl Iterator<Item = OsString>) {
+  let library_path = PathBuf::from(args.next().expect(USAGE));
+  let strategy_path = args.next().map(PathBuf::from);
+
+  let library = load_library(&library_path);
+  let strategy = strategy_path.map(|strategy_path| load_strategy(&strategy_path).0);
+  Repl::new(&library, strategy).run().expect("failed to read input");
+}
+
+fn load_strategy(strategy_path: &Path) -> (FleetStrategy, Vec<u8>) {
+  println!("reading strategy from {}", strategy_path.display());
+  let strategy_source = std::fs::read(strategy_path).expect("failed to read strategy");
+  let strategy = JSON.from_buffer(&strategy_source).expect("failed to parse strategy");
+  (strategy, strategy_source)
+}
+
+fn read_fleet(fleet_path: &Path) -> Fleet {
+  println!("reading fleet from {}", fleet_path.display());
+  Fleet::load(fleet_path).expect("failed to read fleet")
+}
+
+fn write_fleet(out_path: &Path, fleet: Fleet) {
+  println!("writing fleet to {}", out_path.display());
+  fleet.save(out_path).expect("failed to write fleet");
+  println!("successfully wrote fleet to {}", out_path.display());
+}
 
-fn main() {}
+fn load_library(library_path: &Path) -> Library {
+  println!("reading library from {}", library_path.display());
+  let library = Library::load(library_path).expect("failed to read library");
+  println!("successfully read {} ships and {} missiles", library.ships.len(), library.missiles.len());
+  library
+}
@@ -1,16 +1,18 @@
+pub mod constraint;
 pub mod predicate;
+pub mod weapon_group;
 
-use self::predicate::ShipPredicate;
+use self::constraint::FleetConstraint;
+use self::predicate::{MissilePredicate, ShipPredicate};
+use self::weapon_group::WeaponGroupRules;
 
 use nebulous_data::data::components::{ComponentKey, ComponentVariant, SigType};
-use nebulous_data::data::hulls::HullKey;
-use nebulous_data::data::hulls::config::Variant;
-use nebulous_data::data::missiles::{AuxiliaryKey, AvionicsKey, Maneuvers, WarheadKey};
-use nebulous_data::data::missiles::seekers::{SeekerKind, SeekerStrategy};
+use nebulous_data::data::missiles::{AuxiliaryKey, WarheadKey};
+use nebulous_data::data::missiles::seekers::SeekerStrategy;
 use nebulous_data::data::missiles::bodies::MissileBodyKey;
 use nebulous_data::data::munitions::{MunitionFamily, MunitionKey, WeaponRole};
-use nebulous_data::data::MissileSize;
-use nebulous_data::format::{ComponentData, Color, MunitionOrMissileKey, MissileTemplate, MissileSocket, Ship};
+use nebulous_data::data::{Faction, MissileSize};
+use nebulous_data::format::{Color, MunitionOrMissileKey, MissileTemplate, Ship};
 use nebulous_data::loadout::{
   AvionicsConfigured, MissileLoadout, MissileLoadoutError, MissileTemplateAdditional, MissileTemplateSummary,
   ShipAdditional, ShipLoadout, ShipLoadoutError
@@ -19,7 +21,8 @@ use nebulous_data::uuid::Builder as UuidBuilder;
 use rand::Rng;
 use rand::seq::SliceRandom;
 
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::iter::Extend;
 use std::str::FromStr;
 
@@ -39,11 +42,49 @@ pub enum ModelError {
 
 const fn default_one() -> usize { 1 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FleetStrategy {
-  pub selections: Vec<FleetStrategySelection>
+  pub faction: Faction,
+  pub selections: Vec<FleetStrategySelection>,
+  #[serde(default)]
+  pub missiles: Vec<MissileStrategySelection>,
+  /// Requirements on the composition of the whole fleet, which every generated fleet must satisfy.
+  #[serde(default)]
+  pub constraints: Vec<FleetConstraint>,
+  /// How the weapons on each generated ship are split into weapon groups.
+  #[serde(default)]
+  pub weapon_groups: WeaponGroupRules
+}
+
+impl FleetStrategy {
+  /// Whether or not a ship from the library may be used with this strategy at all.
+  pub fn is_usable(&self, ship_state: &ShipState) -> bool {
+    ship_state.loadout.hull_type.hull().faction == self.faction
+  }
+
+  /// Whether or not a missile from the library may be used with this strategy at all.
+  pub fn is_missile_usable(&self, missile_state: &MissileState) -> bool {
+    missile_state.loadout.body_key.missile_body().faction.is_none_or(|faction| faction == self.faction)
+  }
+
+  /// Returns true if adding the ship to the selected ships would break any constraint that they currently satisfy.
+  pub fn breaks_constraints(&self, selected: &[&ShipState], ship_state: &ShipState) -> bool {
+    if self.constraints.is_empty() { return false };
+    let mut with = selected.to_vec();
+    with.push(ship_state);
+    self.constraints.iter().any(|constraint| constraint.test(selected) && !constraint.test(&with))
+  }
+
+  /// Finds the missile selection for launchers of the given type, the first one listed if there are several.
+  pub fn missile_selection(&self, missile_type: MissileType) -> Option<&MissileStrategySelection> {
+    self.missiles.iter().find(|selection| selection.missile_type == missile_type)
+  }
 }
 
+/// A single entry in a fleet strategy.
+///
+/// Each time a ship is added to the fleet, one selection is picked at random according to its weight:
+/// `weight_initial` before the selection has contributed any ships, and `weight_additional` afterwards.
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct FleetStrategySelection {
   #[serde(default = "default_one")]
@@ -64,12 +105,62 @@ pub struct FleetStrategyPredicates {
   pub prioritize: Option<ShipPredicate>
 }
 
+impl FleetStrategyPredicates {
+  /// Returns true if the ship is not rejected and is matched by the `require` predicate, if one is present.
+  pub fn accepts(&self, ship_state: &ShipState) -> bool {
+    let rejected = self.reject.as_ref().is_some_and(|reject| reject.test(ship_state));
+    let required = self.require.as_ref().is_none_or(|require| require.test(ship_state));
+    !rejected && required
+  }
+
+  /// Returns true if the ship is matched by the `prioritize` predicate, if one is present.
+  pub fn prioritizes(&self, ship_state: &ShipState) -> bool {
+    self.prioritize.as_ref().is_some_and(|prioritize| prioritize.test(ship_state))
+  }
+}
+
+/// Rules for choosing the missiles that a type of launcher is filled with.
+///
+/// Every ship in the library has the missiles in its launchers and magazines of this type replaced by one missile from
+/// the library, picked at random from those matching the predicates. Ships whose launchers match no missile keep
+/// the missiles they were designed with.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MissileStrategySelection {
+  pub missile_type: MissileType,
+  #[serde(default)]
+  pub predicates: MissileStrategyPredicates
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct MissileStrategyPredicates {
+  #[serde(skip_serializing, default)]
+  pub reject: Option<MissilePredicate>,
+  #[serde(skip_serializing, default)]
+  pub require: Option<MissilePredicate>,
+  #[serde(skip_serializing, default)]
+  pub prioritize: Option<MissilePredicate>
+}
+
+impl MissileStrategyPredicates {
+  /// Returns true if the missile is not rejected and is matched by the `require` predicate, if one is present.
+  pub fn accepts(&self, missile_state: &MissileState) -> bool {
+    let rejected = self.reject.as_ref().is_some_and(|reject| reject.test(missile_state));
+    let required = self.require.as_ref().is_none_or(|require| require.test(missile_state));
+    !rejected && required
+  }
+
+  /// Returns true if the missile is matched by the `prioritize` predicate, if one is present.
+  pub fn prioritizes(&self, missile_state: &MissileState) -> bool {
+    self.prioritize.as_ref().is_some_and(|prioritize| prioritize.test(missile_state))
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct ShipState {
   #[serde(with = "crate::utils::serde_one_or_many")]
   pub name: Vec<String>,
   pub author: Option<String>,
-  pub tags: HashSet<String>,
+  pub tags: BTreeSet<String>,
   pub cost_budget_total: usize,
   pub cost_budget_spare: usize,
   pub equipment_summary: ShipEquipmentSummary,
@@ -104,7 +195,7 @@ impl ShipState {
     Ok(ShipState {
       name: vec![ship.name.clone()],
       author: None,
-      tags: HashSet::new(),
+      tags: BTreeSet::new(),
       cost_budget_total: costs.total(),
       cost_budget_spare: costs.missiles,
       equipment_summary,
@@ -112,19 +203,40 @@ impl ShipState {
     })
   }
 
-  pub fn to_ship<R: Rng + ?Sized>(&self, rng: &mut R) -> Ship {
-    self.loadout.to_ship(ShipAdditional {
+  /// Iterates over the names of every missile template referenced by this ship's magazines.
+  pub fn iter_missile_names(&self) -> impl Iterator<Item = &str> + '_ {
+    self.loadout.sockets.iter().flatten()
+      .filter_map(|socket| socket.variant.as_ref())
+      .filter_map(|variant| variant.get_magazine_contents())
+      .flat_map(|magazine_contents| magazine_contents.keys())
+      .filter_map(|munition_key| match munition_key {
+        MunitionOrMissileKey::MissileKey(missile_key) => Some(&**missile_key),
+        MunitionOrMissileKey::MunitionKey(..) => None
+      })
+  }
+
+  /// Converts this ship state into a ship, costing any missiles in its magazines against `missile_templates`
+  /// and grouping its weapons according to `weapon_group_rules`.
+  pub fn to_ship<R: Rng + ?Sized>(
+    &self,
+    missile_templates: &[MissileTemplate],
+    weapon_group_rules: &WeaponGroupRules,
+    rng: &mut R
+  ) -> Ship {
+    let mut ship = self.loadout.to_ship(ShipAdditional {
       key: UuidBuilder::from_random_bytes(rng.gen()).into_uuid(),
       name: self.name.choose(rng).cloned()
         .unwrap_or_else(|| "Ship".to_owned()),
-      // TODO: properly calculate cost
       cost: 0,
       callsign: None,
       number: rng.gen_range(0..10000),
-      weapon_groups: Vec::new(),
+      weapon_groups: weapon_group_rules.weapon_groups(&self.loadout),
       initial_formation: None,
       missile_types: Vec::new()
-    }, rng)
+    }, rng);
+
+    ship.cost = ship.calculate_costs(missile_templates).total();
+    ship
   }
 }
 
@@ -142,7 +254,7 @@ pub struct MissileState {
   pub designation: String,
   pub nickname: String,
   pub author: Option<String>,
-  pub tags: HashSet<String>,
+  pub tags: BTreeSet<String>,
   pub base_color: Color,
   pub stripe_color: Color,
   pub cost: usize,
@@ -158,7 +270,7 @@ impl MissileState {
       designation: missile_template.designation.clone(),
       nickname: missile_template.nickname.clone(),
       author: None,
-      tags: HashSet::new(),
+      tags: BTreeSet::new(),
       base_color: missile_template.base_color,
       stripe_color: missile_template.stripe_color,
       cost: missile_template.calculate_cost(),
@@ -169,18 +281,29 @@ impl MissileState {
     })
   }
 
+  /// The name that ships use to refer to this missile in their magazines.
+  pub fn template_name(&self) -> String {
+    format!("{} {}", self.designation, self.nickname)
+  }
+
+  pub const fn missile_type(&self) -> MissileType {
+    MissileType::from_missile_body_key(self.loadout.body_key)
+  }
+
   pub fn to_missile_template<R: Rng + ?Sized>(&self, rng: &mut R) -> MissileTemplate {
-    self.loadout.to_missile_template(MissileTemplateAdditional {
+    let mut missile_template = self.loadout.to_missile_template(MissileTemplateAdditional {
       designation: self.designation.clone(),
       nickname: self.nickname.clone(),
       description: String::new(),
       long_description: String::new(),
-      // TODO: properly calculate cost
       cost: 0,
       template_key: UuidBuilder::from_random_bytes(rng.gen()).into_uuid(),
       base_color: self.base_color,
       stripe_color: self.stripe_color
-    })
+    });
+
+    missile_template.cost = missile_template.calculate_cost();
+    missile_template
   }
 }
 
@@ -284,11 +407,11 @@ pub struct ShipEquipmentSummary {
   pub has_illuminator: bool,
   pub has_deception_module: bool,
   pub has_missile_identification: bool,
-  pub fire_control: HashSet<SigType>,
-  pub jamming: HashSet<SigType>,
-  pub sensors: HashSet<SigType>,
-  pub weapons: HashSet<WeaponFamily>,
-  pub missile_cells: HashMap<MissileType, usize>
+  pub fire_control: BTreeSet<SigType>,
+  pub jamming: BTreeSet<SigType>,
+  pub sensors: BTreeSet<SigType>,
+  pub weapons: BTreeSet<WeaponFamily>,
+  pub missile_cells: BTreeMap<MissileType, usize>
 }
 
 impl ShipEquipmentSummary {
@@ -408,6 +531,28 @@ impl FromIterator<ComponentKey> for ShipEquipmentSummary {
   }
 }
 
+/// Lists the equipment the same way it would be matched by an `equipment/` predicate,
+/// for example `weapon/ballistic/near, sensor/radar, illuminator`, followed by the number of missile cells of each type.
+impl fmt::Display for ShipEquipmentSummary {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let flags = [
+      (self.has_intelligence, "intelligence"),
+      (self.has_illuminator, "illuminator"),
+      (self.has_deception_module, "deception_module"),
+      (self.has_missile_identification, "missile_identification")
+    ];
+
+    let mut items = Vec::new();
+    items.extend(self.weapons.iter().map(|weapon_family| format!("weapon/{weapon_family}")));
+    items.extend(self.sensors.iter().map(|sig_type| format!("sensor/{sig_type}")));
+    items.extend(self.fire_control.iter().map(|sig_type| format!("fire_control/{sig_type}")));
+    items.extend(self.jamming.iter().map(|sig_type| format!("jammer/{sig_type}")));
+    items.extend(flags.into_iter().filter(|&(has, _)| has).map(|(_, name)| name.to_owned()));
+    items.extend(self.missile_cells.iter().map(|(missile_type, count)| format!("{count} {missile_type} cells")));
+    write!(f, "{}", crate::utils::FmtList(&items))
+  }
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -468,6 +613,23 @@ impl WeaponFamily {
   }
 }
 
+/// Written the same way as in [`predicate::WeaponFamilyPredicate`], for example `ballistic/near`.
+impl fmt::Display for WeaponFamily {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::EnergyBeam(distance_realm) => write!(f, "energy_beam/{distance_realm}"),
+      Self::EnergyPlasma(distance_realm) => write!(f, "energy_plasma/{distance_realm}"),
+      Self::EnergyRailgun(distance_realm) => write!(f, "energy_rail_gun/{distance_realm}"),
+      Self::Ballistic(distance_realm) => write!(f, "ballistic/{distance_realm}"),
+      Self::PointDefense(point_defense_type) => write!(f, "point_defense/{point_defense_type}"),
+      Self::StandardMissile(missile_size) => write!(f, "standard_missile/{}", *missile_size as u8),
+      Self::ContainerMissile => f.write_str("container_missile"),
+      Self::LoiteringMine => f.write_str("loitering_mine"),
+      Self::UnguidedRocket => f.write_str("unguided_rocket")
+    }
+  }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum MissileType {
@@ -489,6 +651,30 @@ impl MissileType {
       _ => None
     }
   }
+
+  pub const fn from_missile_body_key(missile_body_key: MissileBodyKey) -> Self {
+    match missile_body_key {
+      MissileBodyKey::SGM1Balestra => MissileType::StandardMissile(MissileSize::Size1),
+      MissileBodyKey::SGM2Tempest => MissileType::StandardMissile(MissileSize::Size2),
+      MissileBodyKey::SGMH2Cyclone => MissileType::StandardMissile(MissileSize::Size2),
+      MissileBodyKey::SGMH3Atlatl => MissileType::StandardMissile(MissileSize::Size3),
+      MissileBodyKey::SGT3Pilum => MissileType::StandardMissile(MissileSize::Size3),
+      MissileBodyKey::CM4Container => MissileType::ContainerMissile,
+      MissileBodyKey::CMS4Container => MissileType::ContainerMissile
+    }
+  }
+}
+
+/// Written the same way as in [`constraint::FleetConstraint`], for example `standard_missile/3`.
+impl fmt::Display for MissileType {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::StandardMissile(missile_size) => write!(f, "standard_missile/{}", *missile_size as u8),
+      Self::ContainerMissile => f.write_str("container_missile"),
+      Self::LoiteringMine => f.write_str("loitering_mine"),
+      Self::UnguidedRocket => f.write_str("unguided_rocket")
+    }
+  }
 }
 
 #[repr(u8)]
@@ -511,6 +697,20 @@ impl DistanceRealm {
       12000.. => Self::Far
     }
   }
+
+  pub const fn to_str(self) -> &'static str {
+    match self {
+      Self::Near => "near",
+      Self::Middle => "middle",
+      Self::Far => "far"
+    }
+  }
+}
+
+impl fmt::Display for DistanceRealm {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.to_str())
+  }
 }
 
 impl FromStr for DistanceRealm {
@@ -548,6 +748,23 @@ pub enum PointDefenseType {
   Railgun
 }
 
+impl PointDefenseType {
+  pub const fn to_str(self) -> &'static str {
+    match self {
+      Self::Beam => "beam",
+      Self::Defender => "defender",
+      Self::Flak => "flak",
+      Self::Railgun => "railgun"
+    }
+  }
+}
+
+impl fmt::Display for PointDefenseType {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.to_str())
+  }
+}
+
 impl FromStr for PointDefenseType {
   type Err = ParsePointDefenseTypeError;
 
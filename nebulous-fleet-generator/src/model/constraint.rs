@@ -0,0 +1,194 @@
+//! Constraints on the composition of a whole fleet, as opposed to the ship-by-ship predicates of its selections.
+//!
+//! A constraint measures a quantity summed over every ship in the fleet and compares it against a number or range,
+//! using the same syntax as numeric fields in predicates:
+//!
+//! ```text
+//! count(equipment/illuminator) >= 2
+//! count(hull_key/solomon) <= 1
+//! missile_cells/standard_missile/3 >= 40
+//! count(equipment/fire_control/radar) >= 1 per 3 ships
+//! ```
+//!
+//! With `per <n> ships`, the bounds are multiplied by the number of groups of `n` ships in the fleet, rounding up.
+
+use crate::model::{MissileType, ShipState};
+use crate::model::predicate::{NumberPredicate, ShipPredicate};
+use crate::utils::{keyword, keyword_match, keyword_parse, symbol, Parseable, Symbol, Token};
+
+use chumsky::prelude::*;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+
+
+
+#[derive(Debug, Clone)]
+pub struct FleetConstraint {
+  pub quantity: FleetQuantity,
+  pub bounds: NumberPredicate,
+  pub per_ships: Option<NonZeroUsize>,
+  /// The text this constraint was parsed from, used to describe it in diagnostics and to serialize it.
+  source: Box<str>
+}
+
+impl FleetConstraint {
+  /// The bounds that the quantity must be within for a fleet of the given number of ships.
+  pub fn bounds_for(&self, ship_count: usize) -> NumberPredicate {
+    match self.per_ships {
+      Some(per_ships) => self.bounds.scaled(ship_count.div_ceil(per_ships.get())),
+      None => self.bounds
+    }
+  }
+
+  pub fn measure<'a>(&self, ship_states: impl IntoIterator<Item = &'a ShipState>) -> usize {
+    ship_states.into_iter().map(|ship_state| self.quantity.measure(ship_state)).sum()
+  }
+
+  pub fn test(&self, ship_states: &[&ShipState]) -> bool {
+    self.bounds_for(ship_states.len()).contains(&self.measure(ship_states.iter().copied()))
+  }
+
+  /// Whether the fleet needs more of this constraint's quantity, treating an empty fleet as a single ship.
+  pub fn needs_more(&self, ship_states: &[&ShipState]) -> bool {
+    self.bounds_for(ship_states.len().max(1)).is_below(self.measure(ship_states.iter().copied()))
+  }
+
+  /// Whether the fleet has too much of this constraint's quantity.
+  pub fn has_too_many(&self, ship_states: &[&ShipState]) -> bool {
+    self.bounds_for(ship_states.len()).is_above(self.measure(ship_states.iter().copied()))
+  }
+}
+
+impl Parseable<Token> for FleetConstraint {
+  fn parser() -> impl Parser<Token, Self, Error = Simple<Token>> {
+    let per_ships = keyword("per")
+      .ignore_then(keyword_parse::<NonZeroUsize>().labelled("number"))
+      .then_ignore(keyword_match(|keyword| matches!(keyword, "ship" | "ships").then_some(())));
+
+    FleetQuantity::parser()
+      .then(NumberPredicate::parser())
+      .then(per_ships.or_not())
+      .map(|((quantity, bounds), per_ships)| FleetConstraint { quantity, bounds, per_ships, source: Box::default() })
+  }
+}
+
+impl FromStr for FleetConstraint {
+  type Err = crate::utils::Errors;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    crate::utils::run::<FleetConstraint>(s)
+      .map(|constraint| FleetConstraint { source: s.trim().into(), ..constraint })
+  }
+}
+
+impl<'de> Deserialize<'de> for FleetConstraint {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    String::deserialize(deserializer).and_then(|string| {
+      string.parse::<Self>().map_err(serde::de::Error::custom)
+    })
+  }
+}
+
+impl Serialize for FleetConstraint {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    self.source.serialize(serializer)
+  }
+}
+
+impl fmt::Display for FleetConstraint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.source)
+  }
+}
+
+/// A quantity that each ship contributes to, summed over the whole fleet.
+#[derive(Debug, Clone)]
+pub enum FleetQuantity {
+  /// The number of ships matching a predicate.
+  Count(ShipPredicate),
+  /// The number of missile cells of a type, see [`crate::model::ShipEquipmentSummary::missile_cells`].
+  MissileCells(MissileType)
+}
+
+impl FleetQuantity {
+  /// How much a single ship contributes to this quantity.
+  pub fn measure(&self, ship_state: &ShipState) -> usize {
+    match self {
+      Self::Count(predicate) => predicate.test(ship_state) as usize,
+      Self::MissileCells(missile_type) => {
+        ship_state.equipment_summary.missile_cells.get(missile_type).copied().unwrap_or(0)
+      }
+    }
+  }
+}
+
+impl Parseable<Token> for FleetQuantity {
+  fn parser() -> impl Parser<Token, Self, Error = Simple<Token>> {
+    choice((
+      keyword("count")
+        .ignore_then(crate::utils::delimited_by_round_brackets(ShipPredicate::parser()))
+        .map(Self::Count),
+      keyword("missile_cells").then(symbol(Symbol::Slash))
+        .ignore_then(MissileType::parser()).map(Self::MissileCells)
+    ))
+  }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::model::{FleetStrategy, ShipEquipmentSummary};
+  use crate::utils::JSON;
+  use nebulous_data::data::{Faction, MissileSize};
+  use nebulous_data::data::hulls::HullKey;
+  use singlefile::FileFormat;
+
+  fn equipped_ship_state(has_illuminator: bool, size3_cells: usize) -> ShipState {
+    let mut equipment_summary = ShipEquipmentSummary { has_illuminator, ..Default::default() };
+    equipment_summary.missile_cells.insert(MissileType::StandardMissile(MissileSize::Size3), size3_cells);
+    ShipState { equipment_summary, ..crate::test_utils::ship_state("Ship", HullKey::RainesFrigate, 0) }
+  }
+
+  #[test]
+  fn evaluate_constraints() {
+    let (a, b) = (equipped_ship_state(true, 0), equipped_ship_state(false, 16));
+
+    let constraint = "count(equipment/illuminator) >= 1 per 2 ships".parse::<FleetConstraint>().unwrap();
+    assert_eq!(constraint.to_string(), "count(equipment/illuminator) >= 1 per 2 ships");
+    assert!(constraint.needs_more(&[]));
+    assert!(constraint.test(&[&a, &b]));
+    assert!(!constraint.test(&[&a, &b, &b]));
+    assert!(constraint.needs_more(&[&a, &b, &b]));
+
+    let constraint = "missile_cells/standard_missile/3 /16..=32".parse::<FleetConstraint>().unwrap();
+    assert!(!constraint.test(&[&a]));
+    assert!(constraint.test(&[&a, &b, &b]));
+    assert!(constraint.has_too_many(&[&b, &b, &b]));
+
+    // Input left over after a complete constraint is an error, rather than being ignored
+    assert!("count(equipment/illuminator) >= 1 per 2 ships or more".parse::<FleetConstraint>().is_err());
+  }
+
+  #[test]
+  fn strategy_keeps_constraints() {
+    let strategy = FleetStrategy {
+      faction: Faction::Alliance,
+      selections: Vec::new(),
+      missiles: Vec::new(),
+      constraints: vec!["count(hull_key/solomon) <= 1".parse().unwrap(), " missile_cells/standard_missile/3 >= 40 ".parse().unwrap()],
+      weapon_groups: Default::default()
+    };
+
+    let mut buffer = Vec::new();
+    JSON.to_writer(&mut buffer, &strategy).unwrap();
+    let strategy: FleetStrategy = JSON.from_reader(buffer.as_slice()).unwrap();
+    let constraints = strategy.constraints.iter().map(FleetConstraint::to_string).collect::<Vec<String>>();
+    assert_eq!(constraints, ["count(hull_key/solomon) <= 1", "missile_cells/standard_missile/3 >= 40"]);
+  }
+}
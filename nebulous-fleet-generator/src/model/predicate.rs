@@ -1,5 +1,5 @@
-use crate::model::{DistanceRealm, ShipEquipmentSummary, MissileEquipmentSummary, PointDefenseType, ShipState, MissileState, WeaponFamily};
-use crate::utils::{keyword, keyword_parse, keyword_match, symbol, Parseable, Symbol, Token};
+use crate::model::{DistanceRealm, ShipEquipmentSummary, MissileEquipmentSummary, PointDefenseType, ShipState, MissileState, MissileType, WeaponFamily};
+use crate::utils::{ident, keyword, keyword_parse, keyword_match, symbol, Parseable, Symbol, Token};
 
 use chumsky::prelude::*;
 use nebulous_data::data::components::SigType;
@@ -11,7 +11,7 @@ use nebulous_data::data::MissileSize;
 use nebulous_data::loadout::AvionicsConfigured;
 use serde::de::{Deserialize, Deserializer};
 
-use std::ops::Range;
+use std::ops::{Bound, Range, RangeBounds, RangeInclusive};
 use std::str::FromStr;
 
 
@@ -23,8 +23,8 @@ pub enum ShipPredicate {
   Not(Box<Self>),
   Tag(String),
   HullKey(HullKey),
-  CostBudgetTotal(Range<usize>),
-  CostBudgetSpare(Range<usize>),
+  CostBudgetTotal(NumberPredicate),
+  CostBudgetSpare(NumberPredicate),
   Equipment(ShipEquipmentPredicate)
 }
 
@@ -43,45 +43,42 @@ impl ShipPredicate {
   }
 }
 
+impl BooleanPredicate for ShipPredicate {
+  fn any(predicates: Box<[Self]>) -> Self { Self::Any(predicates) }
+  fn all(predicates: Box<[Self]>) -> Self { Self::All(predicates) }
+  fn not(predicate: Box<Self>) -> Self { Self::Not(predicate) }
+}
+
 impl Parseable<Token> for ShipPredicate {
   fn parser() -> impl Parser<Token, Self, Error = Simple<Token>> {
-    recursive(|predicate| {
-      let predicate_list = crate::utils::delimited_round_bracket_list(predicate.clone(), 1).map(Vec::into_boxed_slice);
-      let predicate_single = crate::utils::delimited_by_round_brackets(predicate).map(Box::new);
-      let range = keyword_parse::<usize>()
-        .then_ignore(symbol(Symbol::Ellipsis))
-        .then(keyword_parse::<usize>())
-        .map(|(start, end)| Range { start, end });
-      let hull_key = keyword_match(|keyword| match keyword {
-        "sprinter" => Some(HullKey::SprinterCorvette),
-        "raines" => Some(HullKey::RainesFrigate),
-        "keystone" => Some(HullKey::KeystoneDestroyer),
-        "vauxhall" => Some(HullKey::VauxhallLightCruiser),
-        "axford" => Some(HullKey::AxfordHeavyCruiser),
-        "solomon" => Some(HullKey::SolomonBattleship),
-        "ferryman" | "shuttle" => Some(HullKey::FerrymanClipper),
-        "draugr" | "tugboat" => Some(HullKey::DraugrClipper),
-        "flathead" | "cargo_feeder" => Some(HullKey::FlatheadMonitor),
-        "ocello" => Some(HullKey::OcelloCommandCruiser),
-        "marauder" | "bulk_freighter" => Some(HullKey::MarauderLineShip),
-        "moorline" | "container_liner" => Some(HullKey::MoorlineLineShip),
-        _ => None
-      });
-
-      choice((
-        keyword("any").ignore_then(predicate_list.clone()).map(Self::Any),
-        keyword("all").ignore_then(predicate_list.clone()).map(Self::All),
-        keyword("not").ignore_then(predicate_single).map(Self::Not),
-        keyword("hull_key").then(symbol(Symbol::Slash))
-          .ignore_then(hull_key).map(Self::HullKey),
-        keyword("cost_budget_total").then(symbol(Symbol::Slash))
-          .ignore_then(range.clone()).map(Self::CostBudgetSpare),
-        keyword("cost_budget_spare").then(symbol(Symbol::Slash))
-          .ignore_then(range.clone()).map(Self::CostBudgetTotal),
-        keyword("equipment").then(symbol(Symbol::Slash))
-          .ignore_then(ShipEquipmentPredicate::parser()).map(Self::Equipment),
-      ))
-    })
+    let hull_key = keyword_match(|keyword| match keyword {
+      "sprinter" => Some(HullKey::SprinterCorvette),
+      "raines" => Some(HullKey::RainesFrigate),
+      "keystone" => Some(HullKey::KeystoneDestroyer),
+      "vauxhall" => Some(HullKey::VauxhallLightCruiser),
+      "axford" => Some(HullKey::AxfordHeavyCruiser),
+      "solomon" => Some(HullKey::SolomonBattleship),
+      "ferryman" | "shuttle" => Some(HullKey::FerrymanClipper),
+      "draugr" | "tugboat" => Some(HullKey::DraugrClipper),
+      "flathead" | "cargo_feeder" => Some(HullKey::FlatheadMonitor),
+      "ocello" => Some(HullKey::OcelloCommandCruiser),
+      "marauder" | "bulk_freighter" => Some(HullKey::MarauderLineShip),
+      "moorline" | "container_liner" => Some(HullKey::MoorlineLineShip),
+      _ => None
+    }).labelled("hull key");
+
+    boolean_predicate(choice((
+      keyword("hull_key").then(symbol(Symbol::Slash))
+        .ignore_then(hull_key).map(Self::HullKey),
+      keyword("tag").then(symbol(Symbol::Slash))
+        .ignore_then(ident()).map(|tag| Self::Tag(tag.into_string())),
+      keyword("cost_budget_total")
+        .ignore_then(NumberPredicate::parser()).map(Self::CostBudgetTotal),
+      keyword("cost_budget_spare")
+        .ignore_then(NumberPredicate::parser()).map(Self::CostBudgetSpare),
+      keyword("equipment").then(symbol(Symbol::Slash))
+        .ignore_then(ShipEquipmentPredicate::parser()).map(Self::Equipment),
+    )))
   }
 }
 
@@ -120,9 +117,9 @@ impl ShipEquipmentPredicate {
       Self::Illuminator => equipment_summary.has_illuminator,
       Self::DeceptionModule => equipment_summary.has_deception_module,
       Self::MissileIdentification => equipment_summary.has_missile_identification,
-      Self::FireControl(sig_type) => sig_type.map_or(true, |sig_type| equipment_summary.fire_control.contains(&sig_type)),
-      Self::Sensor(sig_type) => sig_type.map_or(true, |sig_type| equipment_summary.sensors.contains(&sig_type)),
-      Self::Jammer(sig_type) => sig_type.map_or(true, |sig_type| equipment_summary.jamming.contains(&sig_type)),
+      Self::FireControl(sig_type) => sig_type.is_none_or(|sig_type| equipment_summary.fire_control.contains(&sig_type)),
+      Self::Sensor(sig_type) => sig_type.is_none_or(|sig_type| equipment_summary.sensors.contains(&sig_type)),
+      Self::Jammer(sig_type) => sig_type.is_none_or(|sig_type| equipment_summary.jamming.contains(&sig_type)),
       Self::Weapon(weapon_family_predicate) => equipment_summary.weapons.iter().any(|weapon_family| {
         weapon_family_predicate.test(weapon_family)
       })
@@ -169,13 +166,13 @@ impl WeaponFamilyPredicate {
       (Self::EnergyPlasma(distance_realm_predicate), WeaponFamily::EnergyPlasma(distance_realm)) |
       (Self::EnergyRailgun(distance_realm_predicate), WeaponFamily::EnergyRailgun(distance_realm)) |
       (Self::Ballistic(distance_realm_predicate), WeaponFamily::Ballistic(distance_realm)) => {
-        distance_realm_predicate.as_ref().map_or(true, |p| p.contains(distance_realm))
+        distance_realm_predicate.as_ref().is_none_or(|p| p.contains(distance_realm))
       },
       (Self::PointDefense(point_defense_type_predicate), WeaponFamily::PointDefense(point_defense_type)) => {
-        point_defense_type_predicate.as_ref().map_or(true, |p| p == point_defense_type)
+        point_defense_type_predicate.as_ref().is_none_or(|p| p == point_defense_type)
       },
       (Self::StandardMissile(missile_size_predicate), WeaponFamily::StandardMissile(missile_size)) => {
-        missile_size_predicate.as_ref().map_or(true, |p| p == missile_size)
+        missile_size_predicate.as_ref().is_none_or(|p| p == missile_size)
       },
       (Self::ContainerMissile, WeaponFamily::ContainerMissile) => true,
       (Self::LoiteringMine, WeaponFamily::LoiteringMine) => true,
@@ -215,7 +212,7 @@ pub enum MissilePredicate {
   Not(Box<Self>),
   Tag(String),
   MissileBodyKey(MissileBodyKey),
-  Cost(Range<usize>),
+  Cost(NumberPredicate),
   Equipment(MissileEquipmentPredicate)
 }
 
@@ -233,6 +230,54 @@ impl MissilePredicate {
   }
 }
 
+impl BooleanPredicate for MissilePredicate {
+  fn any(predicates: Box<[Self]>) -> Self { Self::Any(predicates) }
+  fn all(predicates: Box<[Self]>) -> Self { Self::All(predicates) }
+  fn not(predicate: Box<Self>) -> Self { Self::Not(predicate) }
+}
+
+impl Parseable<Token> for MissilePredicate {
+  fn parser() -> impl Parser<Token, Self, Error = Simple<Token>> {
+    let missile_body_key = keyword_match(|keyword| match keyword {
+      "sgm1" | "balestra" => Some(MissileBodyKey::SGM1Balestra),
+      "sgm2" | "tempest" => Some(MissileBodyKey::SGM2Tempest),
+      "sgmh2" | "cyclone" => Some(MissileBodyKey::SGMH2Cyclone),
+      "sgmh3" | "atlatl" => Some(MissileBodyKey::SGMH3Atlatl),
+      "sgt3" | "pilum" => Some(MissileBodyKey::SGT3Pilum),
+      "cm4" => Some(MissileBodyKey::CM4Container),
+      "cms4" => Some(MissileBodyKey::CMS4Container),
+      _ => None
+    }).labelled("missile body key");
+
+    boolean_predicate(choice((
+      keyword("body_key").then(symbol(Symbol::Slash))
+        .ignore_then(missile_body_key).map(Self::MissileBodyKey),
+      keyword("tag").then(symbol(Symbol::Slash))
+        .ignore_then(ident()).map(|tag| Self::Tag(tag.into_string())),
+      keyword("cost")
+        .ignore_then(NumberPredicate::parser()).map(Self::Cost),
+      keyword("equipment").then(symbol(Symbol::Slash))
+        .ignore_then(MissileEquipmentPredicate::parser()).map(Self::Equipment)
+    )))
+  }
+}
+
+impl FromStr for MissilePredicate {
+  type Err = crate::utils::Errors;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    crate::utils::run::<MissilePredicate>(s)
+  }
+}
+
+impl<'de> Deserialize<'de> for MissilePredicate {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    String::deserialize(deserializer).and_then(|string| {
+      string.parse::<Self>().map_err(serde::de::Error::custom)
+    })
+  }
+}
+
 #[derive(Debug, Clone)]
 pub enum MissileEquipmentPredicate {
   Seeker(SeekerKind, SeekerMode),
@@ -254,7 +299,7 @@ impl MissileEquipmentPredicate {
       },
       Self::Avionics(avionics_key, avionics_predicate) => {
         equipment_summary.avionics.into_avionics_key() == *avionics_key &&
-        avionics_predicate.as_ref().map_or(true, |avionics_predicate| {
+        avionics_predicate.as_ref().is_none_or(|avionics_predicate| {
           avionics_predicate.test(equipment_summary.avionics)
         })
       },
@@ -270,22 +315,25 @@ impl Parseable<Token> for MissileEquipmentPredicate {
     let predicate_seeker_mode = symbol(Symbol::Comma)
       .ignore_then(SeekerMode::parser()).or_not()
       .map(|seeker_mode| seeker_mode.unwrap_or(SeekerMode::Targeting));
-    let predicate_seeker = crate::utils::delimited_by_round_brackets(SeekerKind::parser().then(predicate_seeker_mode))
-      .map(|(seeker_kind, seeker_mode)| Self::Seeker(seeker_kind, seeker_mode));
-
-    let predicate_avionics = AvionicsKey::parser();
-
+    let predicate_seeker = choice((
+      crate::utils::delimited_by_round_brackets(SeekerKind::parser().then(predicate_seeker_mode)).boxed(),
+      SeekerKind::parser().map(|seeker_kind| (seeker_kind, SeekerMode::Targeting)).boxed()
+    ));
 
+    let predicate_avionics = AvionicsKey::parser()
+      .then(crate::utils::delimited_by_round_brackets(AvionicsPredicate::parser()).or_not());
 
     choice((
       keyword("seeker").then(symbol(Symbol::Slash))
-        .ignore_then().map(),
+        .ignore_then(predicate_seeker)
+        .map(|(seeker_kind, seeker_mode)| Self::Seeker(seeker_kind, seeker_mode)),
       keyword("auxiliary").then(symbol(Symbol::Slash))
-        .ignore_then().map(),
+        .ignore_then(AuxiliaryKey::parser()).map(Self::Auxiliary),
       keyword("avionics").then(symbol(Symbol::Slash))
-        .ignore_then().map(),
+        .ignore_then(predicate_avionics)
+        .map(|(avionics_key, avionics_predicate)| Self::Avionics(avionics_key, avionics_predicate)),
       keyword("warhead").then(symbol(Symbol::Slash))
-        .ignore_then().map(),
+        .ignore_then(WarheadKey::parser()).map(Self::Warhead)
     ))
   }
 }
@@ -315,23 +363,145 @@ impl AvionicsPredicate {
   }
 }
 
+impl BooleanPredicate for AvionicsPredicate {
+  fn any(predicates: Box<[Self]>) -> Self { Self::Any(predicates) }
+  fn all(predicates: Box<[Self]>) -> Self { Self::All(predicates) }
+  fn not(predicate: Box<Self>) -> Self { Self::Not(predicate) }
+}
+
 impl Parseable<Token> for AvionicsPredicate {
   fn parser() -> impl Parser<Token, Self, Error = Simple<Token>> {
-    recursive(|predicate| {
-      let predicate_list = crate::utils::delimited_round_bracket_list(predicate.clone(), 1).map(Vec::into_boxed_slice);
-      let predicate_single = crate::utils::delimited_by_round_brackets(predicate).map(Box::new);
-
-      choice((
-        keyword("any").ignore_then(predicate_list.clone()).map(Self::Any),
-        keyword("all").ignore_then(predicate_list.clone()).map(Self::All),
-        keyword("not").ignore_then(predicate_single).map(Self::Not),
-        keyword("hot_launch").to(Self::HotLaunch),
-        keyword("self_destruct_on_lost").to(Self::SelfDestructOnLost),
-        keyword("maneuvers").then(symbol(Symbol::Slash))
-          .ignore_then(Maneuvers::parser()).map(Self::Maneuvers),
-        keyword("defensive_doctrine").to(Self::DefensiveDoctrine)
-      ))
-    })
+    boolean_predicate(choice((
+      keyword("hot_launch").to(Self::HotLaunch),
+      keyword("self_destruct_on_lost").to(Self::SelfDestructOnLost),
+      keyword("maneuvers").then(symbol(Symbol::Slash))
+        .ignore_then(Maneuvers::parser()).map(Self::Maneuvers),
+      keyword("defensive_doctrine").to(Self::DefensiveDoctrine)
+    )))
+  }
+}
+
+/// A predicate made up of other predicates of the same kind.
+trait BooleanPredicate: Sized {
+  fn any(predicates: Box<[Self]>) -> Self;
+  fn all(predicates: Box<[Self]>) -> Self;
+  fn not(predicate: Box<Self>) -> Self;
+}
+
+/// Combines `atom`s with the boolean operators shared by every predicate language.
+///
+/// From tightest to loosest binding these are `!x`, `x and y` and `x or y`, and parentheses may be used for grouping.
+/// The prefix forms `any(x, y)`, `all(x, y)` and `not(x)` are also accepted.
+fn boolean_predicate<T, P>(atom: P) -> impl Parser<Token, T, Error = Simple<Token>>
+where T: BooleanPredicate + 'static, P: Parser<Token, T, Error = Simple<Token>> + 'static {
+  recursive(|predicate| {
+    let predicate_list = crate::utils::delimited_round_bracket_list(predicate.clone(), 1).map(Vec::into_boxed_slice);
+    let predicate_single = crate::utils::delimited_by_round_brackets(predicate.clone()).map(Box::new);
+
+    let primary = choice((
+      keyword("any").ignore_then(predicate_list.clone()).map(T::any),
+      keyword("all").ignore_then(predicate_list.clone()).map(T::all),
+      keyword("not").ignore_then(predicate_single).map(T::not),
+      crate::utils::delimited_by_round_brackets(predicate),
+      atom
+    ));
+
+    let negation = symbol(Symbol::Exclamation).repeated()
+      .then(primary)
+      .foldr(|(), predicate| T::not(Box::new(predicate)));
+    let conjunction = negation
+      .separated_by(keyword("and")).at_least(1)
+      .map(|predicates| flatten(predicates, T::all));
+    conjunction
+      .separated_by(keyword("or")).at_least(1)
+      .map(|predicates| flatten(predicates, T::any))
+  })
+}
+
+fn flatten<T>(mut predicates: Vec<T>, f: impl FnOnce(Box<[T]>) -> T) -> T {
+  match predicates.len() {
+    1 => predicates.pop().unwrap(),
+    _ => f(predicates.into_boxed_slice())
+  }
+}
+
+/// A predicate on a numeric field, written after the field's name as either a range
+/// (`/100..300`, `/100..=300`) or a comparison (`< 300`, `<= 300`, `> 100`, `>= 100`, `== 250`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NumberPredicate {
+  pub start: Bound<usize>,
+  pub end: Bound<usize>
+}
+
+impl NumberPredicate {
+  pub fn contains(&self, value: &usize) -> bool {
+    RangeBounds::contains(&(self.start, self.end), value)
+  }
+
+  /// Whether the value is below the start of this predicate's range.
+  pub fn is_below(&self, value: usize) -> bool {
+    match self.start {
+      Bound::Included(start) => value < start,
+      Bound::Excluded(start) => value <= start,
+      Bound::Unbounded => false
+    }
+  }
+
+  /// Whether the value is above the end of this predicate's range.
+  pub fn is_above(&self, value: usize) -> bool {
+    match self.end {
+      Bound::Included(end) => value > end,
+      Bound::Excluded(end) => value >= end,
+      Bound::Unbounded => false
+    }
+  }
+
+  /// Multiplies both ends of this predicate's range by `factor`.
+  pub fn scaled(self, factor: usize) -> Self {
+    let scale = |bound: Bound<usize>| bound.map(|value| value * factor);
+    NumberPredicate { start: scale(self.start), end: scale(self.end) }
+  }
+}
+
+impl From<Range<usize>> for NumberPredicate {
+  fn from(range: Range<usize>) -> Self {
+    NumberPredicate { start: Bound::Included(range.start), end: Bound::Excluded(range.end) }
+  }
+}
+
+impl From<RangeInclusive<usize>> for NumberPredicate {
+  fn from(range: RangeInclusive<usize>) -> Self {
+    let (start, end) = range.into_inner();
+    NumberPredicate { start: Bound::Included(start), end: Bound::Included(end) }
+  }
+}
+
+impl Parseable<Token> for NumberPredicate {
+  fn parser() -> impl Parser<Token, Self, Error = Simple<Token>> {
+    let number = || keyword_parse::<usize>().labelled("number");
+    let range = symbol(Symbol::Slash)
+      .ignore_then(number())
+      .then(choice((
+        symbol(Symbol::Ellipsis).to(false),
+        symbol(Symbol::EllipsisInclusive).to(true)
+      )))
+      .then(number())
+      .map(|((start, inclusive), end)| NumberPredicate {
+        start: Bound::Included(start),
+        end: if inclusive { Bound::Included(end) } else { Bound::Excluded(end) }
+      });
+    let comparison = |operator: Symbol, f: fn(usize) -> NumberPredicate| {
+      symbol(operator).ignore_then(number()).map(f)
+    };
+    let comparison = choice((
+      comparison(Symbol::LessThan, |value| NumberPredicate { start: Bound::Unbounded, end: Bound::Excluded(value) }),
+      comparison(Symbol::LessThanOrEqual, |value| NumberPredicate { start: Bound::Unbounded, end: Bound::Included(value) }),
+      comparison(Symbol::GreaterThan, |value| NumberPredicate { start: Bound::Excluded(value), end: Bound::Unbounded }),
+      comparison(Symbol::GreaterThanOrEqual, |value| NumberPredicate { start: Bound::Included(value), end: Bound::Unbounded }),
+      comparison(Symbol::Equal, |value| NumberPredicate { start: Bound::Included(value), end: Bound::Included(value) })
+    ));
+
+    range.or(comparison)
   }
 }
 
@@ -368,6 +538,18 @@ impl<T: Parseable<Token> + 'static> Parseable<Token> for MultiPredicate<T> {
   }
 }
 
+impl Parseable<Token> for MissileType {
+  fn parser() -> impl Parser<Token, Self, Error = Simple<Token>> {
+    choice((
+      keyword("standard_missile").then(symbol(Symbol::Slash))
+        .ignore_then(keyword_parse::<MissileSize>()).map(Self::StandardMissile),
+      keyword("container_missile").to(Self::ContainerMissile),
+      keyword("loitering_mine").to(Self::LoiteringMine),
+      keyword("unguided_rocket").to(Self::UnguidedRocket)
+    ))
+  }
+}
+
 impl Parseable<Token> for DistanceRealm {
   fn parser() -> impl Parser<Token, Self, Error = Simple<Token>> {
     keyword_parse::<DistanceRealm>()
@@ -453,3 +635,101 @@ impl Parseable<Token> for Maneuvers {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_ship_predicates() {
+    let predicate = "all(tag/line, cost_budget_total/500..1000, not(cost_budget_spare/0..1))".parse::<ShipPredicate>().unwrap();
+    let ShipPredicate::All(predicates) = predicate else { panic!("expected all") };
+    assert!(matches!(&predicates[0], ShipPredicate::Tag(tag) if tag == "line"));
+    assert!(matches!(&predicates[1], ShipPredicate::CostBudgetTotal(range) if *range == NumberPredicate::from(500..1000)));
+    assert!(matches!(&predicates[2], ShipPredicate::Not(predicate) if matches!(**predicate, ShipPredicate::CostBudgetSpare(..))));
+  }
+
+  #[test]
+  fn parse_ship_predicate_operators() {
+    let predicate = "tag/line and !tag/carrier or (hull_key/solomon and cost_budget_total >= 800)".parse::<ShipPredicate>().unwrap();
+    let ShipPredicate::Any(predicates) = predicate else { panic!("expected or") };
+    let ShipPredicate::All(left) = &predicates[0] else { panic!("expected and") };
+    assert!(matches!(&left[0], ShipPredicate::Tag(tag) if tag == "line"));
+    assert!(matches!(&left[1], ShipPredicate::Not(predicate) if matches!(&**predicate, ShipPredicate::Tag(tag) if tag == "carrier")));
+    let ShipPredicate::All(right) = &predicates[1] else { panic!("expected and") };
+    assert!(matches!(&right[0], ShipPredicate::HullKey(HullKey::SolomonBattleship)));
+    assert!(matches!(&right[1], ShipPredicate::CostBudgetTotal(NumberPredicate { start: Bound::Included(800), end: Bound::Unbounded })));
+
+    let predicate = "!!(tag/a or tag/b) and tag/c".parse::<ShipPredicate>().unwrap();
+    let ShipPredicate::All(predicates) = predicate else { panic!("expected and") };
+    assert!(matches!(&predicates[0], ShipPredicate::Not(predicate) if matches!(&**predicate, ShipPredicate::Not(..))));
+  }
+
+  #[test]
+  fn number_predicates() {
+    let parse = |s: &str| match s.parse::<ShipPredicate>().unwrap() {
+      ShipPredicate::CostBudgetSpare(number_predicate) => number_predicate,
+      _ => panic!("expected cost_budget_spare")
+    };
+
+    let cases: [(&str, &[usize], &[usize]); 7] = [
+      ("cost_budget_spare/10..20", &[10, 19], &[9, 20]),
+      ("cost_budget_spare/10..=20", &[10, 20], &[9, 21]),
+      ("cost_budget_spare < 20", &[0, 19], &[20]),
+      ("cost_budget_spare <= 20", &[0, 20], &[21]),
+      ("cost_budget_spare > 20", &[21], &[0, 20]),
+      ("cost_budget_spare >= 20", &[20, 21], &[19]),
+      ("cost_budget_spare == 20", &[20], &[19, 21])
+    ];
+
+    for (source, included, excluded) in cases {
+      let number_predicate = parse(source);
+      assert!(included.iter().all(|value| number_predicate.contains(value)), "{source}");
+      assert!(!excluded.iter().any(|value| number_predicate.contains(value)), "{source}");
+    };
+  }
+
+  #[test]
+  fn parse_missile_predicates() {
+    let predicate = "body_key/pilum and cost <= 40 and !equipment/warhead/blast_fragmentation".parse::<MissilePredicate>().unwrap();
+    let MissilePredicate::All(predicates) = predicate else { panic!("expected and") };
+    assert!(matches!(&predicates[0], MissilePredicate::MissileBodyKey(MissileBodyKey::SGT3Pilum)));
+    assert!(matches!(&predicates[1], MissilePredicate::Cost(NumberPredicate { start: Bound::Unbounded, end: Bound::Included(40) })));
+    assert!(matches!(&predicates[2], MissilePredicate::Not(predicate) if matches!(**predicate, MissilePredicate::Equipment(MissileEquipmentPredicate::Warhead(WarheadKey::BlastFragmentation)))));
+  }
+
+  #[test]
+  fn render_parse_errors() {
+    let errors = "all(tag/line, hull_key/frigate)".parse::<ShipPredicate>().unwrap_err();
+    assert_eq!(errors.to_string(), "\
+error: unexpected `frigate`, expected hull key
+ --> 1:24
+  |
+1 | all(tag/line, hull_key/frigate)
+  |                        ^^^^^^^");
+
+    // Spans are byte offsets, so text after non-ascii characters is still sliced on char boundaries
+    let errors = "tag/é or hull_key/frigate".parse::<ShipPredicate>().unwrap_err();
+    assert!(errors.to_string().starts_with("error: unexpected `é`, expected one of"));
+    assert!(errors.to_string().ends_with("\
+ --> 1:5
+  |
+1 | tag/é or hull_key/frigate
+  |     ^"));
+
+    // Input left over after a complete predicate is an error, rather than being ignored
+    assert!("tag/line or (tag/a and hull_key/frigate)".parse::<ShipPredicate>().is_err());
+    assert!("tag/line tag/a".parse::<ShipPredicate>().is_err());
+    assert!("tag/a AND tag/b".parse::<ShipPredicate>().is_err());
+  }
+
+  #[test]
+  fn parse_missile_equipment_predicates() {
+    let parse = |s: &str| crate::utils::run::<MissileEquipmentPredicate>(s).unwrap();
+    assert!(matches!(parse("seeker/active_radar"), MissileEquipmentPredicate::Seeker(SeekerKind::ActiveRadar, SeekerMode::Targeting)));
+    assert!(matches!(parse("seeker/(wake_homing, val)"), MissileEquipmentPredicate::Seeker(SeekerKind::WakeHoming, SeekerMode::Validation)));
+    assert!(matches!(parse("avionics/cruise_guidance(hot_launch)"), MissileEquipmentPredicate::Avionics(AvionicsKey::CruiseGuidance, Some(AvionicsPredicate::HotLaunch))));
+    assert!(matches!(parse("warhead/he_impact"), MissileEquipmentPredicate::Warhead(WarheadKey::HEImpact)));
+    assert!(matches!(parse("avionics/direct_guidance(hot_launch and !self_destruct_on_lost)"), MissileEquipmentPredicate::Avionics(AvionicsKey::DirectGuidance, Some(AvionicsPredicate::All(..)))));
+  }
+}
@@ -0,0 +1,224 @@
+//! Weapon groups for generated ships, built from the weapons installed in their sockets.
+//!
+//! Every weapon is put in a group for its role: offensive weapons, point defense, or missiles of one type.
+//! Depending on the strategy's [`WeaponGroupRules`], these are split further by the family of munitions that
+//! the weapons fire, and by whether they are fixed or turreted.
+
+use crate::model::{MissileType, WeaponUsage};
+
+use nebulous_data::data::components::ComponentVariant;
+use nebulous_data::data::munitions::MunitionFamily;
+use nebulous_data::data::MissileSize;
+use nebulous_data::format::WeaponGroup;
+use nebulous_data::format::key::Key;
+use nebulous_data::loadout::ShipLoadout;
+use nebulous_data::xml::Extra;
+
+use std::collections::BTreeMap;
+
+
+
+/// How the weapons on each generated ship are split into weapon groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WeaponGroupRules {
+  /// Whether generated ships are given weapon groups at all.
+  pub enabled: bool,
+  /// Whether weapons that fire different families of munitions, such as 100mm and 120mm guns, are grouped separately.
+  pub by_munition_family: bool,
+  /// Whether fixed weapons are grouped separately from turreted ones.
+  pub by_mounting: bool,
+  /// Whether point defense weapons are grouped, rather than being left to fire on their own.
+  pub point_defense: bool
+}
+
+impl WeaponGroupRules {
+  /// Builds the weapon groups for a ship, with members in the order of the hull's sockets.
+  pub fn weapon_groups(&self, loadout: &ShipLoadout) -> Vec<WeaponGroup> {
+    if !self.enabled { return Vec::new() };
+
+    let hull = loadout.hull_type.hull();
+    let mut groups = BTreeMap::<WeaponGroupKey, Vec<Key>>::new();
+    for (socket, hull_socket) in loadout.sockets.iter().zip(hull.sockets.iter()) {
+      let Some(socket) = socket else { continue };
+      let Some(key) = WeaponGroupKey::from_component_variant(socket.component_key.component().variant) else { continue };
+      if key.role == WeaponGroupRole::PointDefense && !self.point_defense { continue };
+
+      let key = WeaponGroupKey {
+        munition_family: key.munition_family.filter(|_| self.by_munition_family),
+        is_fixed: key.is_fixed.filter(|_| self.by_mounting),
+        ..key
+      };
+
+      groups.entry(key).or_default().push(hull_socket.save_key);
+    };
+
+    groups.iter().map(|(key, members)| {
+      // Fixed and turreted weapons are only told apart by name when both kinds would otherwise share one
+      let name = key.base_name();
+      let name = match key.is_fixed {
+        Some(is_fixed) if groups.keys().any(|other| other != key && other.base_name() == name) => {
+          format!("{name} ({})", if is_fixed { "Fixed" } else { "Turreted" })
+        },
+        Some(..) | None => name
+      };
+
+      WeaponGroup { name, members: members.clone(), extra: Extra::new() }
+    }).collect()
+  }
+}
+
+impl Default for WeaponGroupRules {
+  fn default() -> Self {
+    WeaponGroupRules {
+      enabled: true,
+      by_munition_family: true,
+      by_mounting: true,
+      point_defense: true
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum WeaponGroupRole {
+  Offensive,
+  PointDefense,
+  Missile(MissileType)
+}
+
+/// Weapons with the same key are put in the same group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct WeaponGroupKey {
+  role: WeaponGroupRole,
+  /// Beams fire no munitions, so they are kept apart from guns even when munition families are not.
+  is_beam: bool,
+  /// `None` for beams, and for missiles, which are already grouped by type.
+  munition_family: Option<MunitionFamily>,
+  /// `None` for weapons that are not mounted either way, like missile launchers that are fed from magazines.
+  is_fixed: Option<bool>
+}
+
+impl WeaponGroupKey {
+  fn from_component_variant(variant: Option<ComponentVariant>) -> Option<Self> {
+    let (role, munition_family, is_fixed) = match variant? {
+      ComponentVariant::WeaponBeam { is_fixed, role, .. } => (role, None, Some(is_fixed)),
+      ComponentVariant::WeaponProjectile { is_fixed, role, munition_family, .. } => (role, munition_family, Some(is_fixed)),
+      ComponentVariant::WeaponMissileLauncher { role, munition_family, .. } => (role, Some(munition_family), None),
+      ComponentVariant::WeaponMissileBank { is_fixed, role, munition_family, .. } => (role, Some(munition_family), Some(is_fixed)),
+      _ => return None
+    };
+
+    let is_beam = matches!(variant, Some(ComponentVariant::WeaponBeam { .. }));
+    let missile_type = munition_family.and_then(MissileType::from_munition_family);
+    let role = match (WeaponUsage::from_weapon_role(role)?, missile_type) {
+      (WeaponUsage::Defensive, _) => WeaponGroupRole::PointDefense,
+      (WeaponUsage::Offensive, Some(missile_type)) => WeaponGroupRole::Missile(missile_type),
+      (WeaponUsage::Offensive, None) => WeaponGroupRole::Offensive
+    };
+
+    let munition_family = munition_family.filter(|_| !matches!(role, WeaponGroupRole::Missile(..)));
+    Some(WeaponGroupKey { role, is_beam, munition_family, is_fixed })
+  }
+
+  fn base_name(&self) -> String {
+    match (self.role, self.munition_family) {
+      (WeaponGroupRole::Offensive, _) if self.is_beam => "Beams".to_owned(),
+      (WeaponGroupRole::Offensive, Some(munition_family)) => munition_family_name(munition_family).to_owned(),
+      (WeaponGroupRole::Offensive, None) => "Guns".to_owned(),
+      (WeaponGroupRole::PointDefense, _) if self.is_beam => "PD Beams".to_owned(),
+      (WeaponGroupRole::PointDefense, Some(munition_family)) => format!("PD {}", munition_family_name(munition_family)),
+      (WeaponGroupRole::PointDefense, None) => "PD Guns".to_owned(),
+      (WeaponGroupRole::Missile(missile_type), _) => missile_type_name(missile_type).to_owned()
+    }
+  }
+}
+
+fn munition_family_name(munition_family: MunitionFamily) -> &'static str {
+  match munition_family {
+    MunitionFamily::BallisticMagnetic15mm => "15mm",
+    MunitionFamily::BallisticChemical20mm => "20mm",
+    MunitionFamily::BallisticChemical50mmFlak => "50mm Flak",
+    MunitionFamily::BallisticChemical100mm => "100mm",
+    MunitionFamily::BallisticChemical120mm => "120mm",
+    MunitionFamily::BallisticChemical250mm => "250mm",
+    MunitionFamily::BallisticMagnetic300mmRailgun => "300mm Railguns",
+    MunitionFamily::BallisticMagnetic400mmPlasma => "400mm Plasma",
+    MunitionFamily::BallisticChemical450mm => "450mm",
+    MunitionFamily::BallisticMagnetic500mmMassDriver => "500mm Mass Drivers",
+    MunitionFamily::BallisticChemical600mm => "600mm",
+    // Only reached by defensive missiles, such as interceptors
+    munition_family => MissileType::from_munition_family(munition_family).map_or("Missiles", missile_type_name)
+  }
+}
+
+const fn missile_type_name(missile_type: MissileType) -> &'static str {
+  match missile_type {
+    MissileType::StandardMissile(MissileSize::Size1) => "Size 1 Missiles",
+    MissileType::StandardMissile(MissileSize::Size2) => "Size 2 Missiles",
+    MissileType::StandardMissile(MissileSize::Size3) => "Size 3 Missiles",
+    MissileType::ContainerMissile => "Container Missiles",
+    MissileType::LoiteringMine => "Mines",
+    MissileType::UnguidedRocket => "Rockets"
+  }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use nebulous_data::data::components::ComponentKey;
+  use nebulous_data::data::hulls::HullKey;
+  use nebulous_data::loadout::ShipLoadoutSocket;
+
+  /// Installs a component in the first empty socket that it fits in, returning the socket's key.
+  fn install(loadout: &mut ShipLoadout, component_key: ComponentKey) -> Key {
+    let component = component_key.component();
+    let hull = loadout.hull_type.hull();
+    let index = hull.sockets.iter().enumerate()
+      .position(|(i, socket)| loadout.sockets[i].is_none() && component.is_legal_in(loadout.hull_type, socket))
+      .expect("no socket for component");
+    loadout.sockets[index] = Some(ShipLoadoutSocket { component_key, variant: None });
+    hull.sockets[index].save_key
+  }
+
+  fn names_and_members(weapon_groups: &[WeaponGroup]) -> Vec<(&str, &[Key])> {
+    weapon_groups.iter().map(|weapon_group| (weapon_group.name.as_str(), weapon_group.members.as_slice())).collect()
+  }
+
+  #[test]
+  fn assign_weapon_groups() {
+    let hull_type = HullKey::KeystoneDestroyer;
+    let mut loadout = ShipLoadout { hull_type, hull_config: None, sockets: vec![None; hull_type.hull().sockets.len()].into() };
+    let cannon1 = install(&mut loadout, ComponentKey::Mk61Cannon);
+    let cannon2 = install(&mut loadout, ComponentKey::Mk61Cannon);
+    let railgun = install(&mut loadout, ComponentKey::Mk550Railgun);
+    let pdt = install(&mut loadout, ComponentKey::Mk20DefenderPDT);
+    let launcher = install(&mut loadout, ComponentKey::VLS123Launcher);
+
+    let rules = WeaponGroupRules::default();
+    assert_eq!(names_and_members(&rules.weapon_groups(&loadout)), [
+      ("120mm", &[cannon1, cannon2][..]),
+      ("300mm Railguns", &[railgun][..]),
+      ("PD 20mm", &[pdt][..]),
+      ("Size 1 Missiles", &[launcher][..])
+    ]);
+
+    let rules = WeaponGroupRules { by_munition_family: false, ..WeaponGroupRules::default() };
+    assert_eq!(names_and_members(&rules.weapon_groups(&loadout)), [
+      ("Guns (Turreted)", &[cannon1, cannon2][..]),
+      ("Guns (Fixed)", &[railgun][..]),
+      ("PD Guns", &[pdt][..]),
+      ("Size 1 Missiles", &[launcher][..])
+    ]);
+
+    let rules = WeaponGroupRules { by_munition_family: false, by_mounting: false, point_defense: false, ..WeaponGroupRules::default() };
+    assert_eq!(names_and_members(&rules.weapon_groups(&loadout)), [
+      ("Guns", &[cannon1, cannon2, railgun][..]),
+      ("Size 1 Missiles", &[launcher][..])
+    ]);
+
+    let rules = WeaponGroupRules { enabled: false, ..WeaponGroupRules::default() };
+    assert!(rules.weapon_groups(&loadout).is_empty());
+  }
+}
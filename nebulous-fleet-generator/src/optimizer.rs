@@ -0,0 +1,474 @@
+//! Filling a point budget as closely as possible, as an alternative to the randomized selection in [`crate::generator`].
+
+use crate::library::Library;
+use crate::model::{FleetStrategy, ShipState};
+
+use nebulous_data::format::{Fleet, MunitionOrMissileKey};
+use nebulous_data::loadout::ShipLoadoutSocketVariant;
+
+use std::borrow::Cow;
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizerOptions {
+  pub point_budget: usize,
+  /// Whether ships may have missiles or ammunition removed from their magazines,
+  /// up to their `cost_budget_spare`, so that more of the budget can be used.
+  pub allow_trims: bool
+}
+
+/// A number of munitions or missiles removed from one of a ship's magazines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MagazineTrim {
+  /// The index of the magazine's socket in the ship's loadout.
+  pub socket: usize,
+  pub munition_key: MunitionOrMissileKey,
+  pub count: usize,
+  /// The points saved by removing a single munition or missile.
+  pub unit_cost: usize
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedShip<'l> {
+  pub ship_state: &'l ShipState,
+  /// The index of the strategy selection this ship was chosen for.
+  pub selection: usize,
+  pub trims: Vec<MagazineTrim>
+}
+
+impl<'l> PlannedShip<'l> {
+  pub fn trim_cost(&self) -> usize {
+    self.trims.iter().map(|trim| trim.count * trim.unit_cost).sum()
+  }
+
+  pub fn cost(&self) -> usize {
+    self.ship_state.cost_budget_total - self.trim_cost()
+  }
+
+  fn trimmed_count(&self, socket: usize, munition_key: &MunitionOrMissileKey) -> usize {
+    self.trims.iter()
+      .filter(|trim| trim.socket == socket && trim.munition_key == *munition_key)
+      .map(|trim| trim.count).sum()
+  }
+
+  /// Returns the ship state with this ship's trims applied to its magazines and costs.
+  pub fn to_ship_state(&self) -> Cow<'l, ShipState> {
+    if self.trims.is_empty() {
+      return Cow::Borrowed(self.ship_state);
+    };
+
+    let mut ship_state = self.ship_state.clone();
+    for trim in self.trims.iter() {
+      let socket = ship_state.loadout.sockets[trim.socket].as_mut();
+      if let Some(ShipLoadoutSocketVariant::MagazineComponent { magazine_contents }) = socket.and_then(|socket| socket.variant.as_mut()) {
+        if let Some(quantity) = magazine_contents.get_mut(&trim.munition_key) {
+          *quantity -= trim.count.min(*quantity);
+          if *quantity == 0 {
+            magazine_contents.shift_remove(&trim.munition_key);
+          };
+        };
+      };
+    };
+
+    let trim_cost = self.trim_cost();
+    ship_state.cost_budget_total -= trim_cost;
+    ship_state.cost_budget_spare = ship_state.cost_budget_spare.saturating_sub(trim_cost);
+    Cow::Owned(ship_state)
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate<'l> {
+  ship_state: &'l ShipState,
+  selection: usize,
+  cost: usize,
+  score: Score
+}
+
+/// The secondary objectives, compared after the number of points used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+struct Score {
+  prioritized: usize,
+  weight: usize
+}
+
+impl std::ops::Add for Score {
+  type Output = Self;
+
+  fn add(self, rhs: Self) -> Self {
+    Score { prioritized: self.prioritized + rhs.prioritized, weight: self.weight + rhs.weight }
+  }
+}
+
+enum Stage {
+  /// Any number of candidates may be taken from this stage.
+  Unbounded(Vec<usize>),
+  /// At most one candidate may be taken from this stage.
+  AtMostOne(Vec<usize>)
+}
+
+/// Adds ships from the pool to the required ships, using as much of the point budget as possible.
+///
+/// Each ship must be usable by the strategy and accepted by the selection it is chosen for.
+/// A selection with a `weight_initial` of zero is never used, and one with a `weight_additional` of zero contributes
+/// at most one ship, counting the required ships. Among compositions that use the same number of points, the one with
+/// the most prioritized ships is chosen, followed by the one with the greatest total selection weight.
+///
+/// Ships that would break a constraint which the fleet satisfies so far, or take it past [`Fleet::MAX_SHIPS`],
+/// are never added. Only the best composition for each number of points is kept while searching, so a composition
+/// that is held back by a constraint may hide one that is not.
+///
+/// Remaining ties are broken by the order of the pool, so the result is deterministic for a given pool order;
+/// the generator shuffles the pool with its seeded PRNG to vary the outcome.
+/// The returned plan starts with the required ships.
+pub fn optimize_ships<'l>(
+  pool: &[&'l ShipState],
+  required: Vec<PlannedShip<'l>>,
+  library: &Library,
+  strategy: &FleetStrategy,
+  options: &OptimizerOptions
+) -> Vec<PlannedShip<'l>> {
+  let budget = options.point_budget.saturating_sub(required.iter().map(PlannedShip::cost).sum());
+  let candidates = collect_candidates(pool, strategy);
+
+  let mut unbounded = Vec::new();
+  let mut stages_at_most_one = Vec::new();
+  for (index, selection) in strategy.selections.iter().enumerate() {
+    let members = candidates.iter().enumerate()
+      .filter(|(_, candidate)| candidate.selection == index)
+      .map(|(i, _)| i);
+    if selection.weight_additional == 0 {
+      if required.iter().any(|planned_ship| planned_ship.selection == index) { continue };
+      stages_at_most_one.push(Stage::AtMostOne(members.collect()));
+    } else {
+      // zero-cost ships could be added indefinitely
+      unbounded.extend(members.filter(|&i| candidates[i].cost > 0));
+    };
+  };
+
+  let stages = std::iter::once(Stage::Unbounded(unbounded))
+    .chain(stages_at_most_one)
+    .collect::<Vec<Stage>>();
+
+  // `best[c]` is the best score of a composition costing exactly `c` points,
+  // `parents[stage][c]` records the candidate added to reach `c` in that stage, and the cost it was added to
+  let mut best: Vec<Option<Score>> = vec![None; budget + 1];
+  best[0] = Some(Score::default());
+  let mut parents: Vec<Vec<Option<(usize, usize)>>> = Vec::with_capacity(stages.len());
+  // whether a candidate may be added to the required ships and the candidates chosen so far
+  let fits = |chosen: &[usize], candidate: &Candidate| -> bool {
+    if required.len() + chosen.len() >= Fleet::MAX_SHIPS { return false };
+    let selected = required.iter().map(|planned_ship| planned_ship.ship_state)
+      .chain(chosen.iter().map(|&i| candidates[i].ship_state))
+      .collect::<Vec<&ShipState>>();
+    !strategy.breaks_constraints(&selected, candidate.ship_state)
+  };
+
+  for (s, stage) in stages.iter().enumerate() {
+    parents.push(vec![None; budget + 1]);
+    match stage {
+      Stage::Unbounded(members) => {
+        for c in 0..=budget {
+          let Some(score) = best[c] else { continue };
+          let chosen = backtrack(&stages[..=s], &parents, c);
+          for &i in members {
+            if !fits(&chosen, &candidates[i]) { continue };
+            relax(&mut best, &mut parents[s], c, score, i, &candidates[i]);
+          };
+        };
+      },
+      Stage::AtMostOne(members) => {
+        let previous = best.clone();
+        for (c, score) in previous.iter().enumerate() {
+          let Some(score) = *score else { continue };
+          let chosen = backtrack(&stages[..s], &parents[..s], c);
+          for &i in members {
+            if !fits(&chosen, &candidates[i]) { continue };
+            relax(&mut best, &mut parents[s], c, score, i, &candidates[i]);
+          };
+        };
+      }
+    };
+  };
+
+  let Some(c) = (0..=budget).rev().find(|&c| best[c].is_some()) else {
+    return required;
+  };
+
+  let mut chosen = backtrack(&stages, &parents, c);
+  chosen.sort();
+  let mut planned = required;
+  planned.extend(chosen.into_iter().map(|i| {
+    PlannedShip { ship_state: candidates[i].ship_state, selection: candidates[i].selection, trims: Vec::new() }
+  }));
+
+  if options.allow_trims {
+    add_ships_with_trims(&mut planned, &candidates, library, strategy, options.point_budget);
+  };
+
+  planned
+}
+
+/// The candidates that were added to reach a cost of `c` points, walking back through the given stages.
+fn backtrack(stages: &[Stage], parents: &[Vec<Option<(usize, usize)>>], mut c: usize) -> Vec<usize> {
+  let mut chosen = Vec::new();
+  for (stage, stage_parents) in stages.iter().zip(parents.iter()).rev() {
+    while let Some((i, previous)) = stage_parents[c] {
+      chosen.push(i);
+      c = previous;
+      if let Stage::AtMostOne(..) = stage { break };
+    };
+  };
+
+  chosen
+}
+
+fn relax(
+  best: &mut [Option<Score>],
+  parents: &mut [Option<(usize, usize)>],
+  c: usize,
+  score: Score,
+  i: usize,
+  candidate: &Candidate
+) {
+  let next = c + candidate.cost;
+  if next >= best.len() { return };
+  let score = score + candidate.score;
+  if best[next].is_none_or(|existing| score > existing) {
+    best[next] = Some(score);
+    parents[next] = Some((i, c));
+  };
+}
+
+fn collect_candidates<'l>(pool: &[&'l ShipState], strategy: &FleetStrategy) -> Vec<Candidate<'l>> {
+  let mut candidates = Vec::new();
+  for &ship_state in pool {
+    if !strategy.is_usable(ship_state) { continue };
+    for (index, selection) in strategy.selections.iter().enumerate() {
+      if selection.weight_initial == 0 { continue };
+      if !selection.predicates.accepts(ship_state) { continue };
+
+      let weight = if selection.weight_additional == 0 { selection.weight_initial } else { selection.weight_additional };
+      let prioritized = selection.predicates.prioritizes(ship_state) as usize;
+      candidates.push(Candidate {
+        ship_state,
+        selection: index,
+        cost: ship_state.cost_budget_total,
+        score: Score { prioritized, weight }
+      });
+    };
+  };
+
+  candidates
+}
+
+/// Repeatedly adds the candidate that, after trimming magazines to make it fit, leaves the smallest gap to the budget.
+fn add_ships_with_trims<'l>(
+  planned: &mut Vec<PlannedShip<'l>>,
+  candidates: &[Candidate<'l>],
+  library: &Library,
+  strategy: &FleetStrategy,
+  budget: usize
+) {
+  while planned.len() < Fleet::MAX_SHIPS {
+    let total = planned.iter().map(PlannedShip::cost).sum::<usize>();
+    let gap = budget - total;
+    let selected = planned.iter().map(|p| p.ship_state).collect::<Vec<&ShipState>>();
+
+    let mut best: Option<(usize, Vec<PlannedShip<'l>>)> = None;
+    for candidate in candidates {
+      let selection = &strategy.selections[candidate.selection];
+      if selection.weight_additional == 0 && planned.iter().any(|p| p.selection == candidate.selection) { continue };
+      if strategy.breaks_constraints(&selected, candidate.ship_state) { continue };
+
+      let mut attempt = planned.clone();
+      attempt.push(PlannedShip { ship_state: candidate.ship_state, selection: candidate.selection, trims: Vec::new() });
+      let need = candidate.cost.saturating_sub(gap);
+      let Some(trimmed) = trim_magazines(&mut attempt, library, need) else { continue };
+
+      let new_gap = gap + trimmed - candidate.cost;
+      if new_gap < gap && best.as_ref().is_none_or(|(best_gap, _)| new_gap < *best_gap) {
+        best = Some((new_gap, attempt));
+      };
+    };
+
+    match best {
+      Some((_, attempt)) => *planned = attempt,
+      None => break
+    };
+  };
+}
+
+/// Removes at least `need` points of munitions and missiles from the planned ships' magazines,
+/// without any ship exceeding its spare budget. Returns the number of points removed.
+fn trim_magazines(planned: &mut [PlannedShip], library: &Library, need: usize) -> Option<usize> {
+  if need == 0 { return Some(0) };
+
+  // (ship, socket, munition key, unit cost, available)
+  let mut units = Vec::new();
+  for (p, planned_ship) in planned.iter().enumerate() {
+    for (socket, loadout_socket) in planned_ship.ship_state.loadout.sockets.iter().enumerate() {
+      let magazine_contents = loadout_socket.as_ref()
+        .and_then(|loadout_socket| loadout_socket.variant.as_ref())
+        .and_then(ShipLoadoutSocketVariant::get_magazine_contents);
+      for (munition_key, &quantity) in magazine_contents.into_iter().flatten() {
+        let unit_cost = match munition_key {
+          MunitionOrMissileKey::MunitionKey(munition_key) => munition_key.munition().point_cost,
+          MunitionOrMissileKey::MissileKey(name) => match library.find_missile(name) {
+            Some(missile_state) => missile_state.cost,
+            None => continue
+          }
+        };
+
+        let available = quantity - planned_ship.trimmed_count(socket, munition_key);
+        if unit_cost > 0 && available > 0 {
+          units.push((p, socket, munition_key.clone(), unit_cost, available));
+        };
+      };
+    };
+  };
+
+  // take the most expensive units first without overshooting, then cover any remainder with the cheapest single unit
+  units.sort_by_key(|unit| std::cmp::Reverse(unit.3));
+  let mut ship_budgets = planned.iter()
+    .map(|planned_ship| planned_ship.ship_state.cost_budget_spare - planned_ship.trim_cost())
+    .collect::<Vec<usize>>();
+  let mut takes = Vec::new();
+  let mut remaining = need;
+  for (u, &(p, _, _, unit_cost, available)) in units.iter().enumerate() {
+    let count = available.min(remaining / unit_cost).min(ship_budgets[p] / unit_cost);
+    if count > 0 {
+      ship_budgets[p] -= count * unit_cost;
+      remaining -= count * unit_cost;
+      takes.push((u, count));
+    };
+  };
+
+  if remaining > 0 {
+    let u = units.iter().enumerate().rev().find(|&(u, &(p, _, _, unit_cost, available))| {
+      let taken = takes.iter().filter(|&&(t, _)| t == u).map(|&(_, count)| count).sum::<usize>();
+      unit_cost >= remaining && available > taken && ship_budgets[p] >= unit_cost
+    })?.0;
+    remaining = 0;
+    takes.push((u, 1));
+  };
+
+  let mut trimmed = 0;
+  for (u, count) in takes {
+    let (p, socket, ref munition_key, unit_cost, _) = units[u];
+    trimmed += count * unit_cost;
+    planned[p].trims.push(MagazineTrim { socket, munition_key: munition_key.clone(), count, unit_cost });
+  };
+
+  debug_assert_eq!(remaining, 0);
+  Some(trimmed)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::model::FleetStrategySelection;
+  use crate::test_utils::ship_state;
+  use nebulous_data::data::Faction;
+  use nebulous_data::data::hulls::HullKey;
+  use nebulous_data::data::components::ComponentKey;
+  use nebulous_data::data::munitions::MunitionKey;
+  use nebulous_data::loadout::ShipLoadoutSocket;
+
+  fn strategy(selections: Vec<(usize, usize)>) -> FleetStrategy {
+    FleetStrategy {
+      faction: Faction::Alliance,
+      selections: selections.into_iter()
+        .map(|(weight_initial, weight_additional)| FleetStrategySelection {
+          weight_initial,
+          weight_additional,
+          predicates: Default::default()
+        })
+        .collect(),
+      missiles: Vec::new(),
+      constraints: Vec::new(),
+      weapon_groups: Default::default()
+    }
+  }
+
+  #[test]
+  fn fills_budget_exactly() {
+    let ships = [ship_state("A", HullKey::SprinterCorvette, 700), ship_state("B", HullKey::SprinterCorvette, 450), ship_state("C", HullKey::SprinterCorvette, 275)];
+    let pool = ships.iter().collect::<Vec<&ShipState>>();
+    let options = OptimizerOptions { point_budget: 1000, allow_trims: false };
+    let planned = optimize_ships(&pool, Vec::new(), &Library::new(), &strategy(vec![(1, 1)]), &options);
+
+    // greedy filling would take "A" and stop at 700
+    assert_eq!(planned.iter().map(PlannedShip::cost).sum::<usize>(), 1000);
+    assert_eq!(planned.iter().map(|p| &*p.ship_state.name[0]).collect::<Vec<&str>>(), ["B", "C", "C"]);
+  }
+
+  #[test]
+  fn respects_single_ship_selections() {
+    let ships = [ship_state("A", HullKey::SprinterCorvette, 500)];
+    let pool = ships.iter().collect::<Vec<&ShipState>>();
+    let options = OptimizerOptions { point_budget: 2000, allow_trims: false };
+    let planned = optimize_ships(&pool, Vec::new(), &Library::new(), &strategy(vec![(1, 0), (0, 1)]), &options);
+    assert_eq!(planned.len(), 1);
+  }
+
+  #[test]
+  fn respects_constraints() {
+    let mut big = ship_state("A", HullKey::SprinterCorvette, 500);
+    big.tags.insert("big".to_owned());
+    let ships = [big, ship_state("B", HullKey::SprinterCorvette, 300)];
+    let pool = ships.iter().collect::<Vec<&ShipState>>();
+    let mut strategy = strategy(vec![(1, 1)]);
+    strategy.constraints.push("count(tag/big) <= 1".parse().unwrap());
+    let options = OptimizerOptions { point_budget: 1000, allow_trims: false };
+    let planned = optimize_ships(&pool, Vec::new(), &Library::new(), &strategy, &options);
+
+    // two of "A" would use the whole budget, but break the constraint
+    assert_eq!(planned.iter().map(PlannedShip::cost).sum::<usize>(), 900);
+    assert_eq!(planned.iter().map(|p| &*p.ship_state.name[0]).collect::<Vec<&str>>(), ["B", "B", "B"]);
+  }
+
+  #[test]
+  fn counts_required_ships() {
+    let ships = [ship_state("A", HullKey::SprinterCorvette, 500), ship_state("B", HullKey::SprinterCorvette, 100)];
+    let pool = ships.iter().collect::<Vec<&ShipState>>();
+    let required = vec![PlannedShip { ship_state: &ships[0], selection: 0, trims: Vec::new() }];
+    let options = OptimizerOptions { point_budget: 2000, allow_trims: false };
+    let planned = optimize_ships(&pool, required.clone(), &Library::new(), &strategy(vec![(1, 0)]), &options);
+    assert_eq!(planned, required);
+
+    // "B" alone would need fifteen ships to fill the remaining budget
+    let planned = optimize_ships(&pool[1..], required, &Library::new(), &strategy(vec![(1, 1)]), &options);
+    assert_eq!(planned.len(), Fleet::MAX_SHIPS);
+    assert_eq!(planned[0].ship_state.name[0], "A");
+  }
+
+  #[test]
+  fn trims_magazines_to_fit() {
+    let mut ship = ship_state("A", HullKey::SprinterCorvette, 600);
+    ship.cost_budget_spare = 100;
+    ship.loadout.sockets[0] = Some(ShipLoadoutSocket {
+      component_key: ComponentKey::AmmunitionElevators,
+      variant: Some(ShipLoadoutSocketVariant::MagazineComponent {
+        magazine_contents: [(MunitionOrMissileKey::MunitionKey(MunitionKey::A20mmSlug), 100)].into_iter().collect()
+      })
+    });
+
+    let pool = [&ship];
+    let strategy = strategy(vec![(1, 1)]);
+    let options = OptimizerOptions { point_budget: 1150, allow_trims: false };
+    assert_eq!(optimize_ships(&pool, Vec::new(), &Library::new(), &strategy, &options).len(), 1);
+
+    let options = OptimizerOptions { point_budget: 1150, allow_trims: true };
+    let planned = optimize_ships(&pool, Vec::new(), &Library::new(), &strategy, &options);
+    assert_eq!(planned.iter().map(PlannedShip::cost).sum::<usize>(), 1150);
+
+    let trimmed = planned.iter().map(PlannedShip::to_ship_state)
+      .map(|ship_state| ship_state.cost_budget_total)
+      .sum::<usize>();
+    assert_eq!(trimmed, 1150);
+  }
+}
@@ -0,0 +1,461 @@
+//! An interactive prompt for trying out predicates and strategies against a library.
+//!
+//! Each line entered is either a ship predicate, which lists every ship in the library that it matches,
+//! or one of the commands listed in [`HELP`]. Keywords from the predicate grammar, the library's tags and
+//! the commands themselves can be completed with tab.
+
+use crate::generator::{GeneratorError, Random};
+use crate::library::Library;
+use crate::model::{DistanceRealm, FleetStrategy, PointDefenseType, ShipState};
+use crate::model::predicate::ShipPredicate;
+use crate::utils::{Span, Symbol, Token, Tokens, JSON};
+
+use nebulous_data::data::components::SigType;
+use rand::SeedableRng;
+use rand::rngs::OsRng;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::MemHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::Path;
+
+
+
+pub const HELP: &str = "\
+<predicate>                      list the ships matching a ship predicate, such as `tag/line and !hull_key/solomon`
+:strategy <path>                 load a strategy to dry-run
+:dry-run <point budget> [fleets] select ships for many fleets with the loaded strategy and show how often each was picked
+:help                            show this message
+:quit                            exit";
+
+/// The number of fleets selected by `:dry-run` when no number is given.
+const DEFAULT_DRY_RUN_FLEETS: usize = 1000;
+
+const COMMANDS: &[&str] = &[":strategy", ":dry-run", ":help", ":quit"];
+
+pub struct Repl<'l> {
+  library: &'l Library,
+  strategy: Option<FleetStrategy>
+}
+
+impl<'l> Repl<'l> {
+  pub fn new(library: &'l Library, strategy: Option<FleetStrategy>) -> Self {
+    Repl { library, strategy }
+  }
+
+  /// Reads and evaluates lines from the terminal until the user quits or closes the input.
+  pub fn run(&mut self) -> rustyline::Result<()> {
+    let config = Config::builder()
+      .auto_add_history(true)
+      .completion_type(CompletionType::List)
+      .build();
+    let mut editor = Editor::with_history(config, MemHistory::new())?;
+    editor.set_helper(Some(KeywordCompleter::new(self.library)));
+
+    println!("enter a ship predicate to list the ships it matches, or :help for more commands");
+    loop {
+      match editor.readline("> ") {
+        Ok(line) => match self.eval(&line) {
+          Some(output) => print!("{output}"),
+          None => break
+        },
+        // Ctrl-C discards the current line, like in a shell
+        Err(ReadlineError::Interrupted) => continue,
+        Err(ReadlineError::Eof) => break,
+        Err(error) => return Err(error)
+      };
+    };
+
+    Ok(())
+  }
+
+  /// Evaluates a single line of input, returning what should be printed, or `None` if the user asked to quit.
+  pub fn eval(&mut self, line: &str) -> Option<String> {
+    let line = line.trim();
+    let (command, args) = line.split_once(char::is_whitespace)
+      .map_or((line, ""), |(command, args)| (command, args.trim()));
+
+    let mut out = String::new();
+    match command {
+      "" => (),
+      ":quit" | ":exit" => return None,
+      ":help" => { let _ = writeln!(out, "{HELP}"); },
+      ":strategy" => self.load_strategy(args, &mut out),
+      ":dry-run" => self.dry_run(args, &mut out),
+      command if command.starts_with(':') => {
+        let _ = writeln!(out, "unknown command `{command}`, enter :help for a list of commands");
+      },
+      _ => self.query(line, &mut out)
+    };
+
+    Some(out)
+  }
+
+  fn query(&self, source: &str, out: &mut String) {
+    let predicate = match source.parse::<ShipPredicate>() {
+      Ok(predicate) => predicate,
+      Err(errors) => { let _ = writeln!(out, "{errors}"); return }
+    };
+
+    let mut count = 0;
+    for (id, ship_state) in self.library.ships.iter().filter(|(_, ship_state)| predicate.test(ship_state)) {
+      let _ = writeln!(out, "{}", describe_ship(id, ship_state));
+      let _ = writeln!(out, "  {}", ship_state.equipment_summary);
+      count += 1;
+    };
+
+    let _ = writeln!(out, "{count} of {} ships matched", self.library.ships.len());
+
+    // `run_stage1` cannot fail here, since the source has already been parsed
+    if let Ok(tokens) = crate::utils::run_stage1::<Tokens>(source) {
+      if split_branches(&tokens).is_some() {
+        let _ = writeln!(out, "matches by branch:");
+        self.write_branch_matches(source, &tokens, 1, out);
+      };
+    };
+  }
+
+  /// Writes how many ships each branch of an `any` or `all` matched, and then the same for each of those branches.
+  fn write_branch_matches(&self, source: &str, tokens: &[(Token, Span)], depth: usize, out: &mut String) {
+    let Some(branches) = split_branches(tokens) else { return };
+    for branch in branches {
+      let branch_source = &source[token_span(branch)];
+      let Ok(predicate) = branch_source.parse::<ShipPredicate>() else { continue };
+      let count = self.library.ships.values().filter(|ship_state| predicate.test(ship_state)).count();
+      let _ = writeln!(out, "{:indent$}{count:>4}  {branch_source}", "", indent = depth * 2);
+      self.write_branch_matches(source, branch, depth + 1, out);
+    };
+  }
+
+  fn load_strategy(&mut self, path: &str, out: &mut String) {
+    if path.is_empty() {
+      let _ = writeln!(out, "usage: :strategy <path>");
+      return;
+    };
+
+    match crate::utils::read_file::<FleetStrategy, _>(Path::new(path), JSON) {
+      Ok(strategy) => {
+        let _ = writeln!(
+          out, "loaded strategy for {} with {} selections and {} constraints",
+          strategy.faction, strategy.selections.len(), strategy.constraints.len()
+        );
+        self.strategy = Some(strategy);
+      },
+      Err(error) => { let _ = writeln!(out, "failed to read strategy: {error}"); }
+    };
+  }
+
+  /// Selects ships for many fleets in the same way as [`crate::generator::FillMode::Random`], without assembling them,
+  /// to show how likely each ship in the library is to be picked by the loaded strategy.
+  fn dry_run(&self, args: &str, out: &mut String) {
+    const USAGE: &str = "usage: :dry-run <point budget> [fleets]";
+
+    let Some(strategy) = self.strategy.as_ref() else {
+      let _ = writeln!(out, "no strategy is loaded, load one with :strategy <path>");
+      return;
+    };
+
+    let mut args = args.split_whitespace().map(str::parse::<usize>);
+    let (point_budget, fleets) = match (args.next(), args.next(), args.next()) {
+      (Some(Ok(point_budget)), None, None) => (point_budget, DEFAULT_DRY_RUN_FLEETS),
+      (Some(Ok(point_budget)), Some(Ok(fleets)), None) if fleets > 0 => (point_budget, fleets),
+      _ => { let _ = writeln!(out, "{USAGE}"); return }
+    };
+
+    for (i, selection) in strategy.selections.iter().enumerate() {
+      let candidates = self.library.ships.values()
+        .filter(|ship_state| strategy.is_usable(ship_state) && selection.predicates.accepts(ship_state))
+        .collect::<Vec<&ShipState>>();
+      let prioritized = candidates.iter().filter(|ship_state| selection.predicates.prioritizes(ship_state)).count();
+      let _ = writeln!(
+        out, "selection {} (weight {} then {}): {} candidates, {prioritized} prioritized",
+        i + 1, selection.weight_initial, selection.weight_additional, candidates.len()
+      );
+    };
+
+    let mut rng = Random::from_rng(OsRng).expect("failed to seed prng");
+    let mut picks = BTreeMap::<String, (usize, usize)>::new();
+    let mut failures = BTreeMap::<String, usize>::new();
+    let (mut total_ships, mut total_points) = (0, 0);
+    for _ in 0..fleets {
+      let library = crate::generator::assign_missiles(self.library, strategy, &mut rng);
+      let selected = match crate::generator::select_ships(&library, strategy, point_budget, &mut rng) {
+        Ok(selected) if selected.is_empty() => Err(GeneratorError::NoShipsSelected(point_budget)),
+        result => result
+      };
+
+      match selected {
+        Ok(selected) => {
+          total_ships += selected.len();
+          total_points += selected.iter().map(|(_, ship_state)| ship_state.cost_budget_total).sum::<usize>();
+          let mut counts = BTreeMap::<&str, usize>::new();
+          for &(id, _) in selected.iter() {
+            *counts.entry(id).or_default() += 1;
+          };
+
+          for (id, count) in counts {
+            let (fleets_with, picked) = picks.entry(id.to_owned()).or_default();
+            *fleets_with += 1;
+            *picked += count;
+          };
+        },
+        Err(error) => *failures.entry(error.to_string()).or_default() += 1
+      };
+    };
+
+    let generated = fleets - failures.values().sum::<usize>();
+    let _ = writeln!(out, "generated {generated} of {fleets} fleets within {point_budget} points");
+    if generated > 0 {
+      let _ = writeln!(
+        out, "on average, fleets had {:.1} ships and {:.0} points",
+        total_ships as f64 / generated as f64, total_points as f64 / generated as f64
+      );
+    };
+
+    let mut picks = picks.into_iter().collect::<Vec<(String, (usize, usize))>>();
+    picks.sort_by(|(_, a), (_, b)| b.cmp(a));
+    for (id, (fleets_with, picked)) in picks.iter() {
+      let ship_state = self.library.ships.get(id).expect("ship ids are shared between clones");
+      let _ = writeln!(
+        out, "{:>6.1}% of fleets, {:.2} per fleet: {}",
+        *fleets_with as f64 / fleets as f64 * 100.0,
+        *picked as f64 / fleets as f64,
+        describe_ship(id, ship_state)
+      );
+    };
+
+    let never_picked = self.library.ships.iter()
+      .filter(|(id, ship_state)| !picks.iter().any(|(picked_id, _)| picked_id == id) && strategy.is_usable(ship_state))
+      .count();
+    let _ = writeln!(out, "{never_picked} ships usable with this strategy were never picked");
+
+    for (error, count) in failures.iter() {
+      let _ = writeln!(out, "{count} fleets failed: {error}");
+    };
+  }
+}
+
+fn describe_ship(id: &str, ship_state: &ShipState) -> String {
+  format!(
+    "ship {id}: {} ({}, {} points)",
+    ship_state.name.join(" / "),
+    ship_state.loadout.hull_type.hull().name,
+    ship_state.cost_budget_total
+  )
+}
+
+/// Splits a predicate into the branches of its outermost `any` or `all`, whether written with
+/// `or` and `and` or as `any(...)` and `all(...)`. Returns `None` if the predicate has no such branches.
+fn split_branches(mut tokens: &[(Token, Span)]) -> Option<Vec<&[(Token, Span)]>> {
+  while let Some(inner) = strip_round_brackets(tokens) {
+    tokens = inner;
+  };
+
+  // `or` binds more loosely than `and`, so it is split on first
+  for operator in ["or", "and"] {
+    let branches = split_top_level(tokens, |token| is_keyword(token, operator));
+    if branches.len() > 1 {
+      return Some(branches);
+    };
+  };
+
+  match tokens {
+    [(token, _), rest @ ..] if is_keyword(token, "any") || is_keyword(token, "all") => {
+      let branches = split_top_level(strip_round_brackets(rest)?, |token| *token == Token::Symbol(Symbol::Comma))
+        .into_iter().filter(|branch| !branch.is_empty()).collect::<Vec<_>>();
+      (branches.len() > 1).then_some(branches)
+    },
+    _ => None
+  }
+}
+
+/// Removes a pair of round brackets surrounding every other token, but not those in `(x) or (y)`.
+fn strip_round_brackets(tokens: &[(Token, Span)]) -> Option<&[(Token, Span)]> {
+  let [(Token::Symbol(Symbol::RoundBracketOpen), _), inner @ .., (Token::Symbol(Symbol::RoundBracketClose), _)] = tokens else {
+    return None;
+  };
+
+  let mut depth = 0isize;
+  for (token, _) in inner {
+    depth += bracket_depth_change(token);
+    if depth < 0 { return None };
+  };
+
+  Some(inner)
+}
+
+/// Splits tokens at each separator that is not inside of any brackets.
+fn split_top_level(tokens: &[(Token, Span)], is_separator: impl Fn(&Token) -> bool) -> Vec<&[(Token, Span)]> {
+  let mut branches = Vec::new();
+  let (mut depth, mut start) = (0isize, 0);
+  for (i, (token, _)) in tokens.iter().enumerate() {
+    depth += bracket_depth_change(token);
+    if depth == 0 && is_separator(token) {
+      branches.push(&tokens[start..i]);
+      start = i + 1;
+    };
+  };
+
+  branches.push(&tokens[start..]);
+  branches
+}
+
+fn bracket_depth_change(token: &Token) -> isize {
+  match token {
+    Token::Symbol(Symbol::RoundBracketOpen | Symbol::SquareBracketOpen) => 1,
+    Token::Symbol(Symbol::RoundBracketClose | Symbol::SquareBracketClose) => -1,
+    _ => 0
+  }
+}
+
+fn is_keyword(token: &Token, keyword: &str) -> bool {
+  matches!(token, Token::Ident(ident) if &*ident.contents == keyword)
+}
+
+/// The span of source covered by a non-empty list of tokens.
+fn token_span(tokens: &[(Token, Span)]) -> Span {
+  let start = tokens.first().map_or(0, |(_, span)| span.start);
+  let end = tokens.last().map_or(start, |(_, span)| span.end);
+  start..end
+}
+
+/// Every keyword path in the ship predicate grammar, such as `hull_key/raines` or `equipment/weapon/ballistic/near`.
+///
+/// Paths that may be continued are listed both with and without the rest, as in `equipment/sensor` and
+/// `equipment/sensor/radar`.
+fn predicate_keywords() -> Vec<String> {
+  const HULL_KEYS: &[&str] = &[
+    "sprinter", "raines", "keystone", "vauxhall", "axford", "solomon",
+    "ferryman", "draugr", "flathead", "ocello", "marauder", "moorline"
+  ];
+
+  let sig_types = [SigType::ElectroOptical, SigType::Radar, SigType::Comms];
+  let distance_realms = [DistanceRealm::Near, DistanceRealm::Middle, DistanceRealm::Far];
+  let point_defense_types = [PointDefenseType::Beam, PointDefenseType::Defender, PointDefenseType::Flak, PointDefenseType::Railgun];
+
+  let mut keywords = vec!["cost_budget_total".to_owned(), "cost_budget_spare".to_owned()];
+  keywords.extend(HULL_KEYS.iter().map(|hull_key| format!("hull_key/{hull_key}")));
+  for equipment in ["intelligence", "illuminator", "deception_module", "missile_identification"] {
+    keywords.push(format!("equipment/{equipment}"));
+  };
+
+  for equipment in ["fire_control", "sensor", "jammer"] {
+    keywords.push(format!("equipment/{equipment}"));
+    keywords.extend(sig_types.iter().map(|sig_type| format!("equipment/{equipment}/{sig_type}")));
+  };
+
+  for weapon in ["energy_beam", "energy_plasma", "energy_rail_gun", "ballistic"] {
+    keywords.push(format!("equipment/weapon/{weapon}"));
+    keywords.extend(distance_realms.iter().map(|distance_realm| format!("equipment/weapon/{weapon}/{distance_realm}")));
+  };
+
+  keywords.push("equipment/weapon/point_defense".to_owned());
+  keywords.extend(point_defense_types.iter().map(|point_defense_type| format!("equipment/weapon/point_defense/{point_defense_type}")));
+  keywords.push("equipment/weapon/standard_missile".to_owned());
+  keywords.extend((1..=3).map(|missile_size| format!("equipment/weapon/standard_missile/{missile_size}")));
+  for weapon in ["container_missile", "loitering_mine", "unguided_rocket"] {
+    keywords.push(format!("equipment/weapon/{weapon}"));
+  };
+
+  keywords
+}
+
+/// Completes the word before the cursor with a keyword from the predicate grammar, a tag from the library or a command.
+struct KeywordCompleter {
+  keywords: BTreeSet<String>
+}
+
+impl KeywordCompleter {
+  fn new(library: &Library) -> Self {
+    let mut keywords = predicate_keywords().into_iter().collect::<BTreeSet<String>>();
+    keywords.extend(["any", "all", "not", "and", "or"].map(str::to_owned));
+    keywords.extend(COMMANDS.iter().map(|&command| command.to_owned()));
+    keywords.extend(library.ships.values().flat_map(|ship_state| ship_state.tags.iter()).map(|tag| format!("tag/{tag}")));
+    KeywordCompleter { keywords }
+  }
+
+  /// Completes up to the end of the next segment of a keyword path, so that `equipment/we`
+  /// is completed to `equipment/weapon/` rather than to every weapon that could follow it.
+  fn complete_word(&self, word: &str) -> Vec<String> {
+    let mut completions = self.keywords.iter()
+      .filter(|keyword| keyword.starts_with(word))
+      .map(|keyword| match keyword[word.len()..].find('/') {
+        Some(i) => keyword[..word.len() + i + 1].to_owned(),
+        None => keyword.clone()
+      })
+      .collect::<Vec<String>>();
+    completions.dedup();
+    completions
+  }
+}
+
+impl Completer for KeywordCompleter {
+  type Candidate = Pair;
+
+  fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+    let is_word_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '/' | ':' | '-');
+    let start = line[..pos].trim_end_matches(is_word_char).len();
+    let completions = self.complete_word(&line[start..pos]).into_iter()
+      .map(|completion| Pair { display: completion.clone(), replacement: completion })
+      .collect();
+    Ok((start, completions))
+  }
+}
+
+impl Hinter for KeywordCompleter {
+  type Hint = String;
+}
+
+impl Highlighter for KeywordCompleter {}
+
+impl Validator for KeywordCompleter {}
+
+impl Helper for KeywordCompleter {}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn branch_sources(source: &str) -> Option<Vec<&str>> {
+    let tokens = crate::utils::run_stage1::<Tokens>(source).unwrap();
+    split_branches(&tokens).map(|branches| {
+      branches.into_iter().map(|branch| &source[token_span(branch)]).collect()
+    })
+  }
+
+  #[test]
+  fn split_predicate_branches() {
+    assert_eq!(branch_sources("tag/a or tag/b and tag/c"), Some(vec!["tag/a", "tag/b and tag/c"]));
+    assert_eq!(branch_sources("(tag/a and !(tag/b or tag/c))"), Some(vec!["tag/a", "!(tag/b or tag/c)"]));
+    assert_eq!(branch_sources("(tag/a) and (tag/b)"), Some(vec!["(tag/a)", "(tag/b)"]));
+    assert_eq!(
+      branch_sources("any(equipment/weapon/ballistic/[near, far], tag/b,)"),
+      Some(vec!["equipment/weapon/ballistic/[near, far]", "tag/b"])
+    );
+    assert_eq!(branch_sources("!(tag/a or tag/b)"), None);
+    assert_eq!(branch_sources("all(tag/a)"), None);
+
+    // Spans are byte offsets, so branches after non-ascii whitespace are sliced correctly
+    assert_eq!(branch_sources("tag/a\u{3000}or\u{a0}tag/b or tag/c"), Some(vec!["tag/a", "tag/b", "tag/c"]));
+  }
+
+  #[test]
+  fn complete_keywords() {
+    for keyword in predicate_keywords() {
+      assert!(keyword.parse::<ShipPredicate>().is_ok() || keyword.starts_with("cost_budget"), "failed to parse {keyword}");
+    };
+
+    let completer = KeywordCompleter::new(&Library::default());
+    assert_eq!(completer.complete_word("equipment/we"), ["equipment/weapon/"]);
+    assert_eq!(completer.complete_word("equipment/weapon/ball"), ["equipment/weapon/ballistic", "equipment/weapon/ballistic/"]);
+    assert_eq!(completer.complete_word(":dr"), [":dry-run"]);
+  }
+}
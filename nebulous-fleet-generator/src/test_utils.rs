@@ -0,0 +1,54 @@
+//! Library entries shared by the tests of several modules.
+
+use crate::model::{MissileEquipmentSummary, MissileState, ShipState};
+
+use nebulous_data::data::hulls::HullKey;
+use nebulous_data::data::missiles::Maneuvers;
+use nebulous_data::data::missiles::bodies::MissileBodyKey;
+use nebulous_data::data::missiles::seekers::{SeekerKind, SeekerStrategy};
+use nebulous_data::format::Color;
+use nebulous_data::loadout::{AvionicsConfigured, MissileLoadout, ShipLoadout};
+
+
+
+/// A ship with an empty loadout and no spare budget.
+pub fn ship_state(name: &str, hull_type: HullKey, cost: usize) -> ShipState {
+  let sockets = vec![None; hull_type.hull().sockets.len()].into_boxed_slice();
+  ShipState {
+    name: vec![name.to_owned()],
+    author: None,
+    tags: Default::default(),
+    cost_budget_total: cost,
+    cost_budget_spare: 0,
+    equipment_summary: Default::default(),
+    loadout: ShipLoadout { hull_type, hull_config: None, sockets }
+  }
+}
+
+/// A directly guided SGM-1 missile with an empty loadout.
+pub fn missile_state(nickname: &str, tags: &[&str], cost: usize) -> MissileState {
+  let white = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+  MissileState {
+    designation: "SGM-1".to_owned(),
+    nickname: nickname.to_owned(),
+    author: None,
+    tags: tags.iter().map(|&tag| tag.to_owned()).collect(),
+    base_color: white,
+    stripe_color: white,
+    cost,
+    equipment_summary: MissileEquipmentSummary {
+      body_key: MissileBodyKey::SGM1Balestra,
+      seekers: SeekerStrategy::new(SeekerKind::ActiveRadar, []),
+      auxiliary_components: Vec::new(),
+      avionics: AvionicsConfigured::DirectGuidance {
+        hot_launch: false,
+        self_destruct_on_lost: false,
+        maneuvers: Maneuvers::None,
+        defensive_doctrine: None,
+        approach_angle_control: false
+      },
+      warhead: None
+    },
+    loadout: MissileLoadout { body_key: MissileBodyKey::SGM1Balestra, sockets: Box::new([]) }
+  }
+}
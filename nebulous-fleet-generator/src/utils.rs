@@ -1,9 +1,15 @@
+use chumsky::error::SimpleReason;
 use chumsky::prelude::*;
 use chumsky::stream::Stream;
+use singlefile::FileFormat;
+use singlefile_formats::json_serde::Json;
 use thiserror::Error;
 
+use std::fs::File;
 use std::hash::Hash;
+use std::io::{BufReader, BufWriter, Write};
 use std::ops::Range;
+use std::path::Path;
 use std::str::FromStr;
 use std::fmt;
 
@@ -14,7 +20,7 @@ pub struct FmtList<'t, T>(pub &'t [T]);
 
 impl<'t, T> fmt::Display for FmtList<'t, T> where T: fmt::Display {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    for (i, value) in self.0.into_iter().enumerate() {
+    for (i, value) in self.0.iter().enumerate() {
       if i != 0 { f.write_str(", ")? };
       fmt::Display::fmt(value, f)?;
     };
@@ -25,41 +31,182 @@ impl<'t, T> fmt::Display for FmtList<'t, T> where T: fmt::Display {
 
 
 
+/// The format used for library and strategy files.
+pub const JSON: Json = Json;
+
 #[derive(Debug, Error)]
-pub enum Errors {
-  #[error("error(s) occured in parser stage 1: {}", FmtList(.0))]
-  Stage1(Vec<Simple<char>>),
-  #[error("error(s) occured in parser stage 2: {}", FmtList(.0))]
-  Stage2(Vec<Simple<Token>>)
+pub enum FileError<E> {
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+  #[error(transparent)]
+  Format(E)
+}
+
+pub fn read_file<T, F: FileFormat<T>>(path: impl AsRef<Path>, format: F) -> Result<T, FileError<F::FormatError>> {
+  let reader = BufReader::new(File::open(path)?);
+  format.from_reader(reader).map_err(FileError::Format)
+}
+
+/// Writes to a temporary file next to `path` and then renames it into place,
+/// so that readers never observe a partially written file.
+pub fn write_file_atomic<T, F: FileFormat<T>>(path: impl AsRef<Path>, format: F, value: &T) -> Result<(), FileError<F::FormatError>> {
+  let path = path.as_ref();
+  let mut temp_path = path.as_os_str().to_owned();
+  temp_path.push(".tmp");
+
+  let file = File::create(&temp_path)?;
+  let mut writer = BufWriter::new(file);
+  format.to_writer(&mut writer, value).map_err(FileError::Format)?;
+  writer.flush()?;
+  writer.get_ref().sync_all()?;
+  drop(writer);
+
+  std::fs::rename(&temp_path, path)?;
+  Ok(())
+}
+
+
+
+/// A 64-bit FNV-1a hash, which unlike [`std::hash::DefaultHasher`] is guaranteed not to change between releases.
+pub const fn hash_bytes(bytes: &[u8]) -> u64 {
+  let mut hash = 0xcbf29ce484222325u64;
+  let mut i = 0;
+  while i < bytes.len() {
+    hash ^= bytes[i] as u64;
+    hash = hash.wrapping_mul(0x100000001b3);
+    i += 1;
+  };
+
+  hash
 }
 
-impl From<Vec<Simple<char>>> for Errors {
-  fn from(errors: Vec<Simple<char>>) -> Self {
-    Errors::Stage1(errors)
+/// Lowercases a name, replacing every run of other characters than ASCII letters and digits with the separator,
+/// and trimming it from both ends.
+pub fn slugify(name: &str, separator: char) -> String {
+  let mut slug = String::with_capacity(name.len());
+  for ch in name.chars() {
+    if ch.is_ascii_alphanumeric() {
+      slug.push(ch.to_ascii_lowercase());
+    } else if !slug.is_empty() && !slug.ends_with(separator) {
+      slug.push(separator);
+    };
+  };
+
+  let len = slug.trim_end_matches(separator).len();
+  slug.truncate(len);
+  slug
+}
+
+
+
+/// Parser errors, kept alongside the source they occurred in so that each one can be shown with a snippet of it.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{}", self.render())]
+pub struct Errors {
+  pub input: Box<str>,
+  pub diagnostics: Vec<Diagnostic>
+}
+
+impl Errors {
+  pub fn new<T>(source: &str, errors: Vec<Simple<T>>) -> Self
+  where T: fmt::Display + Hash + Eq {
+    let mut diagnostics = errors.iter().map(Diagnostic::from_simple).collect::<Vec<Diagnostic>>();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    Errors { input: source.into(), diagnostics }
+  }
+
+  /// Renders every diagnostic along with the line of source it points into, for example:
+  ///
+  /// ```text
+  /// error: unexpected `frigate`, expected hull key
+  ///  --> 1:24
+  ///   |
+  /// 1 | all(tag/line, hull_key/frigate)
+  ///   |                        ^^^^^^^
+  /// ```
+  pub fn render(&self) -> String {
+    let mut out = String::new();
+    for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+      if i != 0 { out.push('\n') };
+      diagnostic.render_into(&self.input, &mut out);
+    };
+
+    out
   }
 }
 
-impl From<Vec<Simple<Token>>> for Errors {
-  fn from(errors: Vec<Simple<Token>>) -> Self {
-    Errors::Stage2(errors)
+/// A single parser error, located by the span of source it occurred at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+  pub span: Span,
+  pub message: String
+}
+
+impl Diagnostic {
+  fn from_simple<T>(error: &Simple<T>) -> Self
+  where T: fmt::Display + Hash + Eq {
+    let message = match error.reason() {
+      SimpleReason::Custom(message) => message.clone(),
+      SimpleReason::Unclosed { delimiter, .. } => format!("unclosed delimiter `{delimiter}`"),
+      SimpleReason::Unexpected => {
+        let found = error.found().map_or_else(|| "end of input".to_owned(), |found| format!("`{found}`"));
+        let mut expected = error.expected()
+          .map(|expected| expected.as_ref().map_or_else(|| "end of input".to_owned(), |expected| format!("`{expected}`")))
+          .chain(error.label().map(str::to_owned))
+          .collect::<Vec<String>>();
+        expected.sort();
+        expected.dedup();
+
+        match expected.as_slice() {
+          [] => format!("unexpected {found}"),
+          [expected] => format!("unexpected {found}, expected {expected}"),
+          expected => format!("unexpected {found}, expected one of {}", FmtList(expected))
+        }
+      }
+    };
+
+    Diagnostic { span: error.span(), message }
+  }
+
+  fn render_into(&self, source: &str, out: &mut String) {
+    use std::fmt::Write;
+
+    // The end of input is reported as a span just past the end of the source
+    let start = self.span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+    let line = &source[line_start..line_end];
+    let line_number = source[..line_start].matches('\n').count() + 1;
+    let column = source[line_start..start].chars().count();
+    let width = source[start..self.span.end.clamp(start, line_end)].chars().count().max(1);
+    let gutter = " ".repeat(line_number.to_string().len());
+
+    let _ = writeln!(out, "error: {}", self.message);
+    let _ = writeln!(out, "{gutter}--> {line_number}:{}", column + 1);
+    let _ = writeln!(out, "{gutter} |");
+    let _ = writeln!(out, "{line_number} | {line}");
+    let _ = write!(out, "{gutter} | {}{}", " ".repeat(column), "^".repeat(width));
   }
 }
 
 pub fn run<P: Parseable<Token>>(source: &str) -> Result<P, Errors> {
-  let tokens: Tokens = run_stage1(source)?;
-  let value: P = run_stage2(source, tokens)?;
+  let tokens: Tokens = run_stage1(source).map_err(|errors| Errors::new(source, errors))?;
+  let value: P = run_stage2(source, tokens).map_err(|errors| Errors::new(source, errors))?;
   Ok(value)
 }
 
+/// Tokenizes the source, with spans as byte offsets into it rather than the char indices that parsing a `&str` gives.
 pub fn run_stage1<P: Parseable<char>>(source: &str) -> Result<P, Vec<Simple<char>>> {
-  P::parser().parse(source)
+  let eoi = source.len()..source.len() + 1;
+  let chars = source.char_indices().map(|(i, c)| (c, i..i + c.len_utf8()));
+  P::parser().parse(Stream::from_iter(eoi, chars))
 }
 
 pub fn run_stage2<P, I, T>(source: &str, tokens: I) -> Result<P, Vec<Simple<T>>>
 where P: Parseable<T>, I: IntoIterator<Item = (T, Range<usize>)>, T: Clone + Eq + Hash {
   let eoi = source.len()..source.len() + 1;
   let stream = Stream::from_iter(eoi, tokens.into_iter());
-  P::parser().parse(stream)
+  P::parser().then_ignore(end()).parse(stream)
 }
 
 pub trait Parseable<T: Clone + Eq + Hash>: Sized {
@@ -115,6 +262,13 @@ pub enum Symbol {
   Slash,
   Comma,
   Ellipsis,
+  EllipsisInclusive,
+  Exclamation,
+  LessThan,
+  LessThanOrEqual,
+  GreaterThan,
+  GreaterThanOrEqual,
+  Equal,
   SquareBracketOpen,
   SquareBracketClose,
   RoundBracketOpen,
@@ -127,6 +281,13 @@ impl Symbol {
       Self::Slash => "/",
       Self::Comma => ",",
       Self::Ellipsis => "..",
+      Self::EllipsisInclusive => "..=",
+      Self::Exclamation => "!",
+      Self::LessThan => "<",
+      Self::LessThanOrEqual => "<=",
+      Self::GreaterThan => ">",
+      Self::GreaterThanOrEqual => ">=",
+      Self::Equal => "==",
       Self::SquareBracketOpen => "[",
       Self::SquareBracketClose => "]",
       Self::RoundBracketOpen => "(",
@@ -137,10 +298,18 @@ impl Symbol {
 
 impl Parseable<char> for Symbol {
   fn parser() -> impl Parser<char, Self, Error = Simple<char>> {
+    // Symbols that are prefixes of other symbols must come after them
     choice([
       just("/").to(Self::Slash),
       just(",").to(Self::Comma),
+      just("..=").to(Self::EllipsisInclusive),
       just("..").to(Self::Ellipsis),
+      just("!").to(Self::Exclamation),
+      just("<=").to(Self::LessThanOrEqual),
+      just("<").to(Self::LessThan),
+      just(">=").to(Self::GreaterThanOrEqual),
+      just(">").to(Self::GreaterThan),
+      just("==").to(Self::Equal),
       just("[").to(Self::SquareBracketOpen),
       just("]").to(Self::SquareBracketClose),
       just("(").to(Self::RoundBracketOpen),
@@ -259,6 +428,7 @@ pub mod serde_one_or_many {
   use serde::ser::{Serialize, Serializer};
 
   #[derive(Deserialize)]
+  #[serde(untagged)]
   enum OneOrMany<T> {
     Many(Vec<T>),
     One(T)
@@ -280,3 +450,18 @@ pub mod serde_one_or_many {
     }
   }
 }
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn slugify_names() {
+    assert_eq!(slugify("Vanguard Mk. II", '-'), "vanguard-mk-ii");
+    assert_eq!(slugify("  --Shrike--  ", '-'), "shrike");
+    assert_eq!(slugify("???", '-'), "");
+    assert_eq!(slugify("Vanguard Mk. II", '_'), "vanguard_mk_ii");
+  }
+}
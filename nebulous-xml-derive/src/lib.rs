@@ -0,0 +1,557 @@
+//! Derive macros for `nebulous-xml`'s `SerializeElement` and `DeserializeElement` traits.
+//!
+//! These are re-exported by `nebulous-xml` when its `derive` feature is enabled, and should be used from there.
+//!
+//! # Container attributes
+//! - `#[xml(name = "...")]`: the name of the element, defaulting to the name of the type.
+//! - `#[xml(error = Type)]`: the deserialization error type, defaulting to `nebulous_xml::Error`.
+//!   It must implement `Locate`, as well as `From` for `nebulous_xml::Error` and for the error of every field.
+//! - `#[xml(tag = "...")]`: required on enums, the attribute that holds the name of the variant (usually `xsi:type`).
+//! - `#[xml(unknown_type = path)]`: on enums, a function converting an unrecognized variant name into an error.
+//!
+//! # Variant attributes
+//! - `#[xml(rename = "...")]`: the variant name written to the tag attribute, defaulting to the name of the variant.
+//!
+//! # Field attributes
+//! - `#[xml(rename = "...")]`: the name of the element or attribute, defaulting to the field name in `PascalCase`.
+//! - `#[xml(attribute)]`: read the field from an attribute with `FromStr`, and write it with `ToString`.
+//! - `#[xml(element)]`: the field is an element of its own, rather than the contents of an element.
+//! - `#[xml(named_elements = "...")]`: the field is a collection, each item of which is wrapped in an element with this name.
+//! - `#[xml(nil)]`: on `Option` fields, `None` is written as an element with `xsi:nil="true"` rather than being skipped.
+//! - `#[xml(default)]` or `#[xml(default = path)]`: use `Default::default()` or `path()` if the field is missing.
+//! - `#[xml(extra)]`: on a field of type `Extra`, keeps every attribute and element not claimed by another field,
+//!   and writes them back in their original positions.
+//!
+//! Fields of type `Option<T>` are skipped when serializing `None`, and deserialize as `None` when missing.
+//! Serialization is always infallible, so every field must serialize with an `Infallible` error.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, Path, PathArguments, Type};
+
+
+
+#[proc_macro_derive(DeserializeElement, attributes(xml))]
+pub fn derive_deserialize_element(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  expand_deserialize_element(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(SerializeElement, attributes(xml))]
+pub fn derive_serialize_element(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  expand_serialize_element(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+
+
+fn expand_deserialize_element(input: DeriveInput) -> syn::Result<TokenStream> {
+  let container = Container::from_attributes(&input.ident, &input.attrs)?;
+  let ident = &input.ident;
+  let name = &container.name;
+  let error = container.error.clone().unwrap_or_else(|| syn::parse_quote!(::nebulous_xml::Error));
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+  let body = match &input.data {
+    Data::Struct(data) => {
+      let fields = Field::from_fields(&data.fields)?;
+      let deserialize_fields = deserialize_fields(&error, None, &fields);
+      let idents = fields.iter().map(|field| &field.ident);
+      quote!{
+        #deserialize_fields
+        ::core::result::Result::Ok(Self { #(#idents),* })
+      }
+    },
+    Data::Enum(data) => {
+      let tag = container.expect_tag(ident)?;
+      let unknown_type = container.unknown_type.clone()
+        .unwrap_or_else(|| syn::parse_quote!(::nebulous_xml::Error::unknown_type));
+      let arms = data.variants.iter().map(|variant| {
+        let variant_ident = &variant.ident;
+        let variant_name = Variant::from_attributes(variant_ident, &variant.attrs)?.name;
+        let fields = Field::from_fields(&variant.fields)?;
+        let deserialize_fields = deserialize_fields(&error, Some(tag), &fields);
+        let idents = fields.iter().map(|field| &field.ident);
+        Ok(quote!{
+          #variant_name => {
+            #deserialize_fields
+            ::core::result::Result::Ok(Self::#variant_ident { #(#idents),* })
+          }
+        })
+      }).collect::<syn::Result<Vec<TokenStream>>>()?;
+
+      quote!{
+        let __type = match __attributes.iter().find(|(__name, _)| __name.as_ref() == #tag) {
+          ::core::option::Option::Some((_, __type)) => __type.clone(),
+          ::core::option::Option::None => return ::core::result::Result::Err(
+            ::core::convert::From::from(::nebulous_xml::Error::missing_attribute(#tag))
+          )
+        };
+
+        match __type.as_ref() {
+          #(#arms,)*
+          _ => ::core::result::Result::Err(::core::convert::From::from(#unknown_type(__type)))
+        }
+      }
+    },
+    Data::Union(..) => return Err(syn::Error::new_spanned(&input.ident, "unions are not supported"))
+  };
+
+  Ok(quote!{
+    impl #impl_generics ::nebulous_xml::DeserializeElement for #ident #ty_generics #where_clause {
+      type Error = #error;
+
+      fn deserialize_element(element: ::nebulous_xml::Element) -> ::core::result::Result<Self, Self::Error> {
+        element.located(|element| -> ::core::result::Result<Self, #error> {
+          element.expect_named(#name)?;
+          let ::nebulous_xml::Element { attributes: __attributes, children: __children, .. } = element;
+          #body
+        })
+      }
+    }
+  })
+}
+
+fn deserialize_fields(error: &Type, tag: Option<&str>, fields: &[Field]) -> TokenStream {
+  let (extra_fields, fields): (Vec<&Field>, Vec<&Field>) = fields.iter()
+    .partition(|field| matches!(field.kind, FieldKind::Extra));
+  let (attribute_fields, element_fields): (Vec<&Field>, Vec<&Field>) = fields.iter()
+    .partition(|field| matches!(field.kind, FieldKind::Attribute));
+
+  // Unrecognized attributes and elements have to be collected before the recognized ones are taken out.
+  let collect_extra = extra_fields.iter().map(|field| {
+    let ident = &field.ident;
+    let attribute_names = attribute_fields.iter().map(|field| field.name.as_str()).chain(tag);
+    let element_names = element_fields.iter().map(|field| &field.name);
+    quote!{
+      let #ident = ::nebulous_xml::Extra::collect(&__attributes, &__children, &[#(#attribute_names),*], &[#(#element_names),*]);
+    }
+  });
+
+  let find_attributes = (!attribute_fields.is_empty()).then(|| {
+    let idents = attribute_fields.iter().map(|field| &field.ident);
+    let names = attribute_fields.iter().map(|field| &field.name);
+    quote!(let [#(#idents),*] = __attributes.find_attributes([#(#names),*])?;)
+  });
+
+  let find_elements = (!element_fields.is_empty()).then(|| {
+    let idents = element_fields.iter().map(|field| &field.ident);
+    let names = element_fields.iter().map(|field| &field.name);
+    quote!(let [#(#idents),*] = __children.find_elements([#(#names),*])?;)
+  });
+
+  let fields = fields.iter().map(|field| {
+    let ident = &field.ident;
+    let name = &field.name;
+    let ty = field.value_type();
+    let (value, extract, missing) = match &field.kind {
+      FieldKind::Children => {
+        let extract = located(error, quote!(__element.children.deserialize::<#ty>()));
+        (quote!(__element), extract, quote!(missing_element))
+      },
+      FieldKind::Element => {
+        let extract = located(error, quote!(__element.deserialize::<#ty>()));
+        (quote!(__element), extract, quote!(missing_element))
+      },
+      FieldKind::NamedElements(item_name, item_ty) => {
+        let extract = located(error, quote!{
+          __element.children.deserialize_named_elements::<#item_ty, #ty>(#item_name)
+            .map_err(::nebulous_xml::DeserializeErrorWrapper::convert)
+        });
+        (quote!(__element), extract, quote!(missing_element))
+      },
+      FieldKind::Attribute => {
+        (quote!(__value), quote!(::nebulous_xml::deserialize_attribute_parse::<#ty>(__value)), quote!(missing_attribute))
+      },
+      FieldKind::Extra => unreachable!()
+    };
+
+    let expr = if field.nil {
+      quote!{
+        match #ident {
+          ::core::option::Option::Some(__element) if __element.is_nil() => ::core::option::Option::None,
+          ::core::option::Option::Some(#value) => ::core::option::Option::Some(#extract?),
+          ::core::option::Option::None => ::core::option::Option::None
+        }
+      }
+    } else if field.optional.is_some() {
+      quote!{
+        match #ident {
+          ::core::option::Option::Some(#value) => ::core::option::Option::Some(#extract?),
+          ::core::option::Option::None => ::core::option::Option::None
+        }
+      }
+    } else if let Some(default) = &field.default {
+      let default = match default {
+        Some(path) => quote!(#path()),
+        None => quote!(::core::default::Default::default())
+      };
+
+      quote!{
+        match #ident {
+          ::core::option::Option::Some(#value) => #extract?,
+          ::core::option::Option::None => #default
+        }
+      }
+    } else {
+      quote!{
+        {
+          let #value = #ident.ok_or_else(|| ::nebulous_xml::Error::#missing(#name))?;
+          #extract?
+        }
+      }
+    };
+
+    quote!(let #ident = #expr;)
+  });
+
+  quote!{
+    #(#collect_extra)*
+    #find_attributes
+    #find_elements
+    #(#fields)*
+  }
+}
+
+/// Wraps an expression deserializing `__element`, so that its errors are attributed to that element.
+fn located(error: &Type, extract: TokenStream) -> TokenStream {
+  quote!{
+    __element.located(|__element| {
+      #extract.map_err(<#error as ::core::convert::From<_>>::from)
+    })
+  }
+}
+
+
+
+fn expand_serialize_element(input: DeriveInput) -> syn::Result<TokenStream> {
+  let container = Container::from_attributes(&input.ident, &input.attrs)?;
+  let ident = &input.ident;
+  let name = &container.name;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+  let body = match &input.data {
+    Data::Struct(data) => {
+      let fields = Field::from_fields(&data.fields)?;
+      let serialize_fields = serialize_fields(name, None, &fields);
+      let idents = fields.iter().map(|field| &field.ident);
+      quote!{
+        let Self { #(#idents),* } = self;
+        #serialize_fields
+      }
+    },
+    Data::Enum(data) => {
+      let tag = container.expect_tag(ident)?;
+      let arms = data.variants.iter().map(|variant| {
+        let variant_ident = &variant.ident;
+        let variant_name = Variant::from_attributes(variant_ident, &variant.attrs)?.name;
+        let fields = Field::from_fields(&variant.fields)?;
+        let serialize_fields = serialize_fields(name, Some((tag, &variant_name)), &fields);
+        let idents = fields.iter().map(|field| &field.ident);
+        Ok(quote!{
+          Self::#variant_ident { #(#idents),* } => {
+            #serialize_fields
+          }
+        })
+      }).collect::<syn::Result<Vec<TokenStream>>>()?;
+
+      quote!{
+        match self {
+          #(#arms)*
+        }
+      }
+    },
+    Data::Union(..) => return Err(syn::Error::new_spanned(&input.ident, "unions are not supported"))
+  };
+
+  Ok(quote!{
+    impl #impl_generics ::nebulous_xml::SerializeElement for #ident #ty_generics #where_clause {
+      type Error = ::core::convert::Infallible;
+
+      fn serialize_element(self) -> ::core::result::Result<::nebulous_xml::Element, Self::Error> {
+        #body
+      }
+    }
+  })
+}
+
+fn serialize_fields(name: &str, tag: Option<(&str, &str)>, fields: &[Field]) -> TokenStream {
+  let (extra_fields, fields): (Vec<&Field>, Vec<&Field>) = fields.iter()
+    .partition(|field| matches!(field.kind, FieldKind::Extra));
+  let has_attribute_fields = fields.iter().any(|field| matches!(field.kind, FieldKind::Attribute));
+  let has_element_fields = fields.iter().any(|field| !matches!(field.kind, FieldKind::Attribute));
+  let has_extra_fields = !extra_fields.is_empty();
+
+  let extra_idents = extra_fields.iter().map(|field| &field.ident);
+  let initial_attributes = tag.map(|(tag, variant_name)| {
+    quote!((::std::boxed::Box::<str>::from(#tag), ::std::boxed::Box::<str>::from(#variant_name)))
+  });
+
+  let attributes_mut = (has_attribute_fields || has_extra_fields).then(|| quote!(mut));
+  let children_mut = (has_element_fields || has_extra_fields).then(|| quote!(mut));
+
+  let fields = fields.iter().map(|field| {
+    let ident = &field.ident;
+    let name = &field.name;
+    let push = match &field.kind {
+      FieldKind::Children => quote!{
+        __children.push(::nebulous_xml::Node::Element(::nebulous_xml::Element::new(
+          #name, ::nebulous_xml::SerializeNodes::serialize_nodes(#ident)?
+        )));
+      },
+      FieldKind::Element => quote!{
+        __children.push(::nebulous_xml::Node::Element(::nebulous_xml::SerializeElement::serialize_element(#ident)?));
+      },
+      FieldKind::NamedElements(item_name, ..) => quote!{
+        __children.push(::nebulous_xml::Node::Element(::nebulous_xml::Element::new(
+          #name, ::nebulous_xml::serialize_named_elements(#ident, #item_name)?
+        )));
+      },
+      FieldKind::Attribute => quote!{
+        __attributes.push((
+          ::std::boxed::Box::<str>::from(#name),
+          ::std::string::ToString::to_string(&#ident).into_boxed_str()
+        ));
+      },
+      FieldKind::Extra => unreachable!()
+    };
+
+    if field.nil {
+      quote!{
+        match #ident {
+          ::core::option::Option::Some(#ident) => { #push },
+          ::core::option::Option::None => {
+            __children.push(::nebulous_xml::Node::Element(::nebulous_xml::Element::with_attributes(
+              #name, ::nebulous_xml::attributes!("xsi:nil" = "true"), ::nebulous_xml::Nodes::new()
+            )));
+          }
+        };
+      }
+    } else if field.optional.is_some() {
+      quote!{
+        if let ::core::option::Option::Some(#ident) = #ident { #push };
+      }
+    } else {
+      push
+    }
+  });
+
+  quote!{
+    let #attributes_mut __attributes: ::std::vec::Vec<(::std::boxed::Box<str>, ::std::boxed::Box<str>)> = ::std::vec![#initial_attributes];
+    let #children_mut __children: ::std::vec::Vec<::nebulous_xml::Node> = ::std::vec::Vec::new();
+    #(#fields)*
+    #(#extra_idents.insert_into(&mut __attributes, &mut __children);)*
+    ::core::result::Result::Ok(::nebulous_xml::Element::with_attributes(
+      #name,
+      ::nebulous_xml::Attributes::from(__attributes),
+      ::nebulous_xml::Nodes::from(__children)
+    ))
+  }
+}
+
+
+
+struct Container {
+  name: String,
+  error: Option<Type>,
+  tag: Option<String>,
+  unknown_type: Option<Path>
+}
+
+impl Container {
+  fn from_attributes(ident: &Ident, attrs: &[Attribute]) -> syn::Result<Self> {
+    let mut container = Container { name: ident.unraw().to_string(), error: None, tag: None, unknown_type: None };
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("xml")) {
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("name") {
+          container.name = meta.value()?.parse::<LitStr>()?.value();
+        } else if meta.path.is_ident("error") {
+          container.error = Some(meta.value()?.parse::<Type>()?);
+        } else if meta.path.is_ident("tag") {
+          container.tag = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if meta.path.is_ident("unknown_type") {
+          container.unknown_type = Some(meta.value()?.parse::<Path>()?);
+        } else {
+          return Err(meta.error("unknown container attribute"));
+        };
+
+        Ok(())
+      })?;
+    };
+
+    Ok(container)
+  }
+
+  fn expect_tag(&self, ident: &Ident) -> syn::Result<&str> {
+    self.tag.as_deref().ok_or_else(|| {
+      syn::Error::new_spanned(ident, "enums require a tag attribute, such as `#[xml(tag = \"xsi:type\")]`")
+    })
+  }
+}
+
+struct Variant {
+  name: String
+}
+
+impl Variant {
+  fn from_attributes(ident: &Ident, attrs: &[Attribute]) -> syn::Result<Self> {
+    let mut variant = Variant { name: ident.unraw().to_string() };
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("xml")) {
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("rename") {
+          variant.name = meta.value()?.parse::<LitStr>()?.value();
+        } else {
+          return Err(meta.error("unknown variant attribute"));
+        };
+
+        Ok(())
+      })?;
+    };
+
+    Ok(variant)
+  }
+}
+
+enum FieldKind {
+  Children,
+  Element,
+  NamedElements(String, Box<Type>),
+  Attribute,
+  Extra
+}
+
+struct Field {
+  ident: Ident,
+  ty: Type,
+  /// The inner type of this field, if it is an `Option`.
+  optional: Option<Type>,
+  name: String,
+  kind: FieldKind,
+  nil: bool,
+  default: Option<Option<Path>>
+}
+
+impl Field {
+  fn from_fields(fields: &Fields) -> syn::Result<Vec<Self>> {
+    match fields {
+      Fields::Named(named_fields) => {
+        let fields = named_fields.named.iter().map(Field::from_field).collect::<syn::Result<Vec<Self>>>()?;
+        if fields.iter().filter(|field| matches!(field.kind, FieldKind::Extra)).count() > 1 {
+          return Err(syn::Error::new_spanned(named_fields, "only one field can be marked `extra`"));
+        };
+
+        Ok(fields)
+      },
+      Fields::Unnamed(fields) => Err(syn::Error::new_spanned(fields, "tuple fields are not supported")),
+      Fields::Unit => Ok(Vec::new())
+    }
+  }
+
+  fn from_field(field: &syn::Field) -> syn::Result<Self> {
+    let ident = field.ident.clone().expect("named field");
+    let optional = option_inner_type(&field.ty).cloned();
+    let mut name = to_pascal_case(&ident.unraw().to_string());
+    let mut attribute = false;
+    let mut extra = false;
+    let mut element = false;
+    let mut named_elements = None;
+    let mut nil = false;
+    let mut default = None;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("xml")) {
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("rename") {
+          name = meta.value()?.parse::<LitStr>()?.value();
+        } else if meta.path.is_ident("attribute") {
+          attribute = true;
+        } else if meta.path.is_ident("extra") {
+          extra = true;
+        } else if meta.path.is_ident("element") {
+          element = true;
+        } else if meta.path.is_ident("named_elements") {
+          named_elements = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if meta.path.is_ident("nil") {
+          nil = true;
+        } else if meta.path.is_ident("default") {
+          default = Some(if meta.input.peek(syn::Token![=]) {
+            Some(meta.value()?.parse::<Path>()?)
+          } else {
+            None
+          });
+        } else {
+          return Err(meta.error("unknown field attribute"));
+        };
+
+        Ok(())
+      })?;
+    };
+
+    let value_type = optional.as_ref().unwrap_or(&field.ty);
+    let kind = match (attribute, element, named_elements, extra) {
+      (false, false, None, false) => FieldKind::Children,
+      (true, false, None, false) => FieldKind::Attribute,
+      (false, true, None, false) => FieldKind::Element,
+      (false, false, None, true) => FieldKind::Extra,
+      (false, false, Some(item_name), false) => {
+        let item_type = first_type_argument(value_type).cloned().ok_or_else(|| {
+          syn::Error::new_spanned(&field.ty, "`named_elements` requires a collection type such as `Vec<T>`")
+        })?;
+
+        FieldKind::NamedElements(item_name, Box::new(item_type))
+      },
+      _ => return Err(syn::Error::new_spanned(&ident, "`attribute`, `element`, `named_elements` and `extra` are mutually exclusive"))
+    };
+
+    if nil && (optional.is_none() || matches!(kind, FieldKind::Attribute)) {
+      return Err(syn::Error::new_spanned(&ident, "`nil` requires an `Option` field that is not an attribute"));
+    };
+
+    if default.is_some() && optional.is_some() {
+      return Err(syn::Error::new_spanned(&ident, "`default` cannot be used on `Option` fields"));
+    };
+
+    if matches!(kind, FieldKind::Extra) && (optional.is_some() || nil || default.is_some()) {
+      return Err(syn::Error::new_spanned(&ident, "`extra` cannot be combined with `Option`, `nil` or `default`"));
+    };
+
+    Ok(Field { ident, ty: field.ty.clone(), optional, name, kind, nil, default })
+  }
+
+  fn value_type(&self) -> &Type {
+    self.optional.as_ref().unwrap_or(&self.ty)
+  }
+}
+
+fn option_inner_type(ty: &Type) -> Option<&Type> {
+  match ty {
+    Type::Path(type_path) if type_path.qself.is_none() => {
+      let segment = type_path.path.segments.last()?;
+      if segment.ident == "Option" { first_type_argument(ty) } else { None }
+    },
+    _ => None
+  }
+}
+
+fn first_type_argument(ty: &Type) -> Option<&Type> {
+  let Type::Path(type_path) = ty else { return None };
+  let PathArguments::AngleBracketed(arguments) = &type_path.path.segments.last()?.arguments else { return None };
+  arguments.args.iter().find_map(|argument| match argument {
+    GenericArgument::Type(ty) => Some(ty),
+    _ => None
+  })
+}
+
+fn to_pascal_case(s: &str) -> String {
+  s.split('_')
+    .flat_map(|word| {
+      let mut chars = word.chars();
+      chars.next().map(|first| first.to_uppercase().chain(chars))
+        .into_iter().flatten()
+    })
+    .collect()
+}
@@ -1,8 +1,19 @@
 //! XML Parser/Encoder wrapper and deserialization/serialization framework.
 
+// Allows the derive macros to refer to this crate by name from within it.
+extern crate self as nebulous_xml;
+#[cfg(feature = "derive")]
+extern crate nebulous_xml_derive;
 #[cfg(feature = "uuid")]
 pub extern crate uuid;
 
+pub mod pull;
+#[cfg(feature = "serde")]
+pub mod serde;
+
+#[cfg(feature = "derive")]
+pub use nebulous_xml_derive::{DeserializeElement, SerializeElement};
+
 use quick_xml::reader::Reader;
 use quick_xml::writer::Writer;
 use quick_xml::encoding::Decoder;
@@ -12,7 +23,9 @@ use quick_xml::events::{Event, BytesStart, BytesEnd, BytesText, BytesDecl};
 use quick_xml::events::attributes::{Attribute, Attributes as AttributesIter};
 use thiserror::Error;
 
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::fmt;
 use std::io::{BufRead, Write};
 use std::iter::Filter;
 use std::ops::{Deref, DerefMut};
@@ -41,6 +54,13 @@ pub trait DeserializeElement: Sized {
   type Error;
 
   fn deserialize_element(element: Element) -> Result<Self, Self::Error>;
+
+  /// Deserializes an element that is being read by a [`PullReader`](pull::PullReader).
+  /// By default the whole element is read first, implementations may override this to read only what they need.
+  fn pull_element<R: BufRead>(element: pull::ElementReader<'_, R>) -> Result<Self, Self::Error>
+  where Self::Error: From<Error> {
+    element.read_element().map_err(Self::Error::from).and_then(Self::deserialize_element)
+  }
 }
 
 impl DeserializeNodes for Nodes {
@@ -208,6 +228,11 @@ where T::Err: std::error::Error + Send + Sync + 'static {
     })
 }
 
+pub fn deserialize_attribute_parse<T: FromStr>(value: Box<str>) -> Result<T, Error>
+where T::Err: std::error::Error + Send + Sync + 'static {
+  value.parse().map_err(|err| Error::ParseError(Box::new(err), value.into_string()))
+}
+
 #[macro_export]
 macro_rules! impl_deserialize_nodes_parse {
   ($($Type:ty),* $(,)?) => {
@@ -348,20 +373,23 @@ impl From<Element> for Node {
   }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct Element {
   pub name: Box<str>,
   pub attributes: Attributes,
-  pub children: Nodes
+  pub children: Nodes,
+  /// Where this element was read from, if it was read by [`read_nodes`].
+  /// This is not considered when comparing elements.
+  pub location: Option<Box<Location>>
 }
 
 impl Element {
   pub fn new(name: impl Into<Box<str>>, children: impl Into<Nodes>) -> Self {
-    Element { name: name.into(), attributes: Attributes::new(), children: children.into() }
+    Element { name: name.into(), attributes: Attributes::new(), children: children.into(), location: None }
   }
 
   pub fn with_attributes(name: impl Into<Box<str>>, attributes: Attributes, children: impl Into<Nodes>) -> Self {
-    Element { name: name.into(), attributes, children: children.into() }
+    Element { name: name.into(), attributes, children: children.into(), location: None }
   }
 
   #[inline]
@@ -369,9 +397,74 @@ impl Element {
     T::deserialize_element(self)
   }
 
+  /// Runs `f` on this element, attributing any error it returns to this element's location,
+  /// unless the error was already attributed to a more specific location.
+  pub fn located<T, E, F>(mut self, f: F) -> Result<T, E>
+  where E: Locate, F: FnOnce(Element) -> Result<T, E> {
+    match self.location.take() {
+      Some(location) => f(self).map_err(|error| error.locate(&location)),
+      None => f(self)
+    }
+  }
+
   pub fn expect_named(&self, name: &str) -> Result<(), Error> {
     if self.name.as_ref() != name { Err(Error::UnexpectedElementExpectedElement(self.clone(), name.into())) } else { Ok(()) }
   }
+
+  /// Returns true if this element is marked with `xsi:nil="true"`.
+  pub fn is_nil(&self) -> bool {
+    self.attributes.iter().any(|(name, value)| name.as_ref() == "xsi:nil" && value.as_ref() == "true")
+  }
+}
+
+impl PartialEq for Element {
+  fn eq(&self, other: &Self) -> bool {
+    self.name == other.name && self.attributes == other.attributes && self.children == other.children
+  }
+}
+
+impl Eq for Element {}
+
+/// A position within an XML document. Lines and columns start at 1, and columns are counted in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+  pub offset: usize,
+  pub line: usize,
+  pub column: usize
+}
+
+impl fmt::Display for Position {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}, column {}", self.line, self.column)
+  }
+}
+
+/// The path and position of an element within an XML document.
+///
+/// Paths are made of element names separated by `/`, where elements that share their name
+/// with a sibling are numbered starting from 1, such as `Fleet/Ships/Ship[3]/SocketMap`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+  pub path: Box<str>,
+  pub position: Position
+}
+
+impl fmt::Display for Location {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} ({})", self.path, self.position)
+  }
+}
+
+/// An error which can be attributed to the location of the element it occurred in.
+pub trait Locate: Sized {
+  /// Attributes this error to the given location, unless it has already been attributed to one.
+  fn locate(self, location: &Location) -> Self;
+}
+
+impl Locate for Infallible {
+  fn locate(self, _: &Location) -> Self {
+    self
+  }
 }
 
 #[repr(transparent)]
@@ -477,12 +570,12 @@ impl Nodes {
   }
 
   #[inline]
-  pub fn iter(&self) -> IterNodesRef {
+  pub fn iter(&self) -> IterNodesRef<'_> {
     self.into_iter()
   }
 
   #[inline]
-  pub fn iter_mut(&mut self) -> IterNodesMut {
+  pub fn iter_mut(&mut self) -> IterNodesMut<'_> {
     self.into_iter()
   }
 
@@ -492,12 +585,12 @@ impl Nodes {
   }
 
   #[inline]
-  pub fn iter_raw(&self) -> SliceIter<Node> {
+  pub fn iter_raw(&self) -> SliceIter<'_, Node> {
     self.nodes.iter()
   }
 
   #[inline]
-  pub fn iter_mut_raw(&mut self) -> SliceIterMut<Node> {
+  pub fn iter_mut_raw(&mut self) -> SliceIterMut<'_, Node> {
     self.nodes.iter_mut()
   }
 }
@@ -662,6 +755,74 @@ impl DerefMut for Attributes {
   }
 }
 
+/// The attributes and child elements of an element that were not recognized when it was deserialized,
+/// kept so that they can be written back out in their original positions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extra {
+  /// Unrecognized attributes, in their original order.
+  pub attributes: Vec<(Box<str>, Box<str>)>,
+  /// Unrecognized child elements in their original order,
+  /// each paired with the name of the recognized element that preceded it, if there was one.
+  pub elements: Vec<(Option<Box<str>>, Element)>
+}
+
+impl Extra {
+  pub const fn new() -> Self {
+    Extra { attributes: Vec::new(), elements: Vec::new() }
+  }
+
+  /// Collects every attribute and child element whose name is not in the given lists of known names.
+  pub fn collect(attributes: &Attributes, children: &Nodes, known_attributes: &[&str], known_elements: &[&str]) -> Self {
+    let attributes = attributes.iter()
+      .filter(|(name, _)| !known_attributes.contains(&name.as_ref()))
+      .cloned().collect();
+
+    let mut elements = Vec::new();
+    let mut previous = None;
+    for node in children.iter_raw() {
+      let Node::Element(element) = node else { continue };
+      if known_elements.contains(&element.name.as_ref()) {
+        previous = Some(element.name.clone());
+      } else {
+        elements.push((previous.clone(), element.clone()));
+      };
+    };
+
+    Extra { attributes, elements }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.attributes.is_empty() && self.elements.is_empty()
+  }
+
+  /// Returns true if an unrecognized child element with this name was kept.
+  pub fn contains_element(&self, name: &str) -> bool {
+    self.elements.iter().any(|(_, element)| element.name.as_ref() == name)
+  }
+
+  /// Adds these attributes and elements to the serialized attributes and children of an element.
+  /// Elements are placed after the element they originally followed, or at the end if it is no longer present.
+  pub fn insert_into(self, attributes: &mut Vec<(Box<str>, Box<str>)>, children: &mut Vec<Node>) {
+    attributes.extend(self.attributes);
+
+    let mut last_inserted: Option<(Option<Box<str>>, usize)> = None;
+    for (previous, element) in self.elements {
+      let index = match last_inserted {
+        Some((last_previous, index)) if last_previous == previous => index + 1,
+        _ => match &previous {
+          Some(name) => children.iter()
+            .position(|node| matches!(node, Node::Element(element) if element.name == *name))
+            .map_or(children.len(), |index| index + 1),
+          None => 0
+        }
+      };
+
+      children.insert(index, Node::Element(element));
+      last_inserted = Some((previous, index));
+    };
+  }
+}
+
 
 
 pub fn assert_roundtrip_nodes<T>(value: &T)
@@ -769,25 +930,34 @@ pub fn push_element_recursive<W: Write>(writer: &mut Writer<W>, element: &Elemen
   Ok(())
 }
 
-pub fn read_nodes<R: BufRead>(reader: R) -> Result<Nodes, Error> {
-  pull_nodes_recursive(&mut Reader::from_reader(reader), &mut Vec::new()).map_err(From::from)
+/// Reads a list of nodes from an XML document, recording the [`Location`] of every element.
+pub fn read_nodes<R: BufRead>(mut reader: R) -> Result<Nodes, Error> {
+  let mut source = Vec::new();
+  reader.read_to_end(&mut source).map_err(quick_xml::Error::from)?;
+  let line_starts = LineStarts::new(&source);
+  let mut nodes = pull_nodes_recursive(&mut Reader::from_reader(source.as_slice()), &mut Vec::new(), &line_starts)?;
+  resolve_paths_recursive(&mut nodes, "");
+  Ok(nodes)
 }
 
-fn pull_nodes_recursive<R: BufRead>(reader: &mut Reader<R>, buf: &mut Vec<u8>) -> Result<Nodes, quick_xml::Error> {
+fn pull_nodes_recursive<R: BufRead>(reader: &mut Reader<R>, buf: &mut Vec<u8>, line_starts: &LineStarts) -> Result<Nodes, quick_xml::Error> {
   let mut nodes = Vec::new();
   loop {
+    let position = line_starts.position(reader.buffer_position());
     match reader.read_event_into(buf)? {
       Event::Start(event) => {
         let name = resolve_name(event.name(), reader.decoder())?;
         let attributes = resolve_attributes(event.attributes(), reader.decoder())?;
-        let children = pull_nodes_recursive(reader, buf)?;
-        nodes.push(Node::Element(Element::with_attributes(name, attributes, children)));
+        let children = pull_nodes_recursive(reader, buf, line_starts)?;
+        let location = Location { path: Box::default(), position };
+        nodes.push(Node::Element(Element { name, attributes, children, location: Some(Box::new(location)) }));
       },
       Event::End(..) => break,
       Event::Empty(event) => {
         let name = resolve_name(event.name(), reader.decoder())?;
         let attributes = resolve_attributes(event.attributes(), reader.decoder())?;
-        nodes.push(Node::Element(Element::with_attributes(name, attributes, Nodes::default())));
+        let location = Location { path: Box::default(), position };
+        nodes.push(Node::Element(Element { name, attributes, children: Nodes::default(), location: Some(Box::new(location)) }));
       },
       Event::Text(mut event) => {
         event.inplace_trim_start();
@@ -810,6 +980,53 @@ fn pull_nodes_recursive<R: BufRead>(reader: &mut Reader<R>, buf: &mut Vec<u8>) -
   Ok(Nodes::from(nodes))
 }
 
+/// Fills in the paths of every element's location, now that each element's siblings are known.
+fn resolve_paths_recursive(nodes: &mut Nodes, parent_path: &str) {
+  let mut name_counts = HashMap::<Box<str>, usize>::new();
+  for node in nodes.iter_raw() {
+    if let Node::Element(element) = node {
+      *name_counts.entry(element.name.clone()).or_insert(0) += 1;
+    };
+  };
+
+  let mut name_indices = HashMap::<Box<str>, usize>::new();
+  for node in nodes.iter_mut_raw() {
+    let Node::Element(element) = node else { continue };
+    let segment = if name_counts[&element.name] > 1 {
+      let index = name_indices.entry(element.name.clone()).or_insert(0);
+      *index += 1;
+      format!("{}[{index}]", element.name)
+    } else {
+      element.name.to_string()
+    };
+
+    let path = if parent_path.is_empty() { segment } else { format!("{parent_path}/{segment}") };
+    resolve_paths_recursive(&mut element.children, &path);
+    if let Some(location) = element.location.as_mut() {
+      location.path = path.into_boxed_str();
+    };
+  };
+}
+
+/// The byte offset of the start of every line in a document, for converting offsets into lines and columns.
+struct LineStarts {
+  line_starts: Vec<usize>
+}
+
+impl LineStarts {
+  fn new(source: &[u8]) -> Self {
+    let line_starts = std::iter::once(0)
+      .chain(source.iter().enumerate().filter(|&(_, &b)| b == b'\n').map(|(i, _)| i + 1))
+      .collect();
+    LineStarts { line_starts }
+  }
+
+  fn position(&self, offset: usize) -> Position {
+    let line = self.line_starts.partition_point(|&line_start| line_start <= offset);
+    Position { offset, line, column: offset - self.line_starts[line - 1] + 1 }
+  }
+}
+
 fn resolve_name(name: QName, decoder: Decoder) -> Result<Box<str>, quick_xml::Error> {
   decoder.decode(name.into_inner()).map(|name| name.into_owned().into_boxed_str())
 }
@@ -847,12 +1064,18 @@ pub enum Error {
   MissingElement(Box<str>),
   #[error("missing attribute {:?}", .0)]
   MissingAttribute(Box<str>),
+  #[error("unknown type {:?}", .0)]
+  UnknownType(Box<str>),
   #[error("incorrect nodes count: found {}, expected {}", .0.len(), .1)]
   IncorrectNodesCount(Vec<Node>, usize),
   #[error("incorrect elements count: found {}, expected {}", .0.len(), .1)]
   IncorrectElementsCount(Vec<Element>, usize),
   #[error("failed to parse {1:?}: {0}")]
-  ParseError(Box<dyn std::error::Error + Send + Sync + 'static>, String)
+  ParseError(Box<dyn std::error::Error + Send + Sync + 'static>, String),
+  #[error("{0}")]
+  Custom(Box<str>),
+  #[error("{1} at {0}")]
+  Located(Box<Location>, Box<Error>)
 }
 
 impl Error {
@@ -883,6 +1106,29 @@ impl Error {
   pub fn missing_attribute(name: impl Into<Box<str>>) -> Self {
     Error::MissingAttribute(name.into())
   }
+
+  pub fn unknown_type(name: impl Into<Box<str>>) -> Self {
+    Error::UnknownType(name.into())
+  }
+
+  /// The location of the element this error occurred in, if it is known.
+  pub fn location(&self) -> Option<&Location> {
+    if let Error::Located(location, _) = self { Some(location) } else { None }
+  }
+
+  /// This error without its location.
+  pub fn unlocated(&self) -> &Error {
+    if let Error::Located(_, error) = self { error.unlocated() } else { self }
+  }
+}
+
+impl Locate for Error {
+  fn locate(self, location: &Location) -> Self {
+    match self {
+      Error::Located(..) => self,
+      error => Error::Located(Box::new(location.clone()), Box::new(error))
+    }
+  }
 }
 
 impl From<quick_xml::events::attributes::AttrError> for Error {
@@ -899,6 +1145,15 @@ pub enum DeserializeErrorWrapper<E> {
   Error(#[from] Error)
 }
 
+impl<E> Locate for DeserializeErrorWrapper<E> where E: Locate {
+  fn locate(self, location: &Location) -> Self {
+    match self {
+      Self::Inner(inner) => Self::Inner(inner.locate(location)),
+      Self::Error(error) => Self::Error(error.locate(location))
+    }
+  }
+}
+
 impl<E> DeserializeErrorWrapper<E> {
   pub fn convert(self) -> E where E: From<Error> {
     match self {
@@ -922,3 +1177,88 @@ impl<E> TryFrom<DeserializeErrorWrapper<E>> for Error {
 fn is_whitespace(s: &str) -> bool {
   s.chars().all(|c| c.is_whitespace())
 }
+
+
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+  use super::*;
+
+  #[derive(Debug, Clone, PartialEq, DeserializeElement, SerializeElement)]
+  #[xml(name = "Thing")]
+  struct Thing {
+    #[xml(attribute)]
+    name: String,
+    count: usize,
+    #[xml(default)]
+    weight: u32,
+    note: Option<String>,
+    #[xml(nil)]
+    parent: Option<usize>,
+    #[xml(rename = "Tags", named_elements = "string")]
+    tag_list: Vec<String>,
+    #[xml(element)]
+    shape: Shape
+  }
+
+  #[derive(Debug, Clone, PartialEq, DeserializeElement, SerializeElement)]
+  #[xml(name = "Shape", tag = "xsi:type")]
+  enum Shape {
+    Circle { radius: f32 },
+    #[xml(rename = "Empty")]
+    Nothing
+  }
+
+  #[test]
+  fn test_derive_roundtrip() {
+    let thing = Thing {
+      name: "First".to_owned(),
+      count: 3,
+      weight: 7,
+      note: None,
+      parent: None,
+      tag_list: vec!["a".to_owned(), "b".to_owned()],
+      shape: Shape::Circle { radius: 1.5 }
+    };
+
+    let element = thing.clone().serialize_element().unwrap();
+    assert_eq!(element.attributes, attributes!("Name" = "First"));
+    let names = element.children.iter().map(|node| match node {
+      Node::Element(element) => element.name.as_ref(),
+      Node::Text(..) => panic!("unexpected text")
+    }).collect::<Vec<&str>>();
+    assert_eq!(names, ["Count", "Weight", "Parent", "Tags", "Shape"]);
+
+    assert_roundtrip_element(&thing);
+    assert_roundtrip_element(&Thing { note: Some("Hello".to_owned()), parent: Some(1), shape: Shape::Nothing, ..thing });
+  }
+
+  #[test]
+  fn test_derive_errors() {
+    let nodes = read_nodes(r#"<Thing Name="Second"><Count>1</Count><Parent xsi:nil="true" /><Tags /><Shape xsi:type="Empty" /></Thing>"#.as_bytes()).unwrap();
+    let thing = nodes.try_into_one_element().unwrap().deserialize::<Thing>().unwrap();
+    assert_eq!((thing.weight, thing.parent, thing.shape), (0, None, Shape::Nothing));
+
+    let nodes = read_nodes(r#"<Thing Name="Third"><Tags /><Shape xsi:type="Empty" /></Thing>"#.as_bytes()).unwrap();
+    let error = nodes.try_into_one_element().unwrap().deserialize::<Thing>().unwrap_err();
+    assert!(matches!(error.unlocated(), Error::MissingElement(name) if name.as_ref() == "Count"));
+
+    let nodes = read_nodes(r#"<Shape xsi:type="Square" />"#.as_bytes()).unwrap();
+    let error = nodes.try_into_one_element().unwrap().deserialize::<Shape>().unwrap_err();
+    assert!(matches!(error.unlocated(), Error::UnknownType(name) if name.as_ref() == "Square"));
+  }
+
+  #[test]
+  fn test_error_location() {
+    let source = "<Things>\n  <Thing Name=\"A\">\n    <Count>1</Count><Tags /><Shape xsi:type=\"Empty\" />\n  </Thing>\n  <Thing Name=\"B\">\n    <Count>x</Count><Tags /><Shape xsi:type=\"Empty\" />\n  </Thing>\n</Things>";
+    let things = read_nodes(source.as_bytes()).unwrap().try_into_one_element().unwrap();
+    let error = things.children.deserialize::<Vec<Thing>>().unwrap_err();
+    let error = Error::try_from(error).unwrap_or_else(|error| error);
+
+    let location = error.location().unwrap();
+    assert_eq!(location.path.as_ref(), "Things/Thing[2]/Count");
+    assert_eq!(location.position, Position { offset: 117, line: 6, column: 5 });
+    assert!(matches!(error.unlocated(), Error::ParseError(_, text) if text == "x"));
+    assert_eq!(error.to_string(), r#"failed to parse "x": invalid digit found in string at Things/Thing[2]/Count (line 6, column 5)"#);
+  }
+}
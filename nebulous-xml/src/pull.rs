@@ -0,0 +1,242 @@
+//! Pull-based reading of XML documents, for deserializing without first building a whole [`Nodes`] tree.
+//!
+//! A [`PullReader`] hands out an [`ElementReader`] for the root element, which yields its children one at a time.
+//! Children that are not fully read are skipped over when the next child is requested,
+//! so a [`DeserializeElement::pull_element`] implementation only pays for the parts of a document it looks at.
+//! Elements read this way do not record their [`Location`](crate::Location).
+//!
+//! [`Nodes`]: crate::Nodes
+
+use crate::{resolve_attributes, resolve_name, Attributes, DeserializeElement, Element, Error, Node};
+
+use quick_xml::reader::Reader;
+use quick_xml::events::Event;
+
+use std::io::BufRead;
+
+
+
+/// Reads the root element of an XML document and deserializes it, reading it lazily if `T` supports that.
+pub fn pull_root<T, R>(reader: R) -> Result<T, T::Error>
+where T: DeserializeElement, T::Error: From<Error>, R: BufRead {
+  PullReader::new(reader).root()?.deserialize::<T>()
+}
+
+pub struct PullReader<R> {
+  reader: Reader<R>,
+  buf: Vec<u8>,
+  /// The number of elements that have been started but not yet ended.
+  depth: usize
+}
+
+impl<R: BufRead> PullReader<R> {
+  pub fn new(reader: R) -> Self {
+    PullReader { reader: Reader::from_reader(reader), buf: Vec::new(), depth: 0 }
+  }
+
+  /// Reads up to the root element of the document.
+  pub fn root(&mut self) -> Result<ElementReader<'_, R>, Error> {
+    loop {
+      match self.pull()? {
+        Pulled::Start(name, attributes) => return Ok(ElementReader::new(self, name, attributes, false)),
+        Pulled::Empty(name, attributes) => return Ok(ElementReader::new(self, name, attributes, true)),
+        Pulled::Text(text) if crate::is_whitespace(&text) => continue,
+        Pulled::Text(text) => return Err(Error::unexpected_text(text)),
+        Pulled::End | Pulled::Eof => return Err(Error::IncorrectElementsCount(Vec::new(), 1))
+      };
+    };
+  }
+
+  fn pull(&mut self) -> Result<Pulled, Error> {
+    loop {
+      self.buf.clear();
+      let decoder = self.reader.decoder();
+      return Ok(match self.reader.read_event_into(&mut self.buf)? {
+        Event::Start(event) => {
+          self.depth += 1;
+          Pulled::Start(resolve_name(event.name(), decoder)?, resolve_attributes(event.attributes(), decoder)?)
+        },
+        Event::End(..) => {
+          self.depth = self.depth.saturating_sub(1);
+          Pulled::End
+        },
+        Event::Empty(event) => Pulled::Empty(resolve_name(event.name(), decoder)?, resolve_attributes(event.attributes(), decoder)?),
+        Event::Text(mut event) => {
+          event.inplace_trim_start();
+          event.inplace_trim_end();
+          Pulled::Text(event.unescape()?.into_owned())
+        },
+        Event::CData(event) => Pulled::Text(decoder.decode(event.into_inner().as_ref())?.into_owned()),
+        // Ignored, as in `read_nodes`
+        Event::Comment(..) | Event::Decl(..) | Event::PI(..) | Event::DocType(..) => continue,
+        Event::Eof => Pulled::Eof
+      });
+    };
+  }
+
+  /// Reads one event without decoding it, returning false once the end of the document is reached.
+  fn skip_event(&mut self) -> Result<bool, Error> {
+    self.buf.clear();
+    match self.reader.read_event_into(&mut self.buf)? {
+      Event::Start(..) => self.depth += 1,
+      Event::End(..) => self.depth = self.depth.saturating_sub(1),
+      Event::Eof => return Ok(false),
+      _ => ()
+    };
+
+    Ok(true)
+  }
+}
+
+enum Pulled {
+  Start(Box<str>, Attributes),
+  Empty(Box<str>, Attributes),
+  Text(String),
+  End,
+  Eof
+}
+
+/// A child node of an element being read by an [`ElementReader`].
+pub enum PullNode<'r, R> {
+  Text(String),
+  Element(ElementReader<'r, R>)
+}
+
+/// An element whose name and attributes have been read, but whose children have not.
+pub struct ElementReader<'r, R> {
+  pub name: Box<str>,
+  pub attributes: Attributes,
+  reader: &'r mut PullReader<R>,
+  /// The depth of this element's children.
+  depth: usize,
+  finished: bool
+}
+
+impl<'r, R: BufRead> ElementReader<'r, R> {
+  fn new(reader: &'r mut PullReader<R>, name: Box<str>, attributes: Attributes, finished: bool) -> Self {
+    let depth = reader.depth;
+    ElementReader { name, attributes, reader, depth, finished }
+  }
+
+  pub fn expect_named(&self, name: &str) -> Result<(), Error> {
+    if self.name.as_ref() != name {
+      Err(Error::UnexpectedElementExpectedElement(Element::with_attributes(self.name.clone(), self.attributes.clone(), Vec::new()), name.into()))
+    } else {
+      Ok(())
+    }
+  }
+
+  /// Reads the next child node of this element, skipping whatever was left unread of the previous child.
+  pub fn next_child(&mut self) -> Result<Option<PullNode<'_, R>>, Error> {
+    Ok(match self.pull_child()? {
+      Some(Pulled::Start(name, attributes)) => Some(PullNode::Element(ElementReader::new(self.reader, name, attributes, false))),
+      Some(Pulled::Empty(name, attributes)) => Some(PullNode::Element(ElementReader::new(self.reader, name, attributes, true))),
+      Some(Pulled::Text(text)) => Some(PullNode::Text(text)),
+      Some(Pulled::End | Pulled::Eof) | None => None
+    })
+  }
+
+  /// Reads the next child element of this element, ignoring whitespace.
+  /// If any non-whitespace text is encountered, it will be returned as an error.
+  pub fn next_element(&mut self) -> Result<Option<ElementReader<'_, R>>, Error> {
+    loop {
+      return Ok(match self.pull_child()? {
+        Some(Pulled::Start(name, attributes)) => Some(ElementReader::new(self.reader, name, attributes, false)),
+        Some(Pulled::Empty(name, attributes)) => Some(ElementReader::new(self.reader, name, attributes, true)),
+        Some(Pulled::Text(text)) if crate::is_whitespace(&text) => continue,
+        Some(Pulled::Text(text)) => return Err(Error::unexpected_text(text)),
+        Some(Pulled::End | Pulled::Eof) | None => None
+      });
+    };
+  }
+
+  fn pull_child(&mut self) -> Result<Option<Pulled>, Error> {
+    if self.finished { return Ok(None) };
+    while self.reader.depth > self.depth {
+      if !self.reader.skip_event()? { break };
+    };
+
+    let pulled = self.reader.pull()?;
+    if let Pulled::End | Pulled::Eof = pulled {
+      self.finished = true;
+      return Ok(None);
+    };
+
+    Ok(Some(pulled))
+  }
+
+  /// Skips the rest of this element without decoding it.
+  pub fn skip(self) -> Result<(), Error> {
+    if !self.finished {
+      while self.reader.depth >= self.depth {
+        if !self.reader.skip_event()? { break };
+      };
+    };
+
+    Ok(())
+  }
+
+  /// Reads the rest of this element into an [`Element`].
+  pub fn read_element(mut self) -> Result<Element, Error> {
+    let mut children = Vec::new();
+    while let Some(node) = self.next_child()? {
+      children.push(match node {
+        PullNode::Text(text) => Node::Text(text),
+        PullNode::Element(element) => Node::Element(element.read_element()?)
+      });
+    };
+
+    Ok(Element::with_attributes(self.name, self.attributes, children))
+  }
+
+  /// Reads the rest of this element as text.
+  pub fn read_text(self) -> Result<String, Error> {
+    self.read_element()?.children.try_into_text().map_err(Error::unexpected_element_expected_text)
+  }
+
+  /// Deserializes this element, reading it lazily if `T` supports that.
+  #[inline]
+  pub fn deserialize<T>(self) -> Result<T, T::Error>
+  where T: DeserializeElement, T::Error: From<Error> {
+    T::pull_element(self)
+  }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::read_nodes;
+
+  const DOCUMENT: &str = "<?xml version=\"1.0\"?>\n<Root A=\"1\">\n  <First>one &amp; two</First>\n  <!-- comment -->\n  \
+    <Skipped><Deep><Deeper /></Deep><Deep>text</Deep></Skipped>\n  <Empty />\n  <Last><![CDATA[<raw>]]></Last>\n</Root>\n";
+
+  #[test]
+  fn test_pull_read_element() {
+    let element = PullReader::new(DOCUMENT.as_bytes()).root().unwrap().read_element().unwrap();
+    let expected = read_nodes(DOCUMENT.as_bytes()).unwrap().try_into_one_element().unwrap();
+    assert_eq!(element, expected);
+  }
+
+  #[test]
+  fn test_pull_skip() {
+    let mut reader = PullReader::new(DOCUMENT.as_bytes());
+    let mut root = reader.root().unwrap();
+    assert_eq!(root.attributes.as_ref(), [("A".into(), "1".into())]);
+
+    let mut names = Vec::new();
+    while let Some(mut element) = root.next_element().unwrap() {
+      names.push(element.name.clone());
+      match element.name.as_ref() {
+        "First" => assert_eq!(element.read_text().unwrap(), "one & two"),
+        // Only partially read, the rest is skipped by the next call to `next_element`
+        "Skipped" => assert_eq!(element.next_element().unwrap().unwrap().name.as_ref(), "Deep"),
+        "Empty" => assert!(element.next_child().unwrap().is_none()),
+        _ => element.skip().unwrap()
+      };
+    };
+
+    assert_eq!(names, ["First", "Skipped", "Empty", "Last"].map(Box::<str>::from));
+  }
+}
@@ -0,0 +1,914 @@
+//! A [`serde`](::serde) serializer and deserializer over [`Element`]s, following the game's XML conventions.
+//!
+//! - Structs are written as one child element per field, named after the field in `PascalCase`
+//!   (except single-letter names, which the game keeps lowercase for vectors and colors).
+//!   Fields whose names start with `@` are written as attributes instead.
+//!   Maps are written the same way, with their keys as they are.
+//! - Sequences are written as one child element per item, named after the item's type,
+//!   using the XML schema names for primitives (`int`, `string`, `float`, ...).
+//! - `None` is written as an empty element marked `xsi:nil="true"`.
+//! - Unit enum variants are written as text, other variants are written like structs,
+//!   marked with the variant's name in `PascalCase` in an `xsi:type` attribute.
+//!   Internally tagged enums should be tagged with `type`, since a field named `type` is written as `xsi:type`.
+//! - Newtype structs are transparent.
+//!
+//! When reading, names are matched against the fields and variants serde expects, whether they are in `PascalCase` or not.
+//! Internally tagged and untagged enums are read through `deserialize_any`, where serde does not say what it expects,
+//! so their contents are guessed at: element names and tags are converted to `snake_case`, elements whose children
+//! share a name are read as sequences, and text that looks like a boolean or a number is read as one.
+//! Strings that look like numbers, maps and externally tagged enums with fields cannot be read inside them.
+//!
+//! [`Serde`] adapts any serde type to [`SerializeElement`] and [`DeserializeElement`].
+//!
+//! This module also implements serde's traits for [`Extra`], writing each unrecognized element as an XML fragment,
+//! so that other formats can carry them too.
+
+use crate::{read_nodes, write_nodes, DeserializeElement, Element, Error, Extra, Node, Nodes, SerializeElement};
+
+use ::serde::de::{self, DeserializeOwned, DeserializeSeed, Visitor};
+use ::serde::de::value::StringDeserializer;
+use ::serde::ser::{self, Serializer};
+use ::serde::{Deserialize, Deserializer, Serialize};
+
+use std::fmt;
+use std::str::FromStr;
+use std::vec::IntoIter as VecIntoIter;
+
+
+
+/// Serializes a value as an element named after its type.
+pub fn to_element<T: Serialize + ?Sized>(value: &T) -> Result<Element, Error> {
+  value.serialize(ValueSerializer).map(|value| {
+    let name = value.type_name.clone();
+    value.into_element(name)
+  })
+}
+
+/// Serializes a value as an element with the given name.
+pub fn to_element_named<T: Serialize + ?Sized>(value: &T, name: &str) -> Result<Element, Error> {
+  value.serialize(ValueSerializer).map(|value| value.into_element(name))
+}
+
+/// Deserializes a value from an element, ignoring the element's name.
+pub fn from_element<T: DeserializeOwned>(element: Element) -> Result<T, Error> {
+  element.located(|element| T::deserialize(ElementDeserializer::new(element)))
+}
+
+/// Wraps a serde type so that it can be used wherever [`SerializeElement`] or [`DeserializeElement`] is expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Serde<T>(pub T);
+
+impl<T: DeserializeOwned> DeserializeElement for Serde<T> {
+  type Error = Error;
+
+  fn deserialize_element(element: Element) -> Result<Self, Self::Error> {
+    from_element(element).map(Serde)
+  }
+}
+
+impl<T: Serialize> SerializeElement for Serde<T> {
+  type Error = Error;
+
+  fn serialize_element(self) -> Result<Element, Self::Error> {
+    to_element(&self.0)
+  }
+}
+
+impl ser::Error for Error {
+  fn custom<T: fmt::Display>(msg: T) -> Self {
+    Error::Custom(msg.to_string().into_boxed_str())
+  }
+}
+
+impl de::Error for Error {
+  fn custom<T: fmt::Display>(msg: T) -> Self {
+    Error::Custom(msg.to_string().into_boxed_str())
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExtraRepr {
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  attributes: Vec<(Box<str>, Box<str>)>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  elements: Vec<ExtraElementRepr>
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExtraElementRepr {
+  after: Option<Box<str>>,
+  xml: String
+}
+
+impl Serialize for Extra {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let elements = self.elements.iter()
+      .map(|(after, element)| {
+        let mut xml = Vec::new();
+        write_nodes(&mut xml, &Nodes::new_one(element.clone()), None, None).map_err(ser::Error::custom)?;
+        let xml = String::from_utf8(xml).map_err(ser::Error::custom)?;
+        Ok(ExtraElementRepr { after: after.clone(), xml })
+      })
+      .collect::<Result<Vec<ExtraElementRepr>, S::Error>>()?;
+    ExtraRepr { attributes: self.attributes.clone(), elements }.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Extra {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let ExtraRepr { attributes, elements } = ExtraRepr::deserialize(deserializer)?;
+    let elements = elements.into_iter()
+      .map(|ExtraElementRepr { after, xml }| {
+        let element = read_nodes(xml.as_bytes()).and_then(Nodes::try_into_one_element).map_err(de::Error::custom)?;
+        Ok((after, element))
+      })
+      .collect::<Result<Vec<_>, D::Error>>()?;
+    Ok(Extra { attributes, elements })
+  }
+}
+
+
+
+/// The serialized form of a value, before the element containing it has been named.
+struct Value {
+  /// The name of an element containing this value when it is an item in a sequence.
+  type_name: Box<str>,
+  attributes: Vec<(Box<str>, Box<str>)>,
+  children: Vec<Node>
+}
+
+impl Value {
+  fn text(type_name: &str, text: impl ToString) -> Self {
+    Value { type_name: type_name.into(), attributes: Vec::new(), children: vec![Node::Text(text.to_string())] }
+  }
+
+  fn empty(type_name: &str) -> Self {
+    Value { type_name: type_name.into(), attributes: Vec::new(), children: Vec::new() }
+  }
+
+  fn into_element(self, name: impl Into<Box<str>>) -> Element {
+    Element::with_attributes(name, self.attributes.into(), self.children)
+  }
+
+  fn into_text(self) -> Result<String, Error> {
+    let element = self.into_element("");
+    if !element.attributes.is_empty() {
+      return Err(Error::Custom("expected a value that can be written as text".into()));
+    };
+
+    element.children.try_into_text().map_err(Error::unexpected_element_expected_text)
+  }
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+  type Ok = Value;
+  type Error = Error;
+  type SerializeSeq = Compound;
+  type SerializeTuple = Compound;
+  type SerializeTupleStruct = Compound;
+  type SerializeTupleVariant = Compound;
+  type SerializeMap = Compound;
+  type SerializeStruct = Compound;
+  type SerializeStructVariant = Compound;
+
+  fn serialize_bool(self, v: bool) -> Result<Value, Error> { Ok(Value::text("boolean", v)) }
+  fn serialize_i8(self, v: i8) -> Result<Value, Error> { Ok(Value::text("byte", v)) }
+  fn serialize_i16(self, v: i16) -> Result<Value, Error> { Ok(Value::text("short", v)) }
+  fn serialize_i32(self, v: i32) -> Result<Value, Error> { Ok(Value::text("int", v)) }
+  fn serialize_i64(self, v: i64) -> Result<Value, Error> { Ok(Value::text("long", v)) }
+  fn serialize_u8(self, v: u8) -> Result<Value, Error> { Ok(Value::text("unsignedByte", v)) }
+  fn serialize_u16(self, v: u16) -> Result<Value, Error> { Ok(Value::text("unsignedShort", v)) }
+  fn serialize_u32(self, v: u32) -> Result<Value, Error> { Ok(Value::text("unsignedInt", v)) }
+  fn serialize_u64(self, v: u64) -> Result<Value, Error> { Ok(Value::text("unsignedLong", v)) }
+  fn serialize_f32(self, v: f32) -> Result<Value, Error> { Ok(Value::text("float", v)) }
+  fn serialize_f64(self, v: f64) -> Result<Value, Error> { Ok(Value::text("double", v)) }
+  fn serialize_char(self, v: char) -> Result<Value, Error> { Ok(Value::text("char", v)) }
+  fn serialize_str(self, v: &str) -> Result<Value, Error> { Ok(Value::text("string", v)) }
+
+  fn serialize_bytes(self, _: &[u8]) -> Result<Value, Error> {
+    Err(Error::Custom("byte arrays are not supported".into()))
+  }
+
+  fn serialize_none(self) -> Result<Value, Error> {
+    let mut value = Value::empty("nil");
+    value.attributes.push(("xsi:nil".into(), "true".into()));
+    Ok(value)
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> Result<Value, Error> {
+    Ok(Value::empty("unit"))
+  }
+
+  fn serialize_unit_struct(self, name: &'static str) -> Result<Value, Error> {
+    Ok(Value::empty(name))
+  }
+
+  fn serialize_unit_variant(self, name: &'static str, _: u32, variant: &'static str) -> Result<Value, Error> {
+    Ok(Value::text(name, variant))
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _: &'static str, value: &T) -> Result<Value, Error> {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(self, name: &'static str, _: u32, variant: &'static str, value: &T) -> Result<Value, Error> {
+    let mut value = value.serialize(self)?;
+    value.type_name = name.into();
+    value.attributes.insert(0, ("xsi:type".into(), to_pascal_case(variant).into()));
+    Ok(value)
+  }
+
+  fn serialize_seq(self, _: Option<usize>) -> Result<Compound, Error> {
+    Ok(Compound::new("ArrayOf", None))
+  }
+
+  fn serialize_tuple(self, _: usize) -> Result<Compound, Error> {
+    Ok(Compound::new("ArrayOf", None))
+  }
+
+  fn serialize_tuple_struct(self, name: &'static str, _: usize) -> Result<Compound, Error> {
+    Ok(Compound::new(name, None))
+  }
+
+  fn serialize_tuple_variant(self, name: &'static str, _: u32, variant: &'static str, _: usize) -> Result<Compound, Error> {
+    Ok(Compound::new(name, Some(variant)))
+  }
+
+  fn serialize_map(self, _: Option<usize>) -> Result<Compound, Error> {
+    Ok(Compound::new("map", None))
+  }
+
+  fn serialize_struct(self, name: &'static str, _: usize) -> Result<Compound, Error> {
+    Ok(Compound::new(name, None))
+  }
+
+  fn serialize_struct_variant(self, name: &'static str, _: u32, variant: &'static str, _: usize) -> Result<Compound, Error> {
+    Ok(Compound::new(name, Some(variant)))
+  }
+}
+
+/// Builds up the fields or items of a struct, map, sequence or tuple.
+struct Compound {
+  value: Value,
+  key: Option<String>
+}
+
+impl Compound {
+  fn new(type_name: &str, variant: Option<&str>) -> Self {
+    let mut value = Value::empty(type_name);
+    if let Some(variant) = variant {
+      value.attributes.push(("xsi:type".into(), to_pascal_case(variant).into()));
+    };
+
+    Compound { value, key: None }
+  }
+
+  fn push_item<T: Serialize + ?Sized>(&mut self, item: &T) -> Result<(), Error> {
+    let item = item.serialize(ValueSerializer)?;
+    let name = item.type_name.clone();
+    self.value.children.push(Node::Element(item.into_element(name)));
+    Ok(())
+  }
+
+  fn push_field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+    let value = value.serialize(ValueSerializer)?;
+    if let Some(attribute) = key.strip_prefix('@') {
+      let text = value.into_text()?;
+      self.value.attributes.push((attribute.into(), text.into_boxed_str()));
+    } else {
+      self.value.children.push(Node::Element(value.into_element(key)));
+    };
+
+    Ok(())
+  }
+
+  /// Pushes a field of a struct, naming it in `PascalCase`.
+  /// A field named `type`, such as the tag of an internally tagged enum, is written as the `xsi:type` attribute.
+  fn push_struct_field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+    if key == "type" {
+      let variant = value.serialize(ValueSerializer)?.into_text()?;
+      self.value.attributes.insert(0, ("xsi:type".into(), to_pascal_case(&variant).into()));
+      Ok(())
+    } else {
+      self.push_field(&field_element_name(key), value)
+    }
+  }
+}
+
+impl ser::SerializeSeq for Compound {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+    self.push_item(value)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(self.value)
+  }
+}
+
+impl ser::SerializeTuple for Compound {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+    self.push_item(value)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(self.value)
+  }
+}
+
+impl ser::SerializeTupleStruct for Compound {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+    self.push_item(value)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(self.value)
+  }
+}
+
+impl ser::SerializeTupleVariant for Compound {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+    self.push_item(value)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(self.value)
+  }
+}
+
+impl ser::SerializeMap for Compound {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+    self.key = Some(key.serialize(ValueSerializer)?.into_text()?);
+    Ok(())
+  }
+
+  fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+    let key = self.key.take().ok_or_else(|| Error::Custom("map value serialized before its key".into()))?;
+    self.push_field(&key, value)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(self.value)
+  }
+}
+
+impl ser::SerializeStruct for Compound {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+    self.push_struct_field(key, value)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(self.value)
+  }
+}
+
+impl ser::SerializeStructVariant for Compound {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+    self.push_struct_field(key, value)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(self.value)
+  }
+}
+
+
+
+/// Deserializes a value from the contents of an element.
+pub struct ElementDeserializer {
+  element: Element
+}
+
+impl ElementDeserializer {
+  pub fn new(element: Element) -> Self {
+    ElementDeserializer { element }
+  }
+
+  fn into_text(self) -> Result<TextDeserializer, Error> {
+    self.element.children.try_into_text()
+      .map(TextDeserializer)
+      .map_err(Error::unexpected_element_expected_text)
+  }
+
+  fn has_child_elements(&self) -> bool {
+    self.element.children.iter().any(|node| matches!(node, Node::Element(..)))
+  }
+
+  /// Returns true if every child element has the same name, as the items of a sequence do.
+  fn has_uniform_child_elements(&self) -> bool {
+    let mut names = self.element.children.iter().filter_map(|node| match node {
+      Node::Element(element) => Some(&element.name),
+      Node::Text(..) => None
+    });
+
+    names.next().is_some_and(|first| names.all(|name| name == first))
+  }
+
+  fn has_attributes(&self) -> bool {
+    self.element.attributes.iter().any(|(name, _)| name.as_ref() == "xsi:type" || !is_reserved_attribute(name))
+  }
+
+  fn deserialize_fields<'de, V: Visitor<'de>>(self, keys: Keys, visitor: V) -> Result<V::Value, Error> {
+    let attributes = Vec::from(Box::<[_]>::from(self.element.attributes)).into_iter()
+      .filter_map(|(name, value)| match name.as_ref() {
+        "xsi:type" => keys.tag().then(|| ("type".to_owned(), to_snake_case(&value).into_boxed_str())),
+        _ if is_reserved_attribute(&name) => None,
+        _ => Some((keys.key(&format!("@{name}")), value))
+      })
+      .collect::<Vec<_>>();
+    let elements = self.element.children.try_into_elements_vec()?;
+    visitor.visit_map(FieldsAccess { attributes: attributes.into_iter(), elements: elements.into_iter(), keys, value: None })
+  }
+}
+
+macro_rules! forward_to_text {
+  ($($method:ident),* $(,)?) => {
+    $(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+      self.into_text()?.$method(visitor)
+    })*
+  };
+}
+
+impl<'de> de::Deserializer<'de> for ElementDeserializer {
+  type Error = Error;
+
+  /// Serde only asks for any value when it does not know what it expects, such as inside internally tagged enums,
+  /// so the element's contents are guessed at: elements whose children all share a name are read as sequences,
+  /// other elements with children or attributes are read as maps with `snake_case` keys, and empty elements as empty sequences.
+  fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    if self.element.is_nil() {
+      visitor.visit_none()
+    } else if self.has_attributes() || (self.has_child_elements() && !self.has_uniform_child_elements()) {
+      self.deserialize_fields(Keys::SnakeCase, visitor)
+    } else if self.has_child_elements() || self.element.children.is_empty() {
+      self.deserialize_seq(visitor)
+    } else {
+      self.into_text()?.deserialize_any(visitor)
+    }
+  }
+
+  forward_to_text! {
+    deserialize_bool,
+    deserialize_i8, deserialize_i16, deserialize_i32, deserialize_i64, deserialize_i128,
+    deserialize_u8, deserialize_u16, deserialize_u32, deserialize_u64, deserialize_u128,
+    deserialize_f32, deserialize_f64,
+    deserialize_char, deserialize_str, deserialize_string, deserialize_identifier
+  }
+
+  fn deserialize_bytes<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+    Err(Error::Custom("byte arrays are not supported".into()))
+  }
+
+  fn deserialize_byte_buf<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+    Err(Error::Custom("byte arrays are not supported".into()))
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    if self.element.is_nil() { visitor.visit_none() } else { visitor.visit_some(self) }
+  }
+
+  fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_unit()
+  }
+
+  fn deserialize_unit_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_unit()
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_newtype_struct(self)
+  }
+
+  fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    let elements = self.element.children.try_into_elements_vec()?;
+    visitor.visit_seq(ElementsAccess { elements: elements.into_iter() })
+  }
+
+  fn deserialize_tuple<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_seq(visitor)
+  }
+
+  fn deserialize_tuple_struct<V: Visitor<'de>>(self, _: &'static str, _: usize, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_seq(visitor)
+  }
+
+  fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_fields(Keys::Verbatim, visitor)
+  }
+
+  fn deserialize_struct<V: Visitor<'de>>(self, _: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_fields(Keys::Fields(fields), visitor)
+  }
+
+  fn deserialize_enum<V: Visitor<'de>>(self, _: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+    let mut element = self.element;
+    let variant = element.attributes.iter()
+      .position(|(name, _)| name.as_ref() == "xsi:type")
+      .map(|i| {
+        let mut attributes = Vec::from(Box::<[_]>::from(std::mem::take(&mut element.attributes)));
+        let (_, variant) = attributes.remove(i);
+        element.attributes = attributes.into();
+        variant
+      });
+
+    match variant {
+      Some(variant) => visitor.visit_enum(VariantAccess { variant: match_name(variants, &variant), element: Some(element) }),
+      None => {
+        let variant = ElementDeserializer::new(element).into_text()?.0;
+        visitor.visit_enum(VariantAccess { variant: match_name(variants, &variant), element: None })
+      }
+    }
+  }
+
+  fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_unit()
+  }
+}
+
+/// Deserializes a value from the text content of an element or from an attribute.
+struct TextDeserializer(String);
+
+impl TextDeserializer {
+  fn parse<T: FromStr>(self) -> Result<T, Error>
+  where T::Err: std::error::Error + Send + Sync + 'static {
+    self.0.parse::<T>().map_err(|err| Error::ParseError(Box::new(err), self.0))
+  }
+}
+
+macro_rules! deserialize_parse {
+  ($($method:ident => $visit:ident),* $(,)?) => {
+    $(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+      visitor.$visit(self.parse()?)
+    })*
+  };
+}
+
+impl<'de> de::Deserializer<'de> for TextDeserializer {
+  type Error = Error;
+
+  /// Text that looks like a boolean or a number is read as one, since serde does not say what it expects here.
+  fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    let is_number = self.0.bytes().any(|b| b.is_ascii_digit())
+      && self.0.bytes().all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'));
+    match self.0.as_str() {
+      "true" => visitor.visit_bool(true),
+      "false" => visitor.visit_bool(false),
+      text if is_number => {
+        if let Ok(v) = text.parse::<u64>() {
+          visitor.visit_u64(v)
+        } else if let Ok(v) = text.parse::<i64>() {
+          visitor.visit_i64(v)
+        } else if let Ok(v) = text.parse::<f64>() {
+          visitor.visit_f64(v)
+        } else {
+          visitor.visit_string(self.0)
+        }
+      },
+      _ => visitor.visit_string(self.0)
+    }
+  }
+
+  fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_string(self.0)
+  }
+
+  fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_string(self.0)
+  }
+
+  fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_string(self.0)
+  }
+
+  deserialize_parse! {
+    deserialize_bool => visit_bool,
+    deserialize_i8 => visit_i8, deserialize_i16 => visit_i16, deserialize_i32 => visit_i32,
+    deserialize_i64 => visit_i64, deserialize_i128 => visit_i128,
+    deserialize_u8 => visit_u8, deserialize_u16 => visit_u16, deserialize_u32 => visit_u32,
+    deserialize_u64 => visit_u64, deserialize_u128 => visit_u128,
+    deserialize_f32 => visit_f32, deserialize_f64 => visit_f64,
+    deserialize_char => visit_char
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_some(self)
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_newtype_struct(self)
+  }
+
+  fn deserialize_enum<V: Visitor<'de>>(self, _: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_enum(VariantAccess { variant: match_name(variants, &self.0), element: None })
+  }
+
+  ::serde::forward_to_deserialize_any! {
+    bytes byte_buf unit unit_struct seq tuple tuple_struct
+    map struct ignored_any
+  }
+}
+
+/// Gives the elements of a sequence to serde.
+struct ElementsAccess {
+  elements: VecIntoIter<Element>
+}
+
+impl<'de> de::SeqAccess<'de> for ElementsAccess {
+  type Error = Error;
+
+  fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+    self.elements.next().map(|element| deserialize_located(seed, element)).transpose()
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.elements.len())
+  }
+}
+
+/// Gives the attributes and child elements of a struct or map to serde,
+/// with each attribute's name prefixed with `@`.
+struct FieldsAccess {
+  attributes: VecIntoIter<(String, Box<str>)>,
+  elements: VecIntoIter<Element>,
+  keys: Keys,
+  value: Option<Result<Element, Box<str>>>
+}
+
+impl<'de> de::MapAccess<'de> for FieldsAccess {
+  type Error = Error;
+
+  fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+    let key = if let Some((key, value)) = self.attributes.next() {
+      self.value = Some(Err(value));
+      key
+    } else if let Some(element) = self.elements.next() {
+      let key = self.keys.key(&element.name);
+      self.value = Some(Ok(element));
+      key
+    } else {
+      return Ok(None);
+    };
+
+    seed.deserialize(StringDeserializer::<Error>::new(key)).map(Some)
+  }
+
+  fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+    match self.value.take() {
+      Some(Ok(element)) => deserialize_located(seed, element),
+      Some(Err(value)) => seed.deserialize(TextDeserializer(value.into_string())),
+      None => Err(Error::Custom("map value deserialized before its key".into()))
+    }
+  }
+}
+
+/// Gives the variant of an enum to serde, along with its fields if it was marked with `xsi:type`.
+struct VariantAccess {
+  variant: Box<str>,
+  element: Option<Element>
+}
+
+impl<'de> de::EnumAccess<'de> for VariantAccess {
+  type Error = Error;
+  type Variant = Self;
+
+  fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+    let variant = seed.deserialize(StringDeserializer::<Error>::new(self.variant.to_string()))?;
+    Ok((variant, self))
+  }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess {
+  type Error = Error;
+
+  fn unit_variant(self) -> Result<(), Error> {
+    Ok(())
+  }
+
+  fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+    seed.deserialize(ElementDeserializer::new(self.into_element()?))
+  }
+
+  fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, Error> {
+    de::Deserializer::deserialize_seq(ElementDeserializer::new(self.into_element()?), visitor)
+  }
+
+  fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+    ElementDeserializer::new(self.into_element()?).deserialize_fields(Keys::Fields(fields), visitor)
+  }
+}
+
+impl VariantAccess {
+  fn into_element(self) -> Result<Element, Error> {
+    self.element.ok_or_else(|| Error::Custom(format!("variant {:?} must be marked with xsi:type", self.variant).into_boxed_str()))
+  }
+}
+
+/// Deserializes a child element, attributing any error to its location.
+fn deserialize_located<'de, T: DeserializeSeed<'de>>(seed: T, element: Element) -> Result<T::Value, Error> {
+  element.located(|element| seed.deserialize(ElementDeserializer::new(element)))
+}
+
+/// How the names of attributes and child elements are given to serde as keys.
+#[derive(Debug, Clone, Copy)]
+enum Keys {
+  /// As they are, for maps.
+  Verbatim,
+  /// As the name of the struct field that they were written for.
+  Fields(&'static [&'static str]),
+  /// In `snake_case`, for values that serde reads without saying which fields it expects.
+  SnakeCase
+}
+
+impl Keys {
+  fn key(self, name: &str) -> String {
+    match self {
+      Keys::Verbatim => name.to_owned(),
+      Keys::Fields(fields) => match_name(fields, name).into_string(),
+      Keys::SnakeCase => match name.strip_prefix('@') {
+        Some(attribute) => format!("@{}", to_snake_case(attribute)),
+        None => to_snake_case(name)
+      }
+    }
+  }
+
+  /// Whether the `xsi:type` attribute is given to serde as a field named `type`.
+  fn tag(self) -> bool {
+    match self {
+      Keys::Verbatim => false,
+      Keys::Fields(fields) => fields.contains(&"type"),
+      Keys::SnakeCase => true
+    }
+  }
+}
+
+/// Finds the field or variant that was written with this name, or returns the name as it is if there is none.
+fn match_name(names: &[&str], name: &str) -> Box<str> {
+  names.iter().find(|&&candidate| candidate == name)
+    .or_else(|| names.iter().find(|&&candidate| field_element_name(candidate) == name))
+    .map_or_else(|| name.into(), |&candidate| candidate.into())
+}
+
+fn is_reserved_attribute(name: &str) -> bool {
+  name.starts_with("xsi:") || name.starts_with("xmlns")
+}
+
+/// The name that a struct field is written with, in `PascalCase` and keeping the `@` that marks attributes.
+/// Single-letter names are left as they are, since the game writes the components of Unity's vectors and colors in lowercase.
+fn field_element_name(key: &str) -> String {
+  match key.strip_prefix('@') {
+    Some(attribute) => format!("@{}", field_element_name(attribute)),
+    None if key.chars().nth(1).is_none() => key.to_owned(),
+    None => to_pascal_case(key)
+  }
+}
+
+/// Converts a `snake_case` name to `PascalCase`, leaving names that already are unchanged.
+fn to_pascal_case(name: &str) -> String {
+  let mut out = String::with_capacity(name.len());
+  let mut capitalize = true;
+  for ch in name.chars() {
+    if ch == '_' {
+      capitalize = true;
+    } else if capitalize {
+      out.extend(ch.to_uppercase());
+      capitalize = false;
+    } else {
+      out.push(ch);
+    };
+  };
+
+  out
+}
+
+/// Converts a `PascalCase` name to `snake_case` the same way serde's `rename_all = "snake_case"` does.
+fn to_snake_case(name: &str) -> String {
+  let mut out = String::with_capacity(name.len() + 4);
+  for (i, ch) in name.char_indices() {
+    if i > 0 && ch.is_uppercase() {
+      out.push('_');
+    };
+
+    out.extend(ch.to_lowercase());
+  };
+
+  out
+}
+
+
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+  use super::*;
+  use crate::{DeserializeElement, SerializeElement};
+
+  #[derive(Debug, Clone, PartialEq, DeserializeElement, SerializeElement, Serialize, Deserialize)]
+  #[xml(name = "Thing")]
+  #[serde(rename_all = "PascalCase")]
+  struct Thing {
+    #[xml(attribute)]
+    #[serde(rename = "@Name")]
+    name: String,
+    count: usize,
+    #[xml(nil)]
+    parent: Option<usize>,
+    #[xml(rename = "Tags", named_elements = "string")]
+    #[serde(rename = "Tags")]
+    tag_list: Vec<String>,
+    #[xml(element)]
+    shape: Shape
+  }
+
+  #[derive(Debug, Clone, PartialEq, DeserializeElement, SerializeElement, Serialize, Deserialize)]
+  #[xml(name = "Shape", tag = "xsi:type")]
+  enum Shape {
+    #[serde(rename_all = "PascalCase")]
+    Circle { radius: f32 },
+    #[serde(rename_all = "PascalCase")]
+    Square { side: f32 }
+  }
+
+  #[derive(Debug, Clone, PartialEq, DeserializeElement, SerializeElement, Serialize, Deserialize)]
+  #[xml(name = "Part", tag = "xsi:type")]
+  #[serde(rename_all = "snake_case", tag = "type")]
+  enum Part {
+    SolidPart {
+      mass_scale: f32,
+      is_armored: bool,
+      #[xml(named_elements = "unsignedLong")]
+      layer_sizes: Vec<u64>,
+      #[xml(nil)]
+      parent: Option<i32>
+    },
+    EmptyPart {
+      label: String
+    }
+  }
+
+  #[test]
+  fn test_serde_matches_derive() {
+    let things = [
+      Thing { name: "First".to_owned(), count: 3, parent: None, tag_list: Vec::new(), shape: Shape::Circle { radius: 1.5 } },
+      Thing { name: "Second".to_owned(), count: 0, parent: Some(1), tag_list: vec!["a".to_owned(), "b".to_owned()], shape: Shape::Square { side: 2.0 } }
+    ];
+
+    for thing in things {
+      let element = thing.clone().serialize_element().unwrap();
+      assert_eq!(to_element(&thing).unwrap(), element);
+      assert_eq!(from_element::<Thing>(element).unwrap(), thing);
+    };
+  }
+
+  #[test]
+  fn test_serde_tagged_matches_derive() {
+    let parts = [
+      Part::SolidPart { mass_scale: 1.5, is_armored: true, layer_sizes: Vec::new(), parent: None },
+      Part::SolidPart { mass_scale: 2.0, is_armored: false, layer_sizes: vec![3], parent: Some(-1) },
+      Part::SolidPart { mass_scale: -0.25, is_armored: false, layer_sizes: vec![4, 5], parent: Some(2) },
+      Part::EmptyPart { label: "Bow".to_owned() }
+    ];
+
+    for part in parts {
+      let element = part.clone().serialize_element().unwrap();
+      assert_eq!(to_element(&part).unwrap(), element);
+      assert_eq!(from_element::<Part>(element).unwrap(), part);
+    };
+  }
+
+  #[test]
+  fn test_serde_errors() {
+    let nodes = read_nodes(r#"<Thing Name="Third"><Count>x</Count><Parent xsi:nil="true" /><Tags /><Shape xsi:type="Circle"><Radius>1</Radius></Shape></Thing>"#.as_bytes()).unwrap();
+    let error = from_element::<Thing>(nodes.try_into_one_element().unwrap()).unwrap_err();
+    assert_eq!(error.location().unwrap().path.as_ref(), "Thing/Count");
+    assert!(matches!(error.unlocated(), Error::ParseError(_, text) if text == "x"));
+
+    let nodes = read_nodes(r#"<Thing Name="Third"><Count>1</Count><Tags /><Shape xsi:type="Triangle" /></Thing>"#.as_bytes()).unwrap();
+    let error = Serde::<Thing>::deserialize_element(nodes.try_into_one_element().unwrap()).unwrap_err();
+    assert_eq!(error.location().unwrap().path.as_ref(), "Thing/Shape");
+  }
+}